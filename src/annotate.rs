@@ -0,0 +1,154 @@
+//! Per-entry annotations produced by running an external command
+//!
+//! The command is run through `sh -c` once per entry with `{}` standing for the
+//! entry's path, quoted or not. The first line the command prints becomes the
+//! annotation. Commands run on a bounded number of threads and are killed,
+//! with anything they started, if they take longer than [`TIMEOUT`] to print a
+//! line.
+use std::{
+    io::Read,
+    os::{fd::AsRawFd, unix::process::CommandExt},
+    process::{Child, Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::EntryData;
+
+/// How long a single command may run before it is killed
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Annotation shown when a command fails to produce output in time
+const PLACEHOLDER: &str = "?";
+
+/// Run `template` for every entry, returning the annotations in the same order
+pub fn annotate(entries: &[EntryData], template: &str) -> Vec<String> {
+    let script = script(template);
+    let jobs = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(entries.len().max(1));
+    let next = AtomicUsize::new(0);
+    let mut annotations = vec![String::new(); entries.len()];
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        match entries.get(idx) {
+                            Some(entry) => done.push((idx, run(&script, entry))),
+                            None => break done,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            for (idx, annotation) in worker.join().unwrap_or_default() {
+                annotations[idx] = annotation;
+            }
+        }
+    });
+
+    annotations
+}
+
+/// The shell script running `template`, which takes the path as its first parameter
+/// The path is never part of the script, so it needs no quoting however it is named. Each `{}`
+/// becomes `"$1"`, leaving the quotes it was written in so that `'{}'` works as `{}` does.
+fn script(template: &str) -> String {
+    let mut script = String::with_capacity(template.len());
+    let mut quote: Option<char> = None;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '{' && chars.peek() == Some(&'}') {
+            chars.next();
+            script.push_str(match quote {
+                Some('\'') => "'\"$1\"'",
+                Some(_) => "$1",
+                None => "\"$1\"",
+            });
+            continue;
+        }
+        script.push(c);
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(open), _) if open == c => quote = None,
+            // a backslash escapes the next character outside of single quotes
+            (None | Some('"'), '\\') => script.extend(chars.next()),
+            _ => {}
+        }
+    }
+    script
+}
+
+fn run(script: &str, entry: &EntryData) -> String {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(script)
+        .arg("listare")
+        .arg(&entry.path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        // a group of its own, to stop whatever it starts along with it
+        .process_group(0)
+        .spawn();
+
+    match child {
+        Ok(child) => first_line(child).unwrap_or_else(|| PLACEHOLDER.to_string()),
+        Err(_) => PLACEHOLDER.to_string(),
+    }
+}
+
+/// Read the first line of output from the child, giving up if it takes too long
+fn first_line(mut child: Child) -> Option<String> {
+    let mut stdout = child.stdout.take()?;
+    let deadline = Instant::now() + TIMEOUT;
+    let mut output = Vec::new();
+    let mut buf = [0u8; 256];
+    let complete = loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let mut poll_fd = libc::pollfd { fd: stdout.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        if remaining.is_zero() || unsafe { libc::poll(&mut poll_fd, 1, remaining.as_millis() as i32) } <= 0 {
+            break false;
+        }
+        match stdout.read(&mut buf) {
+            Ok(0) => break true,
+            Ok(count) => output.extend_from_slice(&buf[..count]),
+            Err(_) => break false,
+        }
+        if output.contains(&b'\n') {
+            break true;
+        }
+    };
+
+    // only the first line is needed, so the command and anything it started can be stopped;
+    // the child is not reaped yet, so its process group cannot have been reused
+    unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) };
+    let _ = child.wait();
+
+    let line = output.split(|&b| b == b'\n').next().unwrap_or_default();
+    complete.then(|| String::from_utf8_lossy(line).trim_end_matches('\r').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::script;
+
+    #[test]
+    fn passes_the_path_however_the_placeholder_is_quoted() {
+        assert_eq!(script("file {}"), "file \"$1\"");
+        assert_eq!(script("file '{}'"), "file ''\"$1\"''");
+        assert_eq!(script("file \"{}\""), "file \"$1\"");
+        assert_eq!(script("stat -c '%s {}.bak' {}"), "stat -c '%s '\"$1\"'.bak' \"$1\"");
+        // escaped quotes open nothing
+        assert_eq!(script("echo \\' {}"), "echo \\' \"$1\"");
+        assert_eq!(script("echo \"a\\\"{}\""), "echo \"a\\\"$1\"");
+        assert_eq!(script("echo {"), "echo {");
+    }
+}
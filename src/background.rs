@@ -0,0 +1,85 @@
+//! Detection of the terminal's background, to keep the default colors readable
+//!
+//! The background is taken from `COLORFGBG`, which some terminals set, and
+//! otherwise asked of the terminal with the OSC 11 escape sequence. Terminals
+//! that do not answer within a short time are assumed to be dark.
+use std::time::Duration;
+
+/// The brightness of the terminal background
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Background {
+    Light,
+    Dark,
+}
+
+/// How long to wait for the terminal to report its background color
+const QUERY_TIMEOUT: Duration = Duration::from_millis(100);
+
+impl Background {
+    /// Detect the background of the terminal on stdout
+    pub fn detect() -> Background {
+        if let Some(background) = std::env::var("COLORFGBG").ok().and_then(|v| from_colorfgbg(&v)) {
+            return background;
+        }
+        crate::posix::query_background_color(QUERY_TIMEOUT)
+            .and_then(|response| from_osc11_response(&response))
+            .unwrap_or(Background::Dark)
+    }
+}
+
+/// Parse `COLORFGBG`, e.g. `15;0` or `0;default;15`, whose last field is the background color index
+fn from_colorfgbg(value: &str) -> Option<Background> {
+    let index: u8 = value.rsplit(';').next()?.parse().ok()?;
+    // white and the bright colors other than bright black
+    match index {
+        7 | 9..=15 => Some(Background::Light),
+        0..=6 | 8 => Some(Background::Dark),
+        _ => None,
+    }
+}
+
+/// Parse a reply to the OSC 11 query, e.g. `\x1b]11;rgb:ffff/ffff/dddd\x07`
+fn from_osc11_response(response: &[u8]) -> Option<Background> {
+    let response = std::str::from_utf8(response).ok()?;
+    let rgb = &response[response.find("rgb:")? + "rgb:".len()..];
+    let rgb = rgb.trim_end_matches(['\x07', '\\', '\x1b']);
+
+    // each component has 1 to 4 hex digits, scaled here to 0..1
+    let mut components = rgb.split('/').map(|hex| {
+        let digits = hex.get(..hex.len().min(4))?;
+        let value = u32::from_str_radix(digits, 16).ok()?;
+        let max = (1u32 << (4 * digits.len() as u32)) - 1;
+        Some(value as f64 / max as f64)
+    });
+    let (r, g, b) = (components.next()??, components.next()??, components.next()??);
+
+    // relative luminance, with the sRGB gamma ignored
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    Some(if luminance > 0.5 { Background::Light } else { Background::Dark })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_colorfgbg() {
+        assert_eq!(from_colorfgbg("15;0"), Some(Background::Dark));
+        assert_eq!(from_colorfgbg("0;15"), Some(Background::Light));
+        assert_eq!(from_colorfgbg("0;default;7"), Some(Background::Light));
+        assert_eq!(from_colorfgbg("7;8"), Some(Background::Dark));
+        assert_eq!(from_colorfgbg("15;default"), None);
+        assert_eq!(from_colorfgbg(""), None);
+    }
+
+    #[test]
+    fn parses_osc11_responses() {
+        assert_eq!(from_osc11_response(b"\x1b]11;rgb:ffff/ffff/ffff\x07"), Some(Background::Light));
+        assert_eq!(from_osc11_response(b"\x1b]11;rgb:0000/0000/0000\x1b\\"), Some(Background::Dark));
+        assert_eq!(from_osc11_response(b"\x1b]11;rgb:fd/f6/e3\x07"), Some(Background::Light));
+        assert_eq!(from_osc11_response(b"\x1b]11;rgb:28/2c/34\x07"), Some(Background::Dark));
+        assert_eq!(from_osc11_response(b"\x1b]11;rgb:f/f/f\x07"), Some(Background::Light));
+        assert_eq!(from_osc11_response(b"garbage"), None);
+        assert_eq!(from_osc11_response(b"\x1b]11;rgb:zz/00/00\x07"), None);
+    }
+}
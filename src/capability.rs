@@ -0,0 +1,161 @@
+//! File capabilities, decoded from the `security.capability` attribute
+//!
+//! They are written the way getcap(8) writes them, e.g. `cap_net_bind_service+ep`,
+//! with capabilities that have the same flags grouped as in
+//! `cap_net_admin,cap_net_raw+ep cap_sys_time+i`.
+use std::path::Path;
+
+use crate::xattr;
+
+/// The names of the capabilities, by number
+const NAMES: [&str; 41] = [
+    "chown",
+    "dac_override",
+    "dac_read_search",
+    "fowner",
+    "fsetid",
+    "kill",
+    "setgid",
+    "setuid",
+    "setpcap",
+    "linux_immutable",
+    "net_bind_service",
+    "net_broadcast",
+    "net_admin",
+    "net_raw",
+    "ipc_lock",
+    "ipc_owner",
+    "sys_module",
+    "sys_rawio",
+    "sys_chroot",
+    "sys_ptrace",
+    "sys_pacct",
+    "sys_admin",
+    "sys_boot",
+    "sys_nice",
+    "sys_resource",
+    "sys_time",
+    "sys_tty_config",
+    "mknod",
+    "lease",
+    "audit_write",
+    "audit_control",
+    "setfcap",
+    "mac_override",
+    "mac_admin",
+    "syslog",
+    "wake_alarm",
+    "block_suspend",
+    "audit_read",
+    "perfmon",
+    "bpf",
+    "checkpoint_restore",
+];
+
+const REVISION_MASK: u32 = 0xff00_0000;
+const REVISION_1: u32 = 0x0100_0000; // one 32 bit word per set
+const REVISION_2: u32 = 0x0200_0000; // two words per set
+const REVISION_3: u32 = 0x0300_0000; // two words per set and the root uid of a namespace
+const FLAG_EFFECTIVE: u32 = 0x0000_0001;
+
+fn name(number: usize) -> String {
+    match NAMES.get(number) {
+        Some(name) => format!("cap_{}", name),
+        None => format!("cap_{}", number),
+    }
+}
+
+fn word(value: &[u8], index: usize) -> Option<u32> {
+    let bytes = value.get(index * 4..index * 4 + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// The text of a `security.capability` value, None if it holds no capabilities
+/// or is not a value the kernel would accept
+fn decode(value: &[u8]) -> Option<String> {
+    let magic = word(value, 0)?;
+    let (words, len) = match magic & REVISION_MASK {
+        REVISION_1 => (1, 12),
+        REVISION_2 => (2, 20),
+        REVISION_3 => (2, 24),
+        _ => return None,
+    };
+    if value.len() != len {
+        return None;
+    }
+    let effective = magic & FLAG_EFFECTIVE != 0;
+
+    // the flags of every capability that has any, grouped by flags in order of the first capability
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for number in 0..32 * words {
+        let bit = 1 << (number % 32);
+        let permitted = word(value, 1 + 2 * (number / 32))? & bit != 0;
+        let inheritable = word(value, 2 + 2 * (number / 32))? & bit != 0;
+        if !permitted && !inheritable {
+            continue;
+        }
+
+        let mut flags = String::new();
+        if effective {
+            flags.push('e');
+        }
+        if inheritable {
+            flags.push('i');
+        }
+        if permitted {
+            flags.push('p');
+        }
+        match groups.iter_mut().find(|(f, _)| *f == flags) {
+            Some((_, names)) => names.push(name(number)),
+            None => groups.push((flags, vec![name(number)])),
+        }
+    }
+
+    if groups.is_empty() {
+        return None;
+    }
+    let groups: Vec<String> = groups
+        .into_iter()
+        .map(|(flags, names)| format!("{}+{}", names.join(","), flags))
+        .collect();
+    Some(groups.join(" "))
+}
+
+/// The capabilities of a file, None if it has none
+pub(crate) fn capabilities(path: &Path) -> Option<String> {
+    decode(&xattr::get(path, xattr::CAPABILITY_NAME).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode;
+
+    fn value(magic: u32, words: &[u32]) -> Vec<u8> {
+        std::iter::once(magic).chain(words.iter().copied()).flat_map(u32::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn decodes_capabilities() {
+        // cap_net_bind_service is number 10
+        let bind = value(0x0200_0001, &[1 << 10, 0, 0, 0]);
+        assert_eq!(decode(&bind).as_deref(), Some("cap_net_bind_service+ep"));
+
+        let grouped = value(0x0200_0000, &[1 << 12 | 1 << 13, 1 << 25, 0, 0]);
+        assert_eq!(decode(&grouped).as_deref(), Some("cap_net_admin,cap_net_raw+p cap_sys_time+i"));
+
+        // cap_bpf is number 39, in the second word
+        let bpf = value(0x0300_0001, &[0, 0, 1 << 7, 0, 0]);
+        assert_eq!(decode(&bpf).as_deref(), Some("cap_bpf+ep"));
+
+        let old = value(0x0100_0000, &[1 << 7, 1 << 7]);
+        assert_eq!(decode(&old).as_deref(), Some("cap_setuid+ip"));
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        assert_eq!(decode(&[]), None);
+        assert_eq!(decode(&value(0x0200_0001, &[1])), None);
+        assert_eq!(decode(&value(0x0400_0001, &[1, 0, 0, 0])), None);
+        assert_eq!(decode(&value(0x0200_0001, &[0, 0, 0, 0])), None);
+    }
+}
@@ -0,0 +1,305 @@
+//! Grid cells, composed of segments around the name of an entry
+//!
+//! A cell holds right aligned columns before the name, such as the inode
+//! number, and markers after it. Its width is the sum of the display widths of
+//! those segments, so the tabulator lays cells out without knowing what they
+//! hold, and every segment goes through the same control character hiding and
+//! width measurement as the name.
+use std::fmt::{self, Display};
+use std::os::unix::fs::MetadataExt;
+
+use crate::{controls, hyperlink, longformat, size, tabulate, width, Arguments, EntryData, Theme};
+
+/// An entry as shown in the grid
+pub(crate) struct Cell<'a> {
+    entry: &'a EntryData,
+    theme: &'a Theme,
+    name: String, // as shown, control characters hidden if asked
+    prefix: String,
+    suffix: String,
+    uri: Option<String>, // what the name links to
+    width: usize,        // columns taken by every segment
+}
+
+impl Display for Cell<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = hyperlink::Linked(self.uri.as_deref(), self.theme.paint(self.entry, &self.name));
+        write!(f, "{}{}{}", self.prefix, name, self.suffix)?;
+        crate::pad(f, self.width)
+    }
+}
+
+impl Cell<'_> {
+    /// Cut the name so that the cell takes at most `max_width` columns, ending it with `…`
+    /// Whatever else the cell holds is kept, and at least the ellipsis is left of the name.
+    pub(crate) fn fit(&mut self, max_width: usize, ambiguous_wide: bool) {
+        if self.width <= max_width {
+            return;
+        }
+        let others = self.width - width::display_width(&self.name, ambiguous_wide);
+        self.name = width::truncate(&self.name, max_width.saturating_sub(others).max(1), ambiguous_wide);
+        self.width = others + width::display_width(&self.name, ambiguous_wide);
+    }
+}
+
+impl tabulate::CharacterLength for Cell<'_> {
+    fn characters_long(&self) -> usize {
+        self.width
+    }
+}
+
+/// Composes a [`Cell`] segment by segment
+pub(crate) struct CellBuilder<'a> {
+    cell: Cell<'a>,
+    hide_control_chars: bool,
+    ambiguous_wide: bool,
+}
+
+impl<'a> CellBuilder<'a> {
+    pub(crate) fn new(entry: &'a EntryData, theme: &'a Theme, hide_control_chars: bool, ambiguous_wide: bool) -> Self {
+        let name = controls::hide(&entry.name, hide_control_chars).into_owned();
+        let width = width::display_width(&name, ambiguous_wide);
+        CellBuilder {
+            cell: Cell { entry, theme, name, prefix: String::new(), suffix: String::new(), uri: None, width },
+            hide_control_chars,
+            ambiguous_wide,
+        }
+    }
+
+    fn measure(&self, text: &str) -> (String, usize) {
+        let text = controls::hide(text, self.hide_control_chars).into_owned();
+        let width = width::display_width(&text, self.ambiguous_wide);
+        (text, width)
+    }
+
+    /// Add a column before the name, right aligned within `column_width` and followed by a space
+    pub(crate) fn column(mut self, text: &str, column_width: usize) -> Self {
+        let (text, width) = self.measure(text);
+        let padding = column_width.saturating_sub(width);
+        self.cell.prefix.push_str(&format!("{:padding$}{} ", "", text, padding = padding));
+        self.cell.width += padding + width + 1;
+        self
+    }
+
+    /// Add an icon right before the name
+    pub(crate) fn icon(mut self, icon: &str) -> Self {
+        let (text, width) = self.measure(icon);
+        self.cell.prefix.push_str(&text);
+        self.cell.width += width;
+        self
+    }
+
+    /// Add a marker after the name
+    pub(crate) fn suffix(mut self, text: &str) -> Self {
+        let (text, width) = self.measure(text);
+        self.cell.suffix.push_str(&text);
+        self.cell.width += width;
+        self
+    }
+
+    /// Link the name to the file it names, taking no columns
+    pub(crate) fn hyperlink(mut self) -> Self {
+        self.cell.uri = Some(hyperlink::uri(&self.cell.entry.path));
+        self
+    }
+
+    pub(crate) fn build(self) -> Cell<'a> {
+        self.cell
+    }
+}
+
+/// The columns taken by the name of an entry with its icon and markers, as a cell shows them
+/// The columns before names are as wide for every entry, and are left out.
+pub(crate) fn name_width(args: &Arguments) -> impl Fn(&EntryData) -> usize + Sync + '_ {
+    let icons = args.icons.as_ref();
+    let (hide_control_chars, ambiguous_wide) = (args.hide_control_chars, args.ambiguous_wide);
+    let (indicator_style, warn_insecure) = (args.indicator_style, args.warn_insecure);
+    move |entry| {
+        let measure = |text: &str| width::display_width(&controls::hide(text, hide_control_chars), ambiguous_wide);
+        let icon = icons.map_or(0, |icons| measure(&icons.prefix(entry)));
+        icon + measure(&entry.name) + measure(&entry.markers(indicator_style, warn_insecure))
+    }
+}
+
+/// Build the grid cells, right aligning the inode, block count and context columns that prefix names
+pub(crate) fn grid_cells<'a>(entries: &'a [EntryData], args: &'a Arguments) -> Vec<Cell<'a>> {
+    let mut columns: Vec<Vec<String>> = Vec::new();
+    if args.show_inode {
+        columns.push(entries.iter().map(|e| e.metadata.ino().to_string()).collect());
+    }
+    if args.show_blocks {
+        columns.push(
+            entries
+                .iter()
+                .map(|e| size::format_blocks(e.metadata.blocks(), args.block_format))
+                .collect(),
+        );
+    }
+    if args.show_context {
+        columns.push(entries.iter().map(|e| longformat::context_text(&e.path)).collect());
+    }
+    let widths: Vec<usize> = columns
+        .iter()
+        .map(|column| {
+            column
+                .iter()
+                .map(|text| width::display_width(&controls::hide(text, args.hide_control_chars), args.ambiguous_wide))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let mut cell = CellBuilder::new(entry, &args.theme, args.hide_control_chars, args.ambiguous_wide);
+            for (column, &width) in columns.iter().zip(&widths) {
+                cell = cell.column(&column[i], width);
+            }
+            if let Some(icons) = &args.icons {
+                cell = cell.icon(&icons.prefix(entry));
+            }
+            if args.hyperlink {
+                cell = cell.hyperlink();
+            }
+            cell.suffix(&entry.suffix(args)).build()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CellBuilder;
+    use crate::{controls, tabulate::CharacterLength, width, Background, EntryData, Icons, Palette, Theme};
+
+    fn theme() -> Theme {
+        Theme { background: Background::Dark, rules: Vec::new(), uid: 0, palette: Palette::default(), ls_colors: None }
+    }
+
+    /// Colors forced on until dropped
+    /// Whether to color is a setting of the whole process, so the tests that
+    /// force it take turns, and leave it as they found it for the others.
+    struct ColorsOn {
+        _turn: std::sync::MutexGuard<'static, ()>,
+    }
+
+    impl ColorsOn {
+        fn new() -> Self {
+            static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+            let guard = LOCK.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+            colored::control::set_override(true);
+            ColorsOn { _turn: guard }
+        }
+    }
+
+    impl Drop for ColorsOn {
+        fn drop(&mut self) {
+            colored::control::unset_override();
+        }
+    }
+
+    /// `text` without its SGR and OSC 8 escape sequences, as a terminal shows it
+    fn visible(text: &str) -> String {
+        let mut shown = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match (c, chars.clone().next()) {
+                ('\x1b', Some('[')) => {
+                    chars.by_ref().find(|&c| c == 'm');
+                }
+                // an OSC 8 sequence ends with ESC and a backslash
+                ('\x1b', Some(']')) => {
+                    chars.by_ref().find(|&c| c == '\x1b');
+                    chars.next();
+                }
+                _ => shown.push(c),
+            }
+        }
+        shown
+    }
+
+    #[test]
+    fn reports_the_width_of_every_combination_of_segments() -> std::io::Result<()> {
+        // directories are colored, so the escape sequences must not count
+        let _colors = ColorsOn::new();
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("notes"))?;
+        let entry = EntryData::from_path(dir.path().join("notes"))?;
+        let theme = theme();
+        let icon = Icons::default().prefix(&entry);
+        let columns: [&[&str]; 4] = [&[], &["12345"], &["7", "日本"], &["a\u{1b}b", "§", "unconfined"]];
+        let suffixes = ["", "!", "/", "\u{7}"];
+
+        for toggles in 0..16 {
+            let [hide_control_chars, ambiguous_wide, with_icon, with_link] = [1, 2, 4, 8].map(|bit| toggles & bit != 0);
+            for texts in columns {
+                for suffix in suffixes {
+                    let mut builder = CellBuilder::new(&entry, &theme, hide_control_chars, ambiguous_wide);
+                    for text in texts {
+                        builder = builder.column(text, 6);
+                    }
+                    if with_icon {
+                        builder = builder.icon(&icon);
+                    }
+                    if with_link {
+                        builder = builder.hyperlink();
+                    }
+                    let cell = builder.suffix(suffix).build();
+                    let shown = cell.to_string();
+                    assert!(shown.contains("\x1b[1;34m"), "{:?}", shown);
+                    assert_eq!(shown.contains("\x1b]8;;file://"), with_link, "{:?}", shown);
+
+                    // every column is padded to 6 and followed by a space
+                    let columns: usize = texts
+                        .iter()
+                        .map(|text| width::display_width(&controls::hide(text, hide_control_chars), ambiguous_wide).max(6) + 1)
+                        .sum();
+                    let icon_width = if with_icon { width::display_width(&icon, ambiguous_wide) } else { 0 };
+                    let expected =
+                        columns + icon_width + "notes".len() + width::display_width(&cell.suffix, ambiguous_wide);
+                    assert_eq!(cell.characters_long(), expected, "{:?}", shown);
+                    if hide_control_chars {
+                        let shown = visible(&shown);
+                        assert_eq!(width::display_width(&shown, ambiguous_wide), expected, "{:?}", shown);
+                        assert!(!shown.chars().any(char::is_control), "{:?}", shown);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn cuts_names_to_fit() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("日本語のノート"), "")?;
+        let entry = EntryData::from_path(dir.path().join("日本語のノート"))?;
+        let theme = theme();
+        let cell = || CellBuilder::new(&entry, &theme, true, false).column("7", 2).suffix("*");
+
+        let mut fitted = cell().build();
+        fitted.fit(20, false);
+        assert_eq!(visible(&fitted.to_string()), " 7 日本語のノート*");
+        // the wide character that would take the last column is dropped whole
+        fitted.fit(10, false);
+        assert_eq!(visible(&fitted.to_string()), " 7 日本…*");
+        assert_eq!(fitted.characters_long(), 9);
+        let mut fitted = cell().build();
+        fitted.fit(2, false);
+        assert_eq!(visible(&fitted.to_string()), " 7 …*");
+        Ok(())
+    }
+
+    #[test]
+    fn pads_to_the_width_asked_for() -> std::io::Result<()> {
+        let _colors = ColorsOn::new();
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("notes"), "")?;
+        let entry = EntryData::from_path(dir.path().join("notes"))?;
+        let theme = theme();
+        let cell = CellBuilder::new(&entry, &theme, true, false).column("日本", 5).suffix("!").build();
+        assert_eq!(format!("{:14}|", cell), " 日本 notes!  |");
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+//! Picking entries on the terminal for `--choose`
+//!
+//! The entries are drawn on the controlling terminal, so that stdout can be
+//! captured as in `vim $(listare --choose)`. Up and down (or `k` and `j`) move
+//! the cursor, space marks the entry under it and moves on, and Enter prints
+//! the paths of the marked entries, or of the entry under the cursor when none
+//! is marked. `q`, Escape and Ctrl-C back out without printing anything.
+use std::io::{Read, Write};
+
+use crate::messages::{self, Message};
+use crate::{controls, io_error_text, posix, EntryData, ListareError};
+
+/// A key that means something to the picker
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    Mark,
+    Enter,
+    Quit,
+}
+
+/// The key read as `bytes`, None for keys the picker ignores
+fn key(bytes: &[u8]) -> Option<Key> {
+    match bytes {
+        b"\x1b[A" | b"\x1bOA" | b"k" => Some(Key::Up),
+        b"\x1b[B" | b"\x1bOB" | b"j" => Some(Key::Down),
+        b" " => Some(Key::Mark),
+        b"\r" | b"\n" => Some(Key::Enter),
+        b"q" | b"\x1b" | b"\x03" => Some(Key::Quit),
+        _ => None,
+    }
+}
+
+/// Where the user is in the list, and what they marked
+#[derive(Debug)]
+struct Picker {
+    cursor: usize,
+    marked: Vec<bool>,
+    top: usize, // the first entry on screen
+}
+
+impl Picker {
+    fn new(len: usize) -> Picker {
+        Picker { cursor: 0, marked: vec![false; len], top: 0 }
+    }
+
+    /// Act on a key, returning the chosen indices once Enter is pressed,
+    /// or an empty list if the user quit
+    fn handle(&mut self, key: Key) -> Option<Vec<usize>> {
+        let last = self.marked.len().saturating_sub(1);
+        match key {
+            Key::Up => self.cursor = self.cursor.saturating_sub(1),
+            Key::Down => self.cursor = (self.cursor + 1).min(last),
+            Key::Mark => {
+                if let Some(marked) = self.marked.get_mut(self.cursor) {
+                    *marked = !*marked;
+                }
+                self.cursor = (self.cursor + 1).min(last);
+            }
+            Key::Enter => {
+                let chosen: Vec<usize> = (0..self.marked.len()).filter(|&i| self.marked[i]).collect();
+                return Some(if chosen.is_empty() && !self.marked.is_empty() { vec![self.cursor] } else { chosen });
+            }
+            Key::Quit => return Some(Vec::new()),
+        }
+        None
+    }
+
+    /// Scroll so that the cursor is on a screen of `rows` lines
+    fn scroll(&mut self, rows: usize) {
+        let rows = rows.max(1);
+        if self.cursor < self.top {
+            self.top = self.cursor;
+        } else if self.cursor >= self.top + rows {
+            self.top = self.cursor + 1 - rows;
+        }
+    }
+}
+
+/// Draw the entries from the top of the picker, the one under the cursor in reverse video
+fn draw(tty: &mut impl Write, entries: &[EntryData], picker: &Picker, rows: usize) -> std::io::Result<()> {
+    write!(tty, "\x1b[H\x1b[2J")?;
+    for (i, entry) in entries.iter().enumerate().skip(picker.top).take(rows) {
+        let mark = if picker.marked[i] { '*' } else { ' ' };
+        let name = controls::hide(&entry.name, true);
+        if i == picker.cursor {
+            write!(tty, "\x1b[7m{} {}\x1b[0m\r\n", mark, name)?;
+        } else {
+            write!(tty, "{} {}\r\n", mark, name)?;
+        }
+    }
+    tty.flush()
+}
+
+/// Let the user pick among `entries` on the terminal, returning those chosen
+pub(crate) fn choose(entries: &[EntryData]) -> Result<Vec<&EntryData>, ListareError> {
+    if entries.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut terminal = posix::RawTerminal::open()
+        .ok_or_else(|| ListareError::Generic(format!("listare: {}", messages::text(Message::NeedsTerminal))))?;
+    let rows = terminal.size().map_or(24, |size| size.rows);
+
+    // the alternate screen keeps the picker out of the terminal's scrollback
+    let tty = &mut terminal.tty;
+    let mut picker = Picker::new(entries.len());
+    let io_error = |e: std::io::Error| ListareError::Generic(format!("listare: terminal: {}", io_error_text(&e)));
+    write!(tty, "\x1b[?1049h\x1b[?25l").map_err(io_error)?;
+    let chosen = loop {
+        picker.scroll(rows);
+        if let Err(e) = draw(tty, entries, &picker, rows) {
+            break Err(io_error(e));
+        }
+        let mut buf = [0u8; 8];
+        let count = match tty.read(&mut buf) {
+            Ok(0) => break Ok(Vec::new()),
+            Ok(count) => count,
+            Err(e) => break Err(io_error(e)),
+        };
+        if let Some(chosen) = key(&buf[..count]).and_then(|key| picker.handle(key)) {
+            break Ok(chosen);
+        }
+    };
+    let _ = write!(tty, "\x1b[?25h\x1b[?1049l").and_then(|_| tty.flush());
+
+    match chosen? {
+        chosen if chosen.is_empty() => Err(ListareError::Cancelled),
+        chosen => Ok(chosen.into_iter().filter_map(|i| entries.get(i)).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{key, Key, Picker};
+
+    #[test]
+    fn reads_keys() {
+        assert_eq!(key(b"\x1b[A"), Some(Key::Up));
+        assert_eq!(key(b"j"), Some(Key::Down));
+        assert_eq!(key(b" "), Some(Key::Mark));
+        assert_eq!(key(b"\r"), Some(Key::Enter));
+        assert_eq!(key(b"\x1b"), Some(Key::Quit));
+        assert_eq!(key(b"x"), None);
+        assert_eq!(key(b"\x1b[C"), None);
+    }
+
+    #[test]
+    fn marks_and_chooses_entries() {
+        let mut picker = Picker::new(3);
+        assert_eq!(picker.handle(Key::Up), None);
+        assert_eq!(picker.handle(Key::Mark), None);
+        assert_eq!(picker.handle(Key::Down), None);
+        assert_eq!(picker.handle(Key::Mark), None);
+        assert_eq!(picker.cursor, 2);
+        assert_eq!(picker.handle(Key::Down), None);
+        assert_eq!(picker.cursor, 2);
+        assert_eq!(picker.handle(Key::Enter), Some(vec![0, 2]));
+    }
+
+    #[test]
+    fn chooses_the_entry_under_the_cursor_when_none_is_marked() {
+        let mut picker = Picker::new(3);
+        picker.handle(Key::Down);
+        assert_eq!(picker.handle(Key::Enter), Some(vec![1]));
+        assert_eq!(picker.handle(Key::Quit), Some(vec![]));
+    }
+
+    #[test]
+    fn scrolls_to_the_cursor() {
+        let mut picker = Picker::new(10);
+        for _ in 0..6 {
+            picker.handle(Key::Down);
+        }
+        picker.scroll(4);
+        assert_eq!(picker.top, 3);
+        picker.cursor = 1;
+        picker.scroll(4);
+        assert_eq!(picker.top, 1);
+    }
+}
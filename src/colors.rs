@@ -0,0 +1,173 @@
+//! The built-in colors of files by the extensions of their names
+//!
+//! Like the defaults of dircolors, archives are red, images and videos
+//! magenta and audio cyan, so that a listing looks as it does with GNU ls
+//! out of the box. Source code is yellow, and backups and temporary files are
+//! dimmed. The colors only apply to regular files, and only when `LS_COLORS`
+//! is not set, since a user who set it has chosen their own colors.
+use colored::{ColoredString, Colorize};
+
+use crate::Background;
+
+/// A kind of file recognized by its extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Kind {
+    Archive,
+    Image,
+    Video,
+    Audio,
+    Source,
+    Temporary,
+}
+
+/// Extensions, without the dot and in lower case, and the kinds of files they name
+const EXTENSIONS: &[(&str, Kind)] = &[
+    ("7z", Kind::Archive),
+    ("apk", Kind::Archive),
+    ("arj", Kind::Archive),
+    ("bz2", Kind::Archive),
+    ("cab", Kind::Archive),
+    ("cpio", Kind::Archive),
+    ("deb", Kind::Archive),
+    ("dmg", Kind::Archive),
+    ("ear", Kind::Archive),
+    ("gz", Kind::Archive),
+    ("iso", Kind::Archive),
+    ("jar", Kind::Archive),
+    ("lz", Kind::Archive),
+    ("lz4", Kind::Archive),
+    ("lzma", Kind::Archive),
+    ("rar", Kind::Archive),
+    ("rpm", Kind::Archive),
+    ("tar", Kind::Archive),
+    ("tbz2", Kind::Archive),
+    ("tgz", Kind::Archive),
+    ("txz", Kind::Archive),
+    ("war", Kind::Archive),
+    ("whl", Kind::Archive),
+    ("xz", Kind::Archive),
+    ("z", Kind::Archive),
+    ("zip", Kind::Archive),
+    ("zst", Kind::Archive),
+    ("avif", Kind::Image),
+    ("bmp", Kind::Image),
+    ("gif", Kind::Image),
+    ("heic", Kind::Image),
+    ("ico", Kind::Image),
+    ("jpeg", Kind::Image),
+    ("jpg", Kind::Image),
+    ("png", Kind::Image),
+    ("svg", Kind::Image),
+    ("tif", Kind::Image),
+    ("tiff", Kind::Image),
+    ("webp", Kind::Image),
+    ("xcf", Kind::Image),
+    ("avi", Kind::Video),
+    ("flv", Kind::Video),
+    ("m4v", Kind::Video),
+    ("mkv", Kind::Video),
+    ("mov", Kind::Video),
+    ("mp4", Kind::Video),
+    ("mpeg", Kind::Video),
+    ("mpg", Kind::Video),
+    ("ogv", Kind::Video),
+    ("webm", Kind::Video),
+    ("wmv", Kind::Video),
+    ("aac", Kind::Audio),
+    ("flac", Kind::Audio),
+    ("m4a", Kind::Audio),
+    ("mid", Kind::Audio),
+    ("midi", Kind::Audio),
+    ("mp3", Kind::Audio),
+    ("oga", Kind::Audio),
+    ("ogg", Kind::Audio),
+    ("opus", Kind::Audio),
+    ("wav", Kind::Audio),
+    ("c", Kind::Source),
+    ("cc", Kind::Source),
+    ("cpp", Kind::Source),
+    ("cs", Kind::Source),
+    ("go", Kind::Source),
+    ("h", Kind::Source),
+    ("hpp", Kind::Source),
+    ("java", Kind::Source),
+    ("js", Kind::Source),
+    ("kt", Kind::Source),
+    ("lua", Kind::Source),
+    ("php", Kind::Source),
+    ("py", Kind::Source),
+    ("rb", Kind::Source),
+    ("rs", Kind::Source),
+    ("sh", Kind::Source),
+    ("swift", Kind::Source),
+    ("ts", Kind::Source),
+    ("zig", Kind::Source),
+    ("bak", Kind::Temporary),
+    ("old", Kind::Temporary),
+    ("orig", Kind::Temporary),
+    ("swp", Kind::Temporary),
+    ("tmp", Kind::Temporary),
+];
+
+/// The kind of file a name is for, from its extension or a trailing `~`
+pub(crate) fn kind(name: &str) -> Option<Kind> {
+    if name.ends_with('~') {
+        return Some(Kind::Temporary);
+    }
+    // a leading dot starts a hidden name, not an extension
+    let (stem, extension) = name.rsplit_once('.')?;
+    if stem.is_empty() {
+        return None;
+    }
+    let extension = extension.to_ascii_lowercase();
+    EXTENSIONS.iter().find(|(ext, _)| *ext == extension).map(|&(_, kind)| kind)
+}
+
+impl Kind {
+    /// Color `text` as a file of this kind, readably on `background`
+    pub(crate) fn paint(self, text: &str, background: Background) -> ColoredString {
+        match (self, background) {
+            (Kind::Archive, _) => text.bold().red(),
+            (Kind::Image | Kind::Video, _) => text.bold().magenta(),
+            (Kind::Audio, Background::Dark) => text.cyan(),
+            // cyan is hard to read on a light background
+            (Kind::Audio, Background::Light) => text.blue(),
+            (Kind::Source, Background::Dark) => text.yellow(),
+            // and so is yellow
+            (Kind::Source, Background::Light) => text.yellow().dimmed(),
+            (Kind::Temporary, _) => text.bright_black(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{kind, Kind, EXTENSIONS};
+
+    #[test]
+    fn recognizes_extensions() {
+        assert_eq!(kind("backup.tar.gz"), Some(Kind::Archive));
+        assert_eq!(kind("photo.JPG"), Some(Kind::Image));
+        assert_eq!(kind("song.flac"), Some(Kind::Audio));
+        assert_eq!(kind("movie.mkv"), Some(Kind::Video));
+        assert_eq!(kind("main.rs"), Some(Kind::Source));
+        assert_eq!(kind("notes.txt~"), Some(Kind::Temporary));
+        assert_eq!(kind("README"), None);
+        assert_eq!(kind("notes.txt"), None);
+        assert_eq!(kind("trailing."), None);
+    }
+
+    #[test]
+    fn ignores_hidden_names_without_extensions() {
+        assert_eq!(kind(".zip"), None);
+        assert_eq!(kind(".config.zip"), Some(Kind::Archive));
+    }
+
+    #[test]
+    fn lists_each_extension_once_in_lower_case() {
+        for (i, (ext, _)) in EXTENSIONS.iter().enumerate() {
+            assert_eq!(*ext, ext.to_ascii_lowercase());
+            assert!(EXTENSIONS[i + 1..].iter().all(|(other, _)| other != ext), "{}", ext);
+        }
+    }
+}
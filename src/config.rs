@@ -0,0 +1,257 @@
+//! User configuration loaded from a TOML file
+//!
+//! The file is looked up at `$LISTARE_CONFIG`, then
+//! `$XDG_CONFIG_HOME/listare/config.toml`, then `~/.config/listare/config.toml`.
+//! A missing file is the same as an empty configuration.
+//!
+//! ```toml
+//! # relative to the directory of the config file
+//! theme = "themes/solarized.toml"
+//!
+//! [icons.names]
+//! justfile = "\uf489"
+//!
+//! [icons.extensions]
+//! ipynb = "\ue678"
+//!
+//! [aliases]
+//! ll = ["-l", "--header"]
+//! la = ["@ll", "-a"]
+//!
+//! [long.align]
+//! owner = "right"
+//! size = "left"
+//!
+//! [[colors]]
+//! glob = "*.lock"
+//! style = "dimmed"
+//!
+//! [[colors]]
+//! owner = "root"
+//! style = "bold red"
+//! ```
+use std::{collections::HashMap, ffi::OsString, fmt, path::PathBuf};
+
+use crate::{Alignment, ColorRule, Field, Icons, Owner, Palette, Style};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error), // the config file exists but could not be read
+    Parse(PathBuf, String),      // the config file is not valid
+    AliasCycle(Vec<String>),     // an alias expands to itself
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            ConfigError::Parse(path, msg) => write!(f, "{}: {}", path.display(), msg),
+            ConfigError::AliasCycle(chain) => {
+                write!(f, "alias cycle detected: {}", chain.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Named bundles of arguments invoked as `@name`
+    pub aliases: HashMap<String, Vec<String>>,
+    /// Alignment overrides for long format fields
+    pub long_alignment: HashMap<Field, Alignment>,
+    /// Styles for names matching globs or owners, in the order they are given
+    pub color_rules: Vec<ColorRule>,
+    /// The theme file replacing the built-in colors, as given in the config file
+    pub theme: Option<PathBuf>,
+    /// The styles of the theme file, once loaded
+    pub palette: Palette,
+    /// Icons for `--icons`, by name and extension
+    pub icons: Icons,
+}
+
+impl Config {
+    /// The location of the config file, if one can be determined
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("LISTARE_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("listare").join("config.toml"))
+    }
+
+    /// Load the config file, returning an empty configuration if there is none
+    pub fn load() -> Result<Config, ConfigError> {
+        let path = match Config::path() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let mut config = Config::parse(&text).map_err(|msg| ConfigError::Parse(path.clone(), msg))?;
+                if let Some(theme) = &config.theme {
+                    let dir = path.parent().unwrap_or_else(|| std::path::Path::new(""));
+                    config.palette = Palette::load(&dir.join(theme))?;
+                }
+                Ok(config)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(ConfigError::Io(path, e)),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+        let mut config = Config::default();
+
+        if let Some(theme) = table.get("theme") {
+            config.theme = Some(PathBuf::from(theme.as_str().ok_or("theme must be the path of a file")?));
+        }
+
+        if let Some(aliases) = table.get("aliases") {
+            let aliases = aliases.as_table().ok_or("aliases must be a table")?;
+            for (name, value) in aliases {
+                let args = value
+                    .as_array()
+                    .and_then(|items| {
+                        items
+                            .iter()
+                            .map(|item| item.as_str().map(String::from))
+                            .collect::<Option<Vec<String>>>()
+                    })
+                    .ok_or(format!("alias {} must be a list of strings", name))?;
+                config.aliases.insert(name.clone(), args);
+            }
+        }
+
+        if let Some(long) = table.get("long") {
+            let long = long.as_table().ok_or("long must be a table")?;
+            if let Some(align) = long.get("align") {
+                let align = align.as_table().ok_or("long.align must be a table")?;
+                for (name, value) in align {
+                    let field = Field::from_name(name)
+                        .ok_or(format!("long.align: unknown field {}", name))?;
+                    let alignment = match value.as_str() {
+                        Some("left") => Alignment::Left,
+                        Some("right") => Alignment::Right,
+                        _ => return Err(format!("long.align.{} must be \"left\" or \"right\"", name)),
+                    };
+                    config.long_alignment.insert(field, alignment);
+                }
+            }
+        }
+
+        if let Some(icons) = table.get("icons") {
+            let icons = icons.as_table().ok_or("icons must be a table")?;
+            for (section, glyphs) in icons {
+                let glyphs = glyphs.as_table().ok_or(format!("icons.{} must be a table", section))?;
+                let map = match section.as_str() {
+                    "names" => &mut config.icons.names,
+                    "extensions" => &mut config.icons.extensions,
+                    _ => return Err(format!("icons: unknown section {}", section)),
+                };
+                for (key, glyph) in glyphs {
+                    let glyph = glyph.as_str().ok_or(format!("icons.{}.{} must be a string", section, key))?;
+                    // extensions are matched in lower case, with or without the dot
+                    let key = if section == "extensions" {
+                        key.trim_start_matches('.').to_ascii_lowercase()
+                    } else {
+                        key.clone()
+                    };
+                    map.insert(key, glyph.to_string());
+                }
+            }
+        }
+
+        if let Some(colors) = table.get("colors") {
+            let colors = colors.as_array().ok_or("colors must be a list of tables")?;
+            for rule in colors {
+                let owner = match rule.get("owner") {
+                    Some(owner) => Some(
+                        owner
+                            .as_str()
+                            .and_then(Owner::parse)
+                            .ok_or("colors: owner must be \"root\" or \"other\"")?,
+                    ),
+                    None => None,
+                };
+                // a rule on the owner alone applies to every name
+                let glob = match (rule.get("glob"), owner) {
+                    (Some(glob), _) => glob.as_str().ok_or("colors: glob must be a string")?,
+                    (None, Some(_)) => "*",
+                    (None, None) => return Err("colors: each rule needs a glob or an owner".to_string()),
+                };
+                let style = rule
+                    .get("style")
+                    .and_then(|v| v.as_str())
+                    .and_then(Style::parse)
+                    .ok_or(format!("colors: the rule for {} needs a valid style", glob))?;
+                let mut color_rule = ColorRule::new(glob, style).map_err(|e| format!("colors: {}", e))?;
+                if let Some(owner) = owner {
+                    color_rule = color_rule.owned_by(owner);
+                }
+                config.color_rules.push(color_rule);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Replace every `@name` argument with the arguments of the alias it names
+    /// Aliases may refer to other aliases. Arguments after `--` and `@words`
+    /// that are not defined aliases are passed through unchanged.
+    pub fn expand_aliases(&self, args: Vec<OsString>) -> Result<Vec<OsString>, ConfigError> {
+        let mut expanded = Vec::with_capacity(args.len());
+        let mut args = args.into_iter();
+
+        // the program name is never expanded
+        expanded.extend(args.next());
+
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                expanded.push(arg);
+                expanded.extend(args.by_ref());
+                break;
+            }
+            self.expand_arg(arg, &mut Vec::new(), &mut expanded)?;
+        }
+
+        Ok(expanded)
+    }
+
+    fn expand_arg(
+        &self,
+        arg: OsString,
+        chain: &mut Vec<String>,
+        out: &mut Vec<OsString>,
+    ) -> Result<(), ConfigError> {
+        let alias = arg
+            .to_str()
+            .and_then(|s| s.strip_prefix('@'))
+            .and_then(|name| self.aliases.get_key_value(name));
+
+        let (name, values) = match alias {
+            Some(alias) => alias,
+            None => {
+                out.push(arg);
+                return Ok(());
+            }
+        };
+
+        if chain.contains(name) {
+            chain.push(name.clone());
+            return Err(ConfigError::AliasCycle(chain.clone()));
+        }
+
+        chain.push(name.clone());
+        for value in values {
+            self.expand_arg(OsString::from(value), chain, out)?;
+        }
+        chain.pop();
+        Ok(())
+    }
+}
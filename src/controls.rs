@@ -0,0 +1,38 @@
+//! Keeping file names from controlling the terminal
+//!
+//! A name can hold escape sequences that clear the screen, move the cursor or
+//! retitle the window, and so spoof the rest of a listing. With
+//! `--hide-control-chars`, the default when writing to a terminal, every C0
+//! and C1 control character in a name, link target or other field is shown as
+//! `?`, as GNU ls does with `-q`.
+use std::borrow::Cow;
+
+/// Shown in place of each control character
+const REPLACEMENT: char = '?';
+
+/// `text` with its control characters replaced when `hide` is set
+pub(crate) fn hide(text: &str, hide: bool) -> Cow<'_, str> {
+    if !hide || !text.chars().any(char::is_control) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.chars().map(|c| if c.is_control() { REPLACEMENT } else { c }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hide;
+
+    #[test]
+    fn replaces_c0_and_c1_controls() {
+        assert_eq!(hide("evil\u{1b}[2Jname", true), "evil?[2Jname");
+        assert_eq!(hide("line\nbreak\ttab\u{7f}", true), "line?break?tab?");
+        // CSI as a single C1 character
+        assert_eq!(hide("a\u{9b}31mb", true), "a?31mb");
+    }
+
+    #[test]
+    fn keeps_printable_text() {
+        assert_eq!(hide("café 日本 🎉", true), "café 日本 🎉");
+        assert_eq!(hide("evil\u{1b}[2J", false), "evil\u{1b}[2J");
+    }
+}
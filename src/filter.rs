@@ -0,0 +1,254 @@
+//! Filters deciding which entries of a directory are listed
+//!
+//! Each built-in filter keeps the entries it describes, and filters combine
+//! with [`Filter::and`], [`Filter::or`] and [`Filter::not`]:
+//!
+//! ```
+//! use listare::filter::{Filter, Glob, Hidden, Size};
+//!
+//! // visible Rust sources of at least 1 KiB
+//! let filter = Hidden.not().and(Glob::new("*.rs").unwrap()).and(Size::at_least(1024));
+//! ```
+//!
+//! Hidden entries are left out before the filter of [`Arguments`](crate::Arguments)
+//! sees them, unless `show_hidden` is set, so that they can be counted without
+//! reading their metadata.
+use std::{fmt, os::unix::fs::MetadataExt, time::SystemTime};
+
+use crate::{EntryData, TimeField};
+
+/// Decides whether an entry is listed
+pub trait Filter: fmt::Debug {
+    fn keep(&self, entry: &EntryData) -> bool;
+
+    /// Keep the entries both filters keep
+    fn and<F: Filter>(self, other: F) -> And<Self, F>
+    where
+        Self: Sized,
+    {
+        And(self, other)
+    }
+
+    /// Keep the entries either filter keeps
+    fn or<F: Filter>(self, other: F) -> Or<Self, F>
+    where
+        Self: Sized,
+    {
+        Or(self, other)
+    }
+
+    /// Keep the entries this filter drops
+    fn not(self) -> Not<Self>
+    where
+        Self: Sized,
+    {
+        Not(self)
+    }
+}
+
+impl<F: Filter + ?Sized> Filter for Box<F> {
+    fn keep(&self, entry: &EntryData) -> bool {
+        (**self).keep(entry)
+    }
+}
+
+impl<F: Filter + ?Sized> Filter for &F {
+    fn keep(&self, entry: &EntryData) -> bool {
+        (**self).keep(entry)
+    }
+}
+
+/// Whether a name is that of a hidden file
+pub(crate) fn is_hidden_name(name: &[u8]) -> bool {
+    name.first() == Some(&b'.')
+}
+
+/// Entries whose names start with a period
+#[derive(Clone, Copy, Debug)]
+pub struct Hidden;
+
+impl Filter for Hidden {
+    fn keep(&self, entry: &EntryData) -> bool {
+        is_hidden_name(entry.name.as_bytes())
+    }
+}
+
+/// Entries whose names match a glob, e.g. `*.rs`
+#[derive(Clone, Debug)]
+pub struct Glob(glob::Pattern);
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Glob, glob::PatternError> {
+        glob::Pattern::new(pattern).map(Glob)
+    }
+}
+
+impl Filter for Glob {
+    fn keep(&self, entry: &EntryData) -> bool {
+        self.0.matches(&entry.name)
+    }
+}
+
+/// Entries whose size in bytes is within bounds
+#[derive(Clone, Copy, Debug)]
+pub struct Size {
+    min: u64,
+    max: u64,
+}
+
+impl Size {
+    pub fn at_least(bytes: u64) -> Size {
+        Size { min: bytes, max: u64::MAX }
+    }
+
+    pub fn at_most(bytes: u64) -> Size {
+        Size { min: 0, max: bytes }
+    }
+}
+
+impl Filter for Size {
+    fn keep(&self, entry: &EntryData) -> bool {
+        (self.min..=self.max).contains(&entry.metadata.len())
+    }
+}
+
+/// Entries with a timestamp after or before a point in time
+/// Entries without the timestamp, such as a birth time the filesystem does not
+/// record, are not kept.
+#[derive(Clone, Copy, Debug)]
+pub struct Age {
+    field: TimeField,
+    since: SystemTime,
+    newer: bool, // keep times after `since` rather than before it
+}
+
+impl Age {
+    pub fn newer_than(field: TimeField, since: SystemTime) -> Age {
+        Age { field, since, newer: true }
+    }
+
+    pub fn older_than(field: TimeField, since: SystemTime) -> Age {
+        Age { field, since, newer: false }
+    }
+}
+
+impl Filter for Age {
+    fn keep(&self, entry: &EntryData) -> bool {
+        match self.field.get(&entry.metadata) {
+            Ok(time) if self.newer => time > self.since,
+            Ok(time) => time < self.since,
+            Err(_) => false,
+        }
+    }
+}
+
+/// Entries of a file type, symlinks not being followed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Type {
+    File,
+    Directory,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+impl Filter for Type {
+    fn keep(&self, entry: &EntryData) -> bool {
+        let file_type = match self {
+            Type::File => libc::S_IFREG,
+            Type::Directory => libc::S_IFDIR,
+            Type::Symlink => libc::S_IFLNK,
+            Type::Fifo => libc::S_IFIFO,
+            Type::Socket => libc::S_IFSOCK,
+            Type::BlockDevice => libc::S_IFBLK,
+            Type::CharDevice => libc::S_IFCHR,
+        };
+        entry.metadata.mode() & libc::S_IFMT == file_type
+    }
+}
+
+/// Entries with all of the given permission bits set, e.g. `0o002` for world-writable files
+#[derive(Clone, Copy, Debug)]
+pub struct Perm(pub u32);
+
+impl Filter for Perm {
+    fn keep(&self, entry: &EntryData) -> bool {
+        entry.metadata.mode() & self.0 == self.0
+    }
+}
+
+/// Entries kept by both filters
+#[derive(Clone, Copy, Debug)]
+pub struct And<A, B>(pub A, pub B);
+
+impl<A: Filter, B: Filter> Filter for And<A, B> {
+    fn keep(&self, entry: &EntryData) -> bool {
+        self.0.keep(entry) && self.1.keep(entry)
+    }
+}
+
+/// Entries kept by either filter
+#[derive(Clone, Copy, Debug)]
+pub struct Or<A, B>(pub A, pub B);
+
+impl<A: Filter, B: Filter> Filter for Or<A, B> {
+    fn keep(&self, entry: &EntryData) -> bool {
+        self.0.keep(entry) || self.1.keep(entry)
+    }
+}
+
+/// Entries dropped by a filter
+#[derive(Clone, Copy, Debug)]
+pub struct Not<F>(pub F);
+
+impl<F: Filter> Filter for Not<F> {
+    fn keep(&self, entry: &EntryData) -> bool {
+        !self.0.keep(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Age, Filter, Glob, Hidden, Perm, Size, Type};
+    use crate::{EntryData, TimeField};
+    use std::time::{Duration, SystemTime};
+
+    fn keeps(filter: impl Filter, path: &str) -> bool {
+        EntryData::from_path_str(path).is_ok_and(|entry| filter.keep(&entry))
+    }
+
+    #[test]
+    fn matches_names() {
+        assert!(keeps(Hidden, ".gitignore"));
+        assert!(!keeps(Hidden, "Cargo.toml"));
+        assert!(Glob::new("*.toml").is_ok_and(|glob| keeps(glob, "Cargo.toml")));
+        assert!(Glob::new("*.rs").is_ok_and(|glob| !keeps(glob, "Cargo.toml")));
+        assert!(Glob::new("[").is_err());
+    }
+
+    #[test]
+    fn matches_metadata() {
+        assert!(keeps(Type::Directory, "src"));
+        assert!(keeps(Type::File, "Cargo.toml"));
+        assert!(!keeps(Type::Symlink, "Cargo.toml"));
+        assert!(keeps(Size::at_least(1), "Cargo.toml"));
+        assert!(!keeps(Size::at_most(1), "Cargo.toml"));
+        assert!(keeps(Perm(0o400), "Cargo.toml"));
+        assert!(!keeps(Perm(0o4000), "Cargo.toml"));
+
+        let tomorrow = SystemTime::now() + Duration::from_secs(86400);
+        assert!(keeps(Age::older_than(TimeField::Modified, tomorrow), "Cargo.toml"));
+        assert!(!keeps(Age::newer_than(TimeField::Modified, tomorrow), "Cargo.toml"));
+    }
+
+    #[test]
+    fn combines_filters() {
+        assert!(keeps(Type::Directory.or(Type::File), "Cargo.toml"));
+        assert!(!keeps(Type::File.and(Hidden), "Cargo.toml"));
+        assert!(keeps(Hidden.not().and(Type::File), "Cargo.toml"));
+        let boxed: Box<dyn Filter> = Box::new(Type::File.not());
+        assert!(keeps(&boxed, "src"));
+    }
+}
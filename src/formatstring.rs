@@ -0,0 +1,227 @@
+//! Per-entry templates for `--format-string`
+//!
+//! The directives are a subset of those of stat(1), and the backslash escapes
+//! `\t`, `\n` and `\\` are understood so that templates can be given in
+//! single quotes. Each entry is printed as the rendered template on its own line.
+//!
+//! | directive | value |
+//! |-----------|-------|
+//! | `%n` | name |
+//! | `%N` | quoted name, followed by `-> 'target'` for symlinks |
+//! | `%s` | size in bytes |
+//! | `%b` | allocated blocks of 512 bytes |
+//! | `%U` / `%u` | owner name / user ID |
+//! | `%G` / `%g` | group name / group ID |
+//! | `%A` / `%a` | mode as in `ls -l` / in octal |
+//! | `%F` | file type |
+//! | `%i` | inode number |
+//! | `%h` | number of hard links |
+//! | `%x` `%y` `%z` `%w` | access, modification, change and birth times |
+//! | `%X` `%Y` `%Z` `%W` | the same times in seconds since the epoch |
+//! | `%%` | a literal `%` |
+//!
+//! Times that are not known are printed as `-`.
+use std::{fmt, os::unix::fs::MetadataExt, time::SystemTime};
+
+use crate::{controls, longformat, vfs, EntryData, TimeField};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FormatStringError {
+    UnknownDirective(char), // a % followed by a character that is not a directive
+    Incomplete,             // the template ends with a lone %
+}
+
+impl fmt::Display for FormatStringError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatStringError::UnknownDirective(c) => write!(f, "invalid format string directive: %{}", c),
+            FormatStringError::Incomplete => write!(f, "format string ends with a lone %"),
+        }
+    }
+}
+
+impl std::error::Error for FormatStringError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Directive {
+    Name,
+    QuotedName,
+    Size,
+    Blocks,
+    Owner,
+    Uid,
+    Group,
+    Gid,
+    Mode,
+    OctalMode,
+    FileType,
+    Inode,
+    Links,
+    Time(TimeField),
+    Epoch(TimeField),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Directive(Directive),
+}
+
+/// A parsed `--format-string` template
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatString(Vec<Piece>);
+
+fn directive(c: char) -> Option<Directive> {
+    Some(match c {
+        'n' => Directive::Name,
+        'N' => Directive::QuotedName,
+        's' => Directive::Size,
+        'b' => Directive::Blocks,
+        'U' => Directive::Owner,
+        'u' => Directive::Uid,
+        'G' => Directive::Group,
+        'g' => Directive::Gid,
+        'A' => Directive::Mode,
+        'a' => Directive::OctalMode,
+        'F' => Directive::FileType,
+        'i' => Directive::Inode,
+        'h' => Directive::Links,
+        'x' => Directive::Time(TimeField::Accessed),
+        'y' => Directive::Time(TimeField::Modified),
+        'z' => Directive::Time(TimeField::Changed),
+        'w' => Directive::Time(TimeField::Birth),
+        'X' => Directive::Epoch(TimeField::Accessed),
+        'Y' => Directive::Epoch(TimeField::Modified),
+        'Z' => Directive::Epoch(TimeField::Changed),
+        'W' => Directive::Epoch(TimeField::Birth),
+        _ => return None,
+    })
+}
+
+impl FormatString {
+    pub fn parse(template: &str) -> Result<FormatString, FormatStringError> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '%' => match chars.next() {
+                    Some('%') => text.push('%'),
+                    Some(c) => {
+                        let directive = directive(c).ok_or(FormatStringError::UnknownDirective(c))?;
+                        if !text.is_empty() {
+                            pieces.push(Piece::Text(std::mem::take(&mut text)));
+                        }
+                        pieces.push(Piece::Directive(directive));
+                    }
+                    None => return Err(FormatStringError::Incomplete),
+                },
+                // unknown escapes are kept as they are
+                '\\' => match chars.next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('\\') => text.push('\\'),
+                    Some(c) => {
+                        text.push('\\');
+                        text.push(c);
+                    }
+                    None => text.push('\\'),
+                },
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(FormatString(pieces))
+    }
+
+    /// The template filled in for an entry, with control characters in the values hidden if asked
+    pub(crate) fn render(&self, entry: &EntryData, hide_control_chars: bool) -> String {
+        let mut line = String::new();
+        for piece in &self.0 {
+            match piece {
+                Piece::Text(text) => line.push_str(text),
+                Piece::Directive(directive) => {
+                    line.push_str(&controls::hide(&value(*directive, entry), hide_control_chars))
+                }
+            }
+        }
+        line
+    }
+}
+
+fn value(directive: Directive, entry: &EntryData) -> String {
+    let metadata = &entry.metadata;
+    match directive {
+        Directive::Name => entry.name.clone(),
+        Directive::QuotedName => match vfs::read_link(&entry.path) {
+            Ok(target) if metadata.is_symlink() => format!("'{}' -> '{}'", entry.name, target.display()),
+            _ => format!("'{}'", entry.name),
+        },
+        Directive::Size => metadata.len().to_string(),
+        Directive::Blocks => metadata.blocks().to_string(),
+        Directive::Owner => users::get_user_by_uid(metadata.uid())
+            .map(|u| u.name().to_string_lossy().into_owned())
+            .unwrap_or_else(|| metadata.uid().to_string()),
+        Directive::Uid => metadata.uid().to_string(),
+        Directive::Group => users::get_group_by_gid(metadata.gid())
+            .map(|g| g.name().to_string_lossy().into_owned())
+            .unwrap_or_else(|| metadata.gid().to_string()),
+        Directive::Gid => metadata.gid().to_string(),
+        Directive::Mode => longformat::mode_string(metadata.mode()),
+        Directive::OctalMode => format!("{:o}", metadata.mode() & 0o7777),
+        Directive::FileType => longformat::file_type_name(metadata.mode()).to_string(),
+        Directive::Inode => metadata.ino().to_string(),
+        Directive::Links => metadata.nlink().to_string(),
+        Directive::Time(field) => match field.get(metadata) {
+            Ok(time) => chrono::DateTime::<chrono::Local>::from(time)
+                .format("%Y-%m-%d %H:%M:%S%.9f %z")
+                .to_string(),
+            Err(_) => "-".to_string(),
+        },
+        Directive::Epoch(field) => match field.get(metadata) {
+            Ok(time) => match time.duration_since(SystemTime::UNIX_EPOCH) {
+                Ok(age) => age.as_secs().to_string(),
+                // whole seconds before the epoch, rounded down as stat does
+                Err(e) => {
+                    let before = e.duration();
+                    format!("-{}", before.as_secs() + u64::from(before.subsec_nanos() > 0))
+                }
+            },
+            Err(_) => "-".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Directive, FormatString, FormatStringError, Piece};
+    use crate::TimeField;
+
+    #[test]
+    fn parses_directives_and_escapes() {
+        let parsed = FormatString::parse(r"%n\t%s 100%% %Y\n");
+        let expected = FormatString(vec![
+            Piece::Directive(Directive::Name),
+            Piece::Text("\t".to_string()),
+            Piece::Directive(Directive::Size),
+            Piece::Text(" 100% ".to_string()),
+            Piece::Directive(Directive::Epoch(TimeField::Modified)),
+            Piece::Text("\n".to_string()),
+        ]);
+        assert_eq!(parsed, Ok(expected));
+    }
+
+    #[test]
+    fn keeps_unknown_escapes() {
+        let parsed = FormatString::parse(r"a\qb\");
+        assert_eq!(parsed, Ok(FormatString(vec![Piece::Text(r"a\qb\".to_string())])));
+    }
+
+    #[test]
+    fn rejects_unknown_directives() {
+        assert_eq!(FormatString::parse("%n %q"), Err(FormatStringError::UnknownDirective('q')));
+        assert_eq!(FormatString::parse("size %"), Err(FormatStringError::Incomplete));
+    }
+}
@@ -0,0 +1,109 @@
+//! Filesystem specific attributes, for the `--fs-attributes` column
+//!
+//! Each [`Provider`] knows one kind of filesystem, recognized by the magic
+//! number statfs(2) gives for it, and names the attributes of files on it that
+//! the rest of the long format does not show, such as whether btrfs compresses
+//! a file. Another filesystem is supported by implementing [`Provider`] for it
+//! and adding it to [`PROVIDERS`].
+use std::fs::Metadata;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use crate::{posix, xattr};
+
+/// Names the attributes of files on one kind of filesystem
+trait Provider: Sync {
+    /// The `f_type` statfs(2) gives for the filesystem
+    fn magic(&self) -> u64;
+
+    /// The attributes of a file on the filesystem, e.g. `compressed`
+    fn attributes(&self, path: &Path, metadata: &Metadata) -> Vec<String>;
+}
+
+/// The filesystems whose attributes are known
+const PROVIDERS: &[&dyn Provider] = &[&Btrfs];
+
+const BTRFS_SUPER_MAGIC: u64 = 0x9123_683e;
+const FS_COMPR_FL: u32 = 0x0000_0004; // compress the file's data
+const FS_NOCOMP_FL: u32 = 0x0000_0400; // never compress the file's data
+const FS_NOCOW_FL: u32 = 0x0080_0000; // write the file's data in place, without copy on write
+
+/// Names the xattr holding the compression algorithm chosen for a file
+const BTRFS_COMPRESSION: &str = "btrfs.compression";
+
+struct Btrfs;
+
+impl Provider for Btrfs {
+    fn magic(&self) -> u64 {
+        BTRFS_SUPER_MAGIC
+    }
+
+    fn attributes(&self, path: &Path, metadata: &Metadata) -> Vec<String> {
+        let algorithm = xattr::get(path, BTRFS_COMPRESSION).ok();
+        btrfs_attributes(inode_flags(path, metadata).unwrap_or(0), algorithm.as_deref())
+    }
+}
+
+/// The attributes of a btrfs file with the given inode flags and `btrfs.compression` value
+fn btrfs_attributes(flags: u32, algorithm: Option<&[u8]>) -> Vec<String> {
+    let mut attributes = Vec::new();
+    match algorithm.filter(|algorithm| !algorithm.is_empty()) {
+        Some(algorithm) => attributes.push(format!("compressed={}", String::from_utf8_lossy(algorithm))),
+        None if flags & FS_COMPR_FL != 0 => attributes.push("compressed".to_string()),
+        None => {}
+    }
+    if flags & FS_NOCOMP_FL != 0 {
+        attributes.push("nocompress".to_string());
+    }
+    if flags & FS_NOCOW_FL != 0 {
+        attributes.push("nocow".to_string());
+    }
+    attributes
+}
+
+/// The inode flags of chattr(1), as FS_IOC_GETFLAGS gives them
+/// Only regular files and directories are opened for them, since opening a
+/// device or fifo can have side effects or block.
+fn inode_flags(path: &Path, metadata: &Metadata) -> Option<u32> {
+    if !metadata.is_file() && !metadata.is_dir() {
+        return None;
+    }
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+        .open(path)
+        .ok()?;
+    let mut flags: libc::c_int = 0;
+    match unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } {
+        0 => Some(flags as u32),
+        _ => None,
+    }
+}
+
+/// The filesystem specific attributes of a file, separated by commas, or `-` when it has none
+pub(crate) fn text(path: &Path, metadata: &Metadata) -> String {
+    let attributes = posix::filesystem_type(path)
+        .and_then(|magic| PROVIDERS.iter().find(|provider| provider.magic() == magic))
+        .map(|provider| provider.attributes(path, metadata))
+        .unwrap_or_default();
+    if attributes.is_empty() {
+        "-".to_string()
+    } else {
+        attributes.join(",")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{btrfs_attributes, FS_COMPR_FL, FS_NOCOMP_FL, FS_NOCOW_FL};
+
+    #[test]
+    fn names_btrfs_attributes() {
+        assert!(btrfs_attributes(0, None).is_empty());
+        assert_eq!(btrfs_attributes(FS_COMPR_FL, None), ["compressed"]);
+        assert_eq!(btrfs_attributes(FS_COMPR_FL, Some(b"zstd")), ["compressed=zstd"]);
+        assert_eq!(btrfs_attributes(0, Some(b"")), Vec::<String>::new());
+        assert_eq!(btrfs_attributes(FS_NOCOW_FL | FS_NOCOMP_FL, None), ["nocompress", "nocow"]);
+    }
+}
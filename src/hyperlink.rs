@@ -0,0 +1,63 @@
+//! Clickable names for `--hyperlink`
+//!
+//! Names are wrapped in OSC 8 escape sequences linking to `file://` URIs of
+//! their absolute paths, which terminals that support them open on a click and
+//! others ignore. The sequences take no columns on screen, so widths are
+//! measured on the names alone and the grid stays aligned.
+use std::fmt;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::posix;
+
+/// The `file://` URI of `path`, with the host name as GNU ls gives it
+pub(crate) fn uri(path: &Path) -> String {
+    static HOSTNAME: OnceLock<String> = OnceLock::new();
+    let host = HOSTNAME.get_or_init(|| posix::hostname().unwrap_or_default());
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    format!("file://{}{}", host, percent_encode(path.as_os_str().as_bytes()))
+}
+
+/// Escape the bytes of a path that cannot appear as they are in a URI
+fn percent_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Text shown as a link to a URI, or as it is without one
+pub(crate) struct Linked<'a, T>(pub(crate) Option<&'a str>, pub(crate) T);
+
+impl<T: fmt::Display> fmt::Display for Linked<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(uri) => write!(f, "\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", uri, self.1),
+            None => write!(f, "{}", self.1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{percent_encode, Linked};
+
+    #[test]
+    fn encodes_paths() {
+        assert_eq!(percent_encode(b"/home/user/notes.txt"), "/home/user/notes.txt");
+        assert_eq!(percent_encode(b"/tmp/a b#c%"), "/tmp/a%20b%23c%25");
+        assert_eq!(percent_encode("/tmp/é".as_bytes()), "/tmp/%C3%A9");
+    }
+
+    #[test]
+    fn wraps_text_in_osc_8() {
+        assert_eq!(Linked(Some("file://host/a"), "a").to_string(), "\x1b]8;;file://host/a\x1b\\a\x1b]8;;\x1b\\");
+        assert_eq!(Linked(None, "a").to_string(), "a");
+    }
+}
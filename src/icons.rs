@@ -0,0 +1,179 @@
+//! Nerd Font icons before names, for `--icons`
+//!
+//! Like eza and lsd, an icon is chosen by the whole name of a file first, such
+//! as `Cargo.toml` or `.gitignore`, then by its extension, then by its type.
+//! The glyphs are in the private use area, where a Nerd Font puts them and
+//! other fonts show a placeholder. Each takes one column and is followed by a
+//! space, and both count in the width of the name so that grids stay aligned.
+//!
+//! The config file can change or add icons, by name or extension (see
+//! [`Icons`]), for other icon fonts or file types listare does not know.
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+
+use crate::EntryData;
+
+const DIRECTORY: char = '\u{e5ff}';
+const SYMLINK: char = '\u{f481}';
+const EXECUTABLE: char = '\u{f489}';
+const FILE: char = '\u{f15b}';
+
+/// Whole names, and the icons for them
+const NAMES: &[(&str, char)] = &[
+    (".bashrc", '\u{f489}'),
+    (".git", '\u{e5fb}'),
+    (".gitattributes", '\u{f1d3}'),
+    (".gitignore", '\u{f1d3}'),
+    (".gitmodules", '\u{f1d3}'),
+    (".zshrc", '\u{f489}'),
+    ("Cargo.lock", '\u{e7a8}'),
+    ("Cargo.toml", '\u{e7a8}'),
+    ("Dockerfile", '\u{f308}'),
+    ("LICENSE", '\u{f02d}'),
+    ("Makefile", '\u{f489}'),
+    ("node_modules", '\u{e5fa}'),
+];
+
+/// Extensions, without the dot and in lower case, and the icons for them
+const EXTENSIONS: &[(&str, char)] = &[
+    ("7z", '\u{f410}'),
+    ("bz2", '\u{f410}'),
+    ("c", '\u{e61e}'),
+    ("cc", '\u{e61d}'),
+    ("cpp", '\u{e61d}'),
+    ("css", '\u{e749}'),
+    ("deb", '\u{e77d}'),
+    ("flac", '\u{f001}'),
+    ("gif", '\u{f1c5}'),
+    ("go", '\u{e626}'),
+    ("gz", '\u{f410}'),
+    ("h", '\u{f0fd}'),
+    ("hpp", '\u{f0fd}'),
+    ("html", '\u{f13b}'),
+    ("java", '\u{e738}'),
+    ("jpeg", '\u{f1c5}'),
+    ("jpg", '\u{f1c5}'),
+    ("js", '\u{e74e}'),
+    ("json", '\u{e60b}'),
+    ("lock", '\u{f023}'),
+    ("lua", '\u{e620}'),
+    ("md", '\u{f48a}'),
+    ("mkv", '\u{f03d}'),
+    ("mp3", '\u{f001}'),
+    ("mp4", '\u{f03d}'),
+    ("pdf", '\u{f1c1}'),
+    ("png", '\u{f1c5}'),
+    ("py", '\u{e606}'),
+    ("rb", '\u{e21e}'),
+    ("rs", '\u{e7a8}'),
+    ("sh", '\u{f489}'),
+    ("svg", '\u{f1c5}'),
+    ("tar", '\u{f410}'),
+    ("toml", '\u{e6b2}'),
+    ("ts", '\u{e628}'),
+    ("txt", '\u{f15c}'),
+    ("wav", '\u{f001}'),
+    ("xz", '\u{f410}'),
+    ("yaml", '\u{e6a8}'),
+    ("yml", '\u{e6a8}'),
+    ("zip", '\u{f410}'),
+    ("zst", '\u{f410}'),
+];
+
+/// Icons from the config file, each ahead of the built-in ones it overlaps
+#[derive(Clone, Debug, Default)]
+pub struct Icons {
+    /// Icons for whole names, e.g. `justfile`
+    pub names: HashMap<String, String>,
+    /// Icons for extensions, in lower case and without the dot
+    pub extensions: HashMap<String, String>,
+}
+
+impl Icons {
+    /// The icon for a file named `name`, given whether it is a directory, symlink or executable
+    fn icon_for(&self, name: &str, is_dir: bool, is_symlink: bool, executable: bool) -> String {
+        if let Some(icon) = self.names.get(name) {
+            return icon.clone();
+        }
+        if let Some(&(_, icon)) = NAMES.iter().find(|(known, _)| *known == name) {
+            return icon.to_string();
+        }
+        if is_symlink {
+            return SYMLINK.to_string();
+        }
+        if is_dir {
+            return DIRECTORY.to_string();
+        }
+        // a leading dot starts a hidden name, not an extension
+        if let Some((_, extension)) = name.rsplit_once('.').filter(|(stem, _)| !stem.is_empty()) {
+            let extension = extension.to_ascii_lowercase();
+            if let Some(icon) = self.extensions.get(&extension) {
+                return icon.clone();
+            }
+            if let Some(&(_, icon)) = EXTENSIONS.iter().find(|(known, _)| *known == extension) {
+                return icon.to_string();
+            }
+        }
+        if executable {
+            EXECUTABLE.to_string()
+        } else {
+            FILE.to_string()
+        }
+    }
+
+    /// The icon and the space that go before the name of an entry
+    pub(crate) fn prefix(&self, entry: &EntryData) -> String {
+        let metadata = &entry.metadata;
+        let executable = metadata.is_file() && metadata.permissions().mode() & 0o111 != 0;
+        format!("{} ", self.icon_for(&entry.name, metadata.is_dir(), metadata.is_symlink(), executable))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Icons, DIRECTORY, EXECUTABLE, EXTENSIONS, FILE, NAMES, SYMLINK};
+
+    #[test]
+    fn chooses_by_name_then_extension_then_type() {
+        let icon_for = |name, is_dir, is_symlink, executable| {
+            Icons::default().icon_for(name, is_dir, is_symlink, executable).chars().next()
+        };
+        assert_eq!(icon_for("Cargo.toml", false, false, false), Some('\u{e7a8}'));
+        assert_eq!(icon_for("config.TOML", false, false, false), Some('\u{e6b2}'));
+        assert_eq!(icon_for("src", true, false, false), Some(DIRECTORY));
+        assert_eq!(icon_for("lib.rs", false, true, false), Some(SYMLINK));
+        assert_eq!(icon_for("build", false, false, true), Some(EXECUTABLE));
+        assert_eq!(icon_for("run.sh", false, false, true), Some('\u{f489}'));
+        assert_eq!(icon_for("notes", false, false, false), Some(FILE));
+        // a directory named like a file is still a directory
+        assert_eq!(icon_for("site.html", true, false, false), Some(DIRECTORY));
+        assert_eq!(icon_for(".md", false, false, false), Some(FILE));
+    }
+
+    #[test]
+    fn prefers_configured_icons() {
+        let mut icons = Icons::default();
+        icons.names.insert("justfile".to_string(), "J".to_string());
+        icons.names.insert("src".to_string(), "S".to_string());
+        icons.extensions.insert("rs".to_string(), "R".to_string());
+        icons.extensions.insert("ipynb".to_string(), "N".to_string());
+        assert_eq!(icons.icon_for("justfile", false, false, false), "J");
+        assert_eq!(icons.icon_for("src", true, false, false), "S");
+        assert_eq!(icons.icon_for("main.RS", false, false, false), "R");
+        assert_eq!(icons.icon_for("plot.ipynb", false, false, false), "N");
+        // the built-in icons for names are more specific than extensions
+        assert_eq!(icons.icon_for("Cargo.toml", false, false, false), "\u{e7a8}");
+        assert_eq!(icons.icon_for("notes", false, false, false), FILE.to_string());
+    }
+
+    #[test]
+    fn lists_each_name_and_extension_once() {
+        for (i, (ext, _)) in EXTENSIONS.iter().enumerate() {
+            assert_eq!(*ext, ext.to_ascii_lowercase());
+            assert!(EXTENSIONS[i + 1..].iter().all(|(other, _)| other != ext), "{}", ext);
+        }
+        for (i, (name, _)) in NAMES.iter().enumerate() {
+            assert!(NAMES[i + 1..].iter().all(|(other, _)| other != name), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! A verbose, stat-like report on a single file (`listare --info FILE`)
+use std::{
+    fs::{self, Metadata},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::messages::{self, Message};
+use crate::{io_error_text, longformat, posix, xattr, ListareError, Output};
+
+/// Symlinks followed before a chain is considered a loop, as SYMLOOP_MAX on Linux
+const MAX_LINKS: usize = 40;
+
+/// Print the report for `path`
+pub fn print_info(path: &str) -> Result<(), ListareError> {
+    let path = Path::new(path);
+    let metadata = fs::symlink_metadata(path)
+        .map_err(|e| {
+            let action = messages::format(Message::CannotAccess, &format!("'{}'", path.display()));
+            ListareError::Generic(format!("listare: {}: {}", action, io_error_text(&e)))
+        })?;
+
+    let stdout = &mut std::io::stdout();
+    let out = &mut Output::text(stdout);
+    for (label, value) in report(path, &metadata) {
+        outln!(out, "{:>10}: {}", label, value)?;
+    }
+    Ok(())
+}
+
+fn report(path: &Path, metadata: &Metadata) -> Vec<(&'static str, String)> {
+    let mode = metadata.mode();
+    let mut lines = vec![
+        ("File", path.display().to_string()),
+        ("Type", longformat::file_type_name(mode).to_string()),
+    ];
+
+    if metadata.file_type().is_symlink() {
+        lines.push(("Links to", link_chain(path)));
+    }
+
+    lines.extend([
+        ("Size", format!("{} bytes, {} blocks of 512 bytes", metadata.len(), metadata.blocks())),
+        ("Mode", format!("{} ({:04o})", longformat::mode_string(mode), mode & 0o7777)),
+        ("Owner", format!("{} ({})", user_name(metadata.uid()), metadata.uid())),
+        ("Group", format!("{} ({})", group_name(metadata.gid()), metadata.gid())),
+        ("Inode", metadata.ino().to_string()),
+        ("Hard links", metadata.nlink().to_string()),
+        ("Device", format!("{:x}h/{}d", metadata.dev(), metadata.dev())),
+        ("Filesystem", filesystem(path)),
+        ("Access", time_text(metadata.accessed().ok())),
+        ("Modify", time_text(metadata.modified().ok())),
+        ("Change", time_text(longformat::change_time(metadata))),
+        ("Birth", time_text(metadata.created().ok())),
+        ("Xattrs", xattrs(path)),
+    ]);
+    lines
+}
+
+fn user_name(uid: u32) -> String {
+    users::get_user_by_uid(uid)
+        .map(|u| u.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| "?".to_string())
+}
+
+fn group_name(gid: u32) -> String {
+    users::get_group_by_gid(gid)
+        .map(|g| g.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| "?".to_string())
+}
+
+fn time_text(time: Option<SystemTime>) -> String {
+    match time {
+        Some(time) => chrono::DateTime::<chrono::Local>::from(time)
+            .format("%Y-%m-%d %H:%M:%S%.9f %z")
+            .to_string(),
+        None => "-".to_string(),
+    }
+}
+
+/// Every target in a chain of symlinks, e.g. `b -> c -> /d`
+/// The chain ends with a note if a target is missing or the links loop.
+fn link_chain(path: &Path) -> String {
+    let mut chain = Vec::new();
+    let mut current = path.to_path_buf();
+
+    loop {
+        if chain.len() == MAX_LINKS {
+            chain.push("(too many levels of symbolic links)".to_string());
+            break;
+        }
+        let target = match fs::read_link(&current) {
+            Ok(target) => target,
+            Err(e) => {
+                chain.push(format!("(unreadable: {})", e));
+                break;
+            }
+        };
+        chain.push(target.display().to_string());
+
+        current = match current.parent() {
+            Some(parent) => parent.join(&target),
+            None => PathBuf::from(&target),
+        };
+        match fs::symlink_metadata(&current) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {}
+            Ok(_) => break,
+            Err(_) => {
+                chain.push("(dangling)".to_string());
+                break;
+            }
+        }
+    }
+    chain.join(" -> ")
+}
+
+fn filesystem(path: &Path) -> String {
+    match posix::filesystem_type(path) {
+        Some(magic) => match filesystem_name(magic) {
+            Some(name) => format!("{} ({:#x})", name, magic),
+            None => format!("{:#x}", magic),
+        },
+        None => "?".to_string(),
+    }
+}
+
+/// Names of common filesystems by their statfs magic number
+fn filesystem_name(magic: u64) -> Option<&'static str> {
+    match magic {
+        0xef53 => Some("ext2/ext3/ext4"),
+        0x9123683e => Some("btrfs"),
+        0x58465342 => Some("xfs"),
+        0x01021994 => Some("tmpfs"),
+        0x794c7630 => Some("overlayfs"),
+        0x6969 => Some("nfs"),
+        0x9fa0 => Some("proc"),
+        0x62656572 => Some("sysfs"),
+        0x1373 => Some("devfs"),
+        0x4d44 => Some("vfat"),
+        0x5346544e => Some("ntfs"),
+        0x2fc12fc1 => Some("zfs"),
+        0x65735546 => Some("fuse"),
+        0xf2f52010 => Some("f2fs"),
+        0x9660 => Some("iso9660"),
+        _ => None,
+    }
+}
+
+fn xattrs(path: &Path) -> String {
+    match xattr::list(path) {
+        Ok(names) if names.is_empty() => "-".to_string(),
+        Ok(names) => names.join(", "),
+        Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => "not supported".to_string(),
+        Err(e) => format!("? ({})", e),
+    }
+}
+
@@ -0,0 +1,114 @@
+//! One JSON object per entry for `--json-lines`
+//!
+//! Each line is a whole object, so the output can be read by jq or a log
+//! processor while it is still being written. With `--sort=none` the entries of
+//! a directory are printed as they are read, without waiting for the rest.
+//!
+//! | key | value |
+//! |-----|-------|
+//! | `name` / `path` | name as listed / path |
+//! | `type` | file type, e.g. `regular file` |
+//! | `mode` | mode as in `ls -l` |
+//! | `size` / `blocks` | size in bytes / allocated blocks of 512 bytes |
+//! | `inode` / `links` | inode number / number of hard links |
+//! | `uid` / `gid` | user and group IDs |
+//! | `user` / `group` | owner and group names |
+//! | `modified` `accessed` `changed` `birth` | seconds since the epoch, to the nanosecond |
+//! | `target` | target of a symlink |
+//!
+//! Values that are not known, such as the name of a deleted user or the target
+//! of anything but a symlink, are `null`.
+use std::os::unix::fs::MetadataExt;
+
+use crate::{longformat, vfs, EntryData, TimeField};
+
+/// `text` as a JSON string
+/// Control characters are escaped even where JSON allows them, so that a
+/// record cannot send escape sequences to a terminal.
+fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A time as a JSON number of seconds, e.g. `1700000000.250000000`
+fn seconds((secs, nanos): (i64, u32)) -> String {
+    let total = i128::from(secs) * 1_000_000_000 + i128::from(nanos);
+    let sign = if total < 0 { "-" } else { "" };
+    let total = total.unsigned_abs();
+    format!("{}{}.{:09}", sign, total / 1_000_000_000, total % 1_000_000_000)
+}
+
+fn or_null(value: Option<String>) -> String {
+    value.unwrap_or_else(|| "null".to_string())
+}
+
+/// The JSON object describing an entry, on one line
+pub(crate) fn record(entry: &EntryData) -> String {
+    let metadata = &entry.metadata;
+    let time = |field: TimeField| {
+        let time = field.get(metadata).ok()?;
+        longformat::unix_time(&time).map(seconds)
+    };
+    let target = if metadata.is_symlink() { vfs::read_link(&entry.path).ok() } else { None };
+
+    let members = [
+        ("name", string(&entry.name)),
+        ("path", string(&entry.path.to_string_lossy())),
+        ("type", string(longformat::file_type_name(metadata.mode()))),
+        ("mode", string(&longformat::mode_string(metadata.mode()))),
+        ("size", metadata.len().to_string()),
+        ("blocks", metadata.blocks().to_string()),
+        ("inode", metadata.ino().to_string()),
+        ("links", metadata.nlink().to_string()),
+        ("uid", metadata.uid().to_string()),
+        ("gid", metadata.gid().to_string()),
+        ("user", or_null(users::get_user_by_uid(metadata.uid()).map(|u| string(&u.name().to_string_lossy())))),
+        ("group", or_null(users::get_group_by_gid(metadata.gid()).map(|g| string(&g.name().to_string_lossy())))),
+        ("modified", or_null(time(TimeField::Modified))),
+        ("accessed", or_null(time(TimeField::Accessed))),
+        ("changed", or_null(time(TimeField::Changed))),
+        ("birth", or_null(time(TimeField::Birth))),
+        ("target", or_null(target.map(|target| string(&target.to_string_lossy())))),
+    ];
+    let members: Vec<String> = members
+        .iter()
+        .map(|(key, value)| format!("\"{}\":{}", key, value))
+        .collect();
+    format!("{{{}}}", members.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{seconds, string};
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(string("plain"), "\"plain\"");
+        assert_eq!(string("say \"hi\"\\"), "\"say \\\"hi\\\"\\\\\"");
+        assert_eq!(string("a\nb\tc"), "\"a\\nb\\tc\"");
+        assert_eq!(string("evil\u{1b}[2J\u{9b}"), "\"evil\\u001b[2J\\u009b\"");
+        assert_eq!(string("café 日本"), "\"café 日本\"");
+    }
+
+    #[test]
+    fn writes_times_as_seconds() {
+        assert_eq!(seconds((1_700_000_000, 250_000_000)), "1700000000.250000000");
+        assert_eq!(seconds((0, 0)), "0.000000000");
+        // half a second before the epoch
+        assert_eq!(seconds((-1, 500_000_000)), "-0.500000000");
+        assert_eq!(seconds((-2, 0)), "-2.000000000");
+    }
+}
@@ -1,39 +1,276 @@
+// one odd file must never stop a whole listing, so errors are handled rather than unwrapped
+#![deny(clippy::unwrap_used, clippy::expect_used)]
+
 use std::{
-    fmt::{self, Display}, fs::{self, DirEntry, Metadata}, path::{self, PathBuf}
+    borrow::Cow,
+    collections::HashMap,
+    fmt::{self, Display}, fs::{self, DirEntry, Metadata}, io, path::{self, PathBuf}, sync::OnceLock
 };
 
+/// Write a line to an [`Output`], returning [`ListareError::Write`] rather than panicking when that fails
+macro_rules! outln {
+    ($out:expr) => {
+        outln!($out, "")
+    };
+    ($out:expr, $($arg:tt)*) => {{
+        let out: &mut $crate::Output = $out;
+        let end = out.end();
+        std::io::Write::write_fmt(out, format_args!("{}{}", format_args!($($arg)*), end))
+            .map_err($crate::ListareError::Write)
+    }};
+}
+
+mod annotate;
+mod background;
+mod capability;
+mod cell;
+mod choose;
+mod colors;
+pub mod config;
+mod controls;
+pub mod filter;
+#[cfg(all(feature = "fs-attributes", target_os = "linux"))]
+mod fsattr;
+mod hyperlink;
+mod icons;
+pub mod info;
 pub mod posix;
+pub mod shell;
 mod tabulate;
+mod theme;
+mod formatstring;
+mod jsonlines;
 mod longformat;
+mod lscolors;
+mod palette;
+#[cfg(feature = "memprofile")]
+pub mod memprofile;
+mod messages;
+mod pathutil;
+mod separators;
+mod size;
+mod scale;
+mod sort;
+mod timestyle;
+mod treesize;
+mod vfs;
+mod width;
+mod xattr;
+
+pub use background::Background;
+pub use filter::Filter;
+pub use formatstring::{FormatString, FormatStringError};
+pub use icons::Icons;
+pub use longformat::{Access, Alignment, EpochUnit, Field, TimeField};
+pub use lscolors::LsColors;
+pub use palette::Palette;
+pub use size::{SizeFormat, SizeFormatError};
+pub use scale::ColorScale;
+pub use separators::SeparatorStyle;
+pub use sort::SortKey;
+pub use theme::{ColorRule, Owner, Style, Theme};
+pub use timestyle::{parse_time_zone, TimeStyle, TimeStyleError};
+pub use width::line_length;
+
+
+use theme::Painted;
+
+use messages::Message;
+
+/// How entries are laid out, as chosen by `--format` or the flags standing for its values
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// A grid filled column by column (`-C`)
+    Vertical,
+    /// A grid filled line by line (`-x`)
+    Across,
+    /// Lines of names separated by commas (`-m`)
+    Commas,
+    /// One entry per line (`-1`)
+    SingleColumn,
+    /// One entry per line with its details (`-l`)
+    Long,
+}
 
-use colored::{ColoredString, Colorize};
-use tabulate::CharacterLength;
+/// Which characters follow names to tell their file types
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndicatorStyle {
+    None,
+    /// `/` after directories only (`-p`)
+    Slash,
+    /// `/` after directories, `*` after executables, `@` after symlinks, `|` after FIFOs and `=` after sockets (`-F`)
+    Classify,
+}
 
 #[derive(Debug)]
 pub struct Arguments {
+    /// `usize::MAX` when lines are never wrapped
     pub max_line_length: usize,
+    /// Cut names wider than a line in the grid layouts, as a terminal would wrap them
+    pub truncate_names: bool,
     pub paths: Vec<String>,
     pub list_dir_content: bool,
     pub show_hidden: bool,
-    pub by_lines: bool,
-    pub long_format: bool,
+    /// List the `.` and `..` entries of directories too, as `-a` does and `-A` does not
+    pub show_implied: bool,
+    pub layout: Layout,
+    /// Pad between grid columns with tabs to stops this far apart, 0 for spaces only
+    pub tab_size: usize,
+    /// Print a line of column titles above the long format
+    pub header: bool,
+    /// Lay out long format records side by side when the line is wide enough
+    pub long_grid: bool,
+    /// Print each entry on its own line from this template instead of a grid or long format
+    pub format_string: Option<FormatString>,
+    /// Let the user pick entries on the terminal and print their paths, instead of listing them
+    pub choose: bool,
+    /// End each line with a NUL instead of a newline, listing one entry per line
+    pub zero: bool,
+    /// Print each entry as a JSON object on its own line, without headings or blank lines
+    pub json_lines: bool,
+    /// Print the long format as a Markdown table, without the total
+    pub markdown: bool,
+    /// Write the listing to this file instead of stdout
+    pub output: Option<PathBuf>,
+    /// List directories whose entries would take more bytes than this in unsorted batches
+    pub max_memory: Option<u64>,
+    /// Only list the directories among the contents of directories (`--dirs-first-page`)
+    pub dirs_only: bool,
+    /// Mark world-writable and setuid files, and count them on stderr
+    pub warn_insecure: bool,
+    pub indicator_style: IndicatorStyle,
+    pub sort: SortKey,
+    /// Break ties between names by their bytes rather than the locale's collation
+    pub byte_order: bool,
+    pub annotate_cmd: Option<String>,
+    pub max_entries: Option<usize>,
+    pub epoch: Option<EpochUnit>,
+    pub size_format: SizeFormat,
+    pub time_style: TimeStyle,
+    pub show_inode: bool,
+    pub show_context: bool,
+    /// In long format, print the capabilities of each file
+    pub show_capabilities: bool,
+    /// Show the filesystem specific attributes of files, e.g. btrfs compression (feature `fs-attributes`)
+    pub show_fs_attributes: bool,
+    pub octal_permissions: bool,
+    /// In long format, show whether the user running listare has these accesses
+    pub can: Vec<Access>,
+    /// In long format, print the extended attributes of each entry below it
+    pub list_xattrs: bool,
+    pub long_alignment: HashMap<Field, Alignment>,
+    pub auto_shrink: bool,
+    pub numeric_ids: bool,
+    /// Cut owner and group names longer than this many columns in the long format
+    pub max_owner_width: Option<usize>,
+    pub show_blocks: bool,
+    pub total_apparent: bool,
+    /// In long format, show the size of each directory as that of everything beneath it
+    pub total_size: bool,
+    /// Count files with several hard links once in the total and in `total_size`
+    pub count_links_once: bool,
+    pub block_format: SizeFormat,
+    pub mark_groups: bool,
+    pub show_owner: bool,
+    pub show_group: bool,
+    pub normalize_targets: bool,
+    pub targets_relative_to: Option<PathBuf>,
+    /// Removed from the start of paths named on the command line where they are printed
+    pub strip_prefix: Option<PathBuf>,
+    pub count_skipped: bool,
+    pub time_field: TimeField,
+    /// Show the modification, access, status change and birth times in long format
+    pub all_times: bool,
+    pub separators: Option<SeparatorStyle>,
+    pub ambiguous_wide: bool,
+    /// Show control characters in names and other fields as `?`
+    pub hide_control_chars: bool,
+    pub theme: Theme,
+    /// Make names links to their files, with OSC 8 escape sequences
+    pub hyperlink: bool,
+    /// Color the size and time columns by where they fall among the entries
+    pub color_scale: Option<ColorScale>,
+    /// Put an icon before each name, from these and the built-in Nerd Font icons
+    pub icons: Option<Icons>,
+    /// Only list the contents of directories that this keeps
+    pub filter: Option<Box<dyn Filter>>,
+}
+
+/// Where a listing is written, and the character that ends its lines
+pub(crate) struct Output<'a> {
+    out: &'a mut dyn io::Write,
+    end: char, // a NUL with `--zero`, otherwise a newline
+}
+
+impl<'a> Output<'a> {
+    pub(crate) fn new(out: &'a mut dyn io::Write, end: char) -> Self {
+        Output { out, end }
+    }
+
+    /// Lines of text for people to read, ending with newlines
+    pub(crate) fn text(out: &'a mut dyn io::Write) -> Self {
+        Output::new(out, '\n')
+    }
+
+    pub(crate) fn end(&self) -> char {
+        self.end
+    }
+}
+
+impl io::Write for Output<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.out.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
 }
 
+/// A file being listed
 #[derive(Clone, Debug)]
-struct EntryData {
+pub struct EntryData {
     metadata: Metadata,
     path: PathBuf,
     name: String,
+    target: OnceLock<Option<Metadata>>, // of what a symlink points to, once looked up
 }
 
 impl EntryData {
+    /// The metadata of the file itself, not of what a symlink points to
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// The path given on the command line, or of an entry of a listed directory
+    pub fn path(&self) -> &path::Path {
+        &self.path
+    }
+
+    /// The name as it is listed
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The `.` or `..` entry of a directory, which reading it leaves out
+    fn implied(dir: &path::Path, name: &str) -> Result<Self, std::io::Error> {
+        let path = dir.join(name);
+        Ok(EntryData {
+            metadata: vfs::symlink_metadata(&path)?,
+            path,
+            name: name.to_string(),
+            target: OnceLock::new(),
+        })
+    }
+
     fn from_path_str(path_str: &str) -> Result<Self, std::io::Error> {
         let path = path::PathBuf::from(path_str);
-        let metadata = fs::symlink_metadata(&path)?;
+        let metadata = vfs::symlink_metadata(&path)?;
         Ok(EntryData {
             metadata,
             path,
             name: path_str.to_string(),
+            target: OnceLock::new(),
         })
     }
 
@@ -45,14 +282,15 @@ impl EntryData {
             .to_string();
         let abspath = root.join(&relpath);
         Ok(EntryData {
-            metadata: fs::symlink_metadata(&abspath)?,
+            metadata: vfs::symlink_metadata(&abspath)?,
             path: relpath,
-            name: name,
+            name,
+            target: OnceLock::new(),
         })
     }
 
     fn from_path(path: path::PathBuf) -> Result<Self, std::io::Error> {
-        let metadata = fs::symlink_metadata(&path)?;
+        let metadata = vfs::symlink_metadata(&path)?;
         let name = path
             .file_name()
             .ok_or(std::io::Error::from(std::io::ErrorKind::InvalidInput))?
@@ -62,12 +300,13 @@ impl EntryData {
             metadata,
             path,
             name,
+            target: OnceLock::new(),
         })
     }
 
     fn from_direntry(entry: DirEntry) -> Result<Self, std::io::Error> {
-        let metadata = entry.metadata()?;
         let path = entry.path();
+        let metadata = vfs::symlink_metadata(&path)?;
         let name = path
             .file_name()
             .ok_or(std::io::Error::from(std::io::ErrorKind::InvalidInput))?
@@ -77,48 +316,75 @@ impl EntryData {
             metadata,
             path,
             name,
+            target: OnceLock::new(),
         })
     }
 
-    fn colored_name(&self) -> ColoredString {
-        self.colored(&self.name)
+    /// The metadata of what a symlink points to, None if it dangles
+    /// It is looked up once, as coloring a name may need it more than once.
+    pub(crate) fn target_metadata(&self) -> Option<&Metadata> {
+        self.target.get_or_init(|| vfs::metadata(&self.path).ok()).as_ref()
     }
 
-    fn colored_path(&self) -> ColoredString {
-        self.colored(&self.path.to_string_lossy())
+    /// Whether this is a world-writable or setuid regular file, flagged by `--warn-insecure`
+    fn is_insecure(&self) -> bool {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = self.metadata.permissions().mode();
+        self.metadata.is_file() && (mode & libc::S_IWOTH != 0 || mode & libc::S_ISUID != 0)
     }
 
-    fn colored(&self, text: &str) -> ColoredString {
-        if self.metadata.is_symlink() {
-            let link_exists = fs::metadata(&self.path).is_ok();
-
-            if link_exists {
-                text.bold().cyan()
-            } else {
-                text.bold().red()
+    /// The character telling the file type of an entry, if the style gives it one
+    fn indicator(&self, style: IndicatorStyle) -> Option<char> {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+        let file_type = self.metadata.file_type();
+        match style {
+            IndicatorStyle::None => None,
+            IndicatorStyle::Slash | IndicatorStyle::Classify if file_type.is_dir() => Some('/'),
+            IndicatorStyle::Slash => None,
+            IndicatorStyle::Classify if file_type.is_symlink() => Some('@'),
+            IndicatorStyle::Classify if file_type.is_fifo() => Some('|'),
+            IndicatorStyle::Classify if file_type.is_socket() => Some('='),
+            IndicatorStyle::Classify if file_type.is_file() && self.metadata.permissions().mode() & 0o111 != 0 => {
+                Some('*')
             }
-        } else if self.metadata.is_dir() {
-            text.bold().blue()
-        } else {
-            text.normal()
+            IndicatorStyle::Classify => None,
         }
     }
-}
 
-impl Display for EntryData {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{:width$}",
-            self.colored_name(),
-            width = f.width().unwrap_or(self.characters_long())
-        )
+    /// The markers after the name of an entry: its type indicator, then the insecure marker
+    fn suffix(&self, args: &Arguments) -> String {
+        self.markers(args.indicator_style, args.warn_insecure)
     }
+
+    /// The file type indicator and `--warn-insecure` marker following the name
+    fn markers(&self, style: IndicatorStyle, warn_insecure: bool) -> String {
+        let mut suffix: String = self.indicator(style).into_iter().collect();
+        if warn_insecure && self.is_insecure() {
+            suffix.push_str(INSECURE_MARKER);
+        }
+        suffix
+    }
+
+    /// The name as it is printed, without control characters if they are hidden
+    fn shown_name(&self, args: &Arguments) -> Cow<'_, str> {
+        controls::hide(&self.name, args.hide_control_chars)
+    }
+
+    fn colored_name(&self, args: &Arguments) -> Painted {
+        args.theme.paint(self, &self.shown_name(args))
+    }
+}
+
+/// Pad with spaces after text that takes `used` columns, up to the width requested by the formatter
+/// The formatter's own padding counts characters, not columns.
+fn pad(f: &mut fmt::Formatter, used: usize) -> fmt::Result {
+    let padding = f.width().unwrap_or(0).saturating_sub(used);
+    write!(f, "{:padding$}", "", padding = padding)
 }
 
 impl tabulate::CharacterLength for EntryData {
     fn characters_long(&self) -> usize {
-        self.name.chars().count()
+        width::display_width(&self.name, false)
     }
 }
 
@@ -126,143 +392,538 @@ fn is_hidden(entry: &DirEntry) -> bool {
     use std::os::unix::ffi::OsStrExt;
     if cfg!(target_os = "linux") {
         // if linux, check if the first byte is a period
-        *entry
-            .file_name()
-            .as_os_str()
-            .as_bytes()
-            .get(0)
-            .unwrap_or(&b' ')
-            == b'.'
+        filter::is_hidden_name(entry.file_name().as_os_str().as_bytes())
     } else {
         false
     }
 }
 
-fn get_children(dir: fs::ReadDir, include_hidden: bool) -> Vec<EntryData> {
-    dir.into_iter()
-        .filter_map(|e| {
-            let entry = e.ok()?;
-            if entry.file_name().is_empty() {
-                eprintln!("Could not read file name of {:?}", entry);
-                return None;
+/// Directory entries left out of a listing, counted by the reason they were skipped
+#[derive(Debug, Default)]
+struct Skipped {
+    hidden: usize,
+    unreadable: usize,
+}
+
+impl Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut counts = Vec::new();
+        if self.hidden > 0 {
+            counts.push(messages::format(Message::Hidden, &self.hidden.to_string()));
+        }
+        if self.unreadable > 0 {
+            counts.push(messages::format(Message::Unreadable, &self.unreadable.to_string()));
+        }
+        write!(f, "({})", counts.join(", "))
+    }
+}
+
+/// Whether an entry is a directory or a symlink to one
+/// The type comes from the directory listing where the filesystem provides it,
+/// so that other entries are dropped without a stat call.
+fn is_dir_entry(entry: &DirEntry) -> bool {
+    match entry.file_type() {
+        Ok(file_type) if file_type.is_symlink() => vfs::metadata(&entry.path()).is_ok_and(|m| m.is_dir()),
+        Ok(file_type) => file_type.is_dir(),
+        // leave the error to be reported when the entry is read
+        Err(_) => true,
+    }
+}
+
+/// Directories with at least this many entries are read in inode order
+const INODE_ORDER_THRESHOLD: usize = 1000;
+
+/// Read the entries of a directory, in the order they are best looked up in
+/// Given a limit, reading stops once the entries read would take more bytes
+/// of memory than that, leaving `dir_iter` at the next one, and the last
+/// value returned is true. The estimate counts the metadata, path and name
+/// kept for each entry.
+fn read_dir_entries(
+    dir: &EntryData,
+    dir_iter: &mut fs::ReadDir,
+    limit: Option<u64>,
+    args: &Arguments,
+    problems: &mut Problems,
+) -> (Vec<DirEntry>, Skipped, bool) {
+    let mut skipped = Skipped::default();
+    let mut dir_entries = Vec::new();
+    let overhead = (std::mem::size_of::<EntryData>() + dir.path.as_os_str().len() + 1) as u64;
+    let mut projected = 0;
+    for entry in dir_iter.by_ref() {
+        match entry {
+            Ok(entry) => {
+                projected += overhead + 2 * entry.file_name().len() as u64;
+                dir_entries.push(entry);
+                if limit.is_some_and(|limit| projected > limit) {
+                    return (dir_entries, skipped, true);
+                }
             }
-            if !include_hidden && is_hidden(&entry) {
-                // hidden file
-                return None;
+            Err(err) => {
+                problems.minor(Message::ReadingDirectory, &dir.path, &err);
+                skipped.unreadable += 1;
             }
-            Some(EntryData::from_direntry(entry).ok()?)
-        })
-        .collect()
+        }
+    }
+    // the entries are sorted for display later, so stat them in the order
+    // that is usually closest to the order of the inodes on disk
+    if dir_entries.len() >= INODE_ORDER_THRESHOLD && args.sort != SortKey::None {
+        use std::os::unix::fs::DirEntryExt;
+        dir_entries.sort_unstable_by_key(|entry| entry.ino());
+    }
+    (dir_entries, skipped, false)
 }
 
-fn tabulate_entries(entries: &[EntryData], args: &Arguments) {
-    if entries.is_empty() {
-        return;
-    }
-
-    println!(
-        "{}",
-        tabulate::Tabulator::new(
-            &entries,
-            args.max_line_length,
-            if args.by_lines {
-                tabulate::TabulateOrientation::Rows
-            } else {
-                tabulate::TabulateOrientation::Columns
+/// The entries of `dir` that are listed among `dir_entries`, after `.` and `..` when `implied` is set
+/// Hidden entries are left out unless `-a` or `-A` asks for them, and only
+/// `-a` asks for the implied ones, which reading a directory never gives.
+fn get_children(
+    dir: &EntryData,
+    dir_entries: impl IntoIterator<Item = DirEntry>,
+    implied: bool,
+    args: &Arguments,
+    problems: &mut Problems,
+    skipped: &mut Skipped,
+) -> Vec<EntryData> {
+    let mut children = if implied { implied_entries(dir, args, problems, skipped) } else { Vec::new() };
+    children.extend(dir_entries.into_iter().filter_map(|entry| child_entry(entry, args, problems, skipped)));
+    children
+}
+
+/// The `.` and `..` entries of `dir` that are listed
+fn implied_entries(dir: &EntryData, args: &Arguments, problems: &mut Problems, skipped: &mut Skipped) -> Vec<EntryData> {
+    let mut entries = Vec::new();
+    for name in [".", ".."] {
+        match EntryData::implied(&dir.path, name) {
+            Ok(data) if args.filter.as_ref().is_some_and(|filter| !filter.keep(&data)) => {}
+            Ok(data) => entries.push(data),
+            Err(err) => {
+                problems.minor(Message::CannotAccess, &dir.path.join(name), &err);
+                skipped.unreadable += 1;
             }
-        )
-    );
+        }
+    }
+    entries
 }
 
+/// The entry for a child of a directory, None if it is left out of the listing
+fn child_entry(entry: DirEntry, args: &Arguments, problems: &mut Problems, skipped: &mut Skipped) -> Option<EntryData> {
+    if entry.file_name().is_empty() {
+        eprintln!("Could not read file name of {:?}", entry);
+        skipped.unreadable += 1;
+        return None;
+    }
+    if !args.show_hidden && is_hidden(&entry) {
+        // hidden file
+        skipped.hidden += 1;
+        return None;
+    }
+    if args.dirs_only && !is_dir_entry(&entry) {
+        return None;
+    }
+    let path = entry.path();
+    match EntryData::from_direntry(entry) {
+        Ok(data) if args.filter.as_ref().is_some_and(|filter| !filter.keep(&data)) => None,
+        Ok(data) => Some(data),
+        Err(err) => {
+            problems.minor(Message::CannotAccess, &path, &err);
+            skipped.unreadable += 1;
+            None
+        }
+    }
+}
+
+/// Print the children of a directory as JSON lines as they are read, for `--json-lines --sort=none`
+/// Huge directories are listed without holding all of their entries in memory.
+fn stream_children(
+    dir: &EntryData,
+    dir_iter: fs::ReadDir,
+    args: &Arguments,
+    counter: &mut EntryCounter,
+    problems: &mut Problems,
+    out: &mut Output,
+) -> Result<(), ListareError> {
+    let mut skipped = Skipped::default();
+    if args.show_implied {
+        for data in implied_entries(dir, args, problems, &mut skipped) {
+            counter.add(std::slice::from_ref(&data))?;
+            outln!(out, "{}", jsonlines::record(&data))?;
+        }
+    }
+    for entry in dir_iter {
+        match entry {
+            Ok(entry) => {
+                if let Some(data) = child_entry(entry, args, problems, &mut skipped) {
+                    counter.add(std::slice::from_ref(&data))?;
+                    outln!(out, "{}", jsonlines::record(&data))?;
+                }
+            }
+            Err(err) => problems.minor(Message::ReadingDirectory, &dir.path, &err),
+        }
+    }
+    Ok(())
+}
 
+/// Follows the names of world-writable and setuid files with `--warn-insecure`
+const INSECURE_MARKER: &str = "!";
 
-fn list_entries(mut entries: Vec<EntryData>, args: &Arguments) {
-    entries.sort_by(|a, b| posix::strcoll(&a.name, &b.name));
+fn tabulate_entries(entries: &[EntryData], args: &Arguments, out: &mut Output) -> Result<(), ListareError> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let mut cells = cell::grid_cells(entries, args);
+    // a grid too narrow for a name is a single column, of names cut to fit
+    if args.truncate_names && matches!(args.layout, Layout::Across | Layout::Vertical) {
+        for cell in &mut cells {
+            cell.fit(args.max_line_length, args.ambiguous_wide);
+        }
+    }
+    let grid = |orientation| tabulate::Tabulator::new(&cells, args.max_line_length, orientation).tab_size(args.tab_size);
+    match args.layout {
+        Layout::SingleColumn | Layout::Long => {
+            for cell in &cells {
+                outln!(out, "{}", cell)?;
+            }
+            Ok(())
+        }
+        Layout::Commas => outln!(out, "{}", tabulate::CommaSeparated::new(&cells, args.max_line_length)),
+        Layout::Across => outln!(out, "{}", grid(tabulate::TabulateOrientation::Rows)),
+        Layout::Vertical => outln!(out, "{}", grid(tabulate::TabulateOrientation::Columns)),
+    }
+}
 
-    if args.long_format {
-        longformat::longformat_tabulate_entries(&entries, args);
+/// `dir_contents` is true when the entries are the contents of a directory
+fn list_entries(
+    entries: Vec<EntryData>,
+    sort: SortKey,
+    args: &Arguments,
+    dir_contents: bool,
+    out: &mut Output,
+) -> Result<(), ListareError> {
+    let entries = sort::sort_entries(entries, sort, args.byte_order, &cell::name_width(args));
+    let separators = match args.separators {
+        Some(style) => separators::separators(&entries, sort, style),
+        None => Vec::new(),
+    };
+
+    if args.json_lines {
+        for entry in &entries {
+            outln!(out, "{}", jsonlines::record(entry))?;
+        }
+        Ok(())
+    } else if let Some(format) = &args.format_string {
+        for entry in &entries {
+            outln!(out, "{}", format.render(entry, args.hide_control_chars))?;
+        }
+        Ok(())
+    } else if args.layout == Layout::Long {
+        longformat::longformat_tabulate_entries(&entries, args, dir_contents, &separators, out)
     } else {
-        tabulate_entries(&entries, args);
+        // each group is laid out as its own grid
+        let mut start = 0;
+        for (end, line) in &separators {
+            if start < *end {
+                tabulate_entries(&entries[start..*end], args, out)?;
+            }
+            outln!(out, "{}", line)?;
+            start = *end;
+        }
+        tabulate_entries(&entries[start..], args, out)
     }
 }
 
-fn list_dirs(dirs: &[EntryData], args: &Arguments, headings: bool) -> Result<(), ListareError> {
+/// Whether listing the contents of a directory starts with the total of the long format
+fn shows_total(args: &Arguments) -> bool {
+    args.layout == Layout::Long && !args.json_lines && args.format_string.is_none() && !args.markdown
+}
+
+/// Counts the entries listed so far, enforcing the optional limit on their number
+/// and keeping count of insecure files for `--warn-insecure`
+struct EntryCounter {
+    limit: Option<usize>,
+    seen: usize,
+    insecure: usize, // world-writable and setuid files among those seen
+}
+
+impl EntryCounter {
+    fn new(limit: Option<usize>) -> Self {
+        EntryCounter { limit, seen: 0, insecure: 0 }
+    }
+
+    /// Account for more entries, failing if that would exceed the limit
+    fn add(&mut self, entries: &[EntryData]) -> Result<(), ListareError> {
+        self.seen += entries.len();
+        self.insecure += entries.iter().filter(|e| e.is_insecure()).count();
+        match self.limit {
+            Some(limit) if self.seen > limit => Err(ListareError::LimitExceeded(limit)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn list_dirs(
+    dirs: &[EntryData],
+    args: &Arguments,
+    headings: bool,
+    counter: &mut EntryCounter,
+    problems: &mut Problems,
+    out: &mut Output,
+) -> Result<(), ListareError> {
     for (i, dir) in dirs.iter().enumerate() {
-        if let Ok(dir_iter) = fs::read_dir(&dir.path) {
-            if headings {
-                println!("{}:", dir.name);
+        match vfs::read_dir(&dir.path) {
+            Ok(dir_iter) if args.json_lines && args.sort == SortKey::None => {
+                stream_children(dir, dir_iter, args, counter, problems, out)?;
+            }
+            Ok(mut dir_iter) => {
+                let (dir_entries, mut skipped, over_limit) =
+                    read_dir_entries(dir, &mut dir_iter, args.max_memory, args, problems);
+                // every JSON record names its path, so they need no headings or blank lines
+                let headings = headings && !args.json_lines;
+
+                if over_limit {
+                    let path = format!("'{}'", dir.path.display());
+                    eprintln!("listare: {}", messages::format(Message::Batched, &path));
+                    if headings {
+                        outln!(out, "{}:", dir.shown_name(args))?;
+                    }
+                    // the entries are listed as they are read, as many at a time as were read
+                    // before reaching the limit, so their order is the directory's own
+                    let batch = dir_entries.len();
+                    let mut dir_entries = dir_entries.into_iter().map(Ok).chain(dir_iter).peekable();
+                    // the implied entries come with the first batch
+                    let mut implied = args.show_implied;
+                    let mut total = longformat::Total::new();
+                    while dir_entries.peek().is_some() {
+                        let mut read = Vec::with_capacity(batch);
+                        for entry in dir_entries.by_ref().take(batch) {
+                            match entry {
+                                Ok(entry) => read.push(entry),
+                                Err(err) => {
+                                    problems.minor(Message::ReadingDirectory, &dir.path, &err);
+                                    skipped.unreadable += 1;
+                                }
+                            }
+                        }
+                        let children =
+                            get_children(dir, read, std::mem::take(&mut implied), args, problems, &mut skipped);
+                        counter.add(&children)?;
+                        for child in &children {
+                            total.add(&child.metadata, args);
+                        }
+                        list_entries(children, SortKey::None, args, false, out)?;
+                    }
+                    // only known once every entry was read, so it ends the listing rather than starting it
+                    if shows_total(args) {
+                        total.print(args, out)?;
+                    }
+                } else {
+                    let children = get_children(dir, dir_entries, args.show_implied, args, problems, &mut skipped);
+                    counter.add(&children)?;
+                    if headings {
+                        outln!(out, "{}:", dir.shown_name(args))?;
+                    }
+                    list_entries(children, args.sort, args, true, out)?;
+                }
+
+                if args.json_lines {
+                    continue;
+                }
+
+                if args.count_skipped && (skipped.hidden > 0 || skipped.unreadable > 0) {
+                    outln!(out, "{}", skipped)?;
+                }
+
+                if i != dirs.len() - 1 {
+                    outln!(out)?;
+                }
             }
+            Err(err) => problems.serious(Message::CannotOpenDirectory, &dir.path, &err),
+        }
+    }
+    Ok(())
+}
 
-            list_entries(get_children(dir_iter, args.show_hidden), args);
+/// The description of an I/O error, without the "(os error N)" that std appends
+pub(crate) fn io_error_text(err: &std::io::Error) -> String {
+    let text = err.to_string();
+    match text.find(" (os error ") {
+        Some(idx) => text[..idx].to_string(),
+        None => text,
+    }
+}
 
-            if i != dirs.len() - 1 {
-                println!();
-            }
+/// Errors reported while listing
+/// Listing carries on past them, and the run fails once everything else is listed.
+#[derive(Debug, Default)]
+struct Problems {
+    serious: bool, // a path named on the command line could not be listed
+    minor: bool,   // an entry inside a directory could not be listed
+}
+
+impl Problems {
+    fn report(action: Message, path: &path::Path, err: &std::io::Error) {
+        let action = messages::format(action, &format!("'{}'", path.display()));
+        eprintln!("listare: {}: {}", action, io_error_text(err));
+    }
+
+    fn serious(&mut self, action: Message, path: &path::Path, err: &std::io::Error) {
+        Problems::report(action, path, err);
+        self.serious = true;
+    }
+
+    fn minor(&mut self, action: Message, path: &path::Path, err: &std::io::Error) {
+        Problems::report(action, path, err);
+        self.minor = true;
+    }
+
+    fn into_result(self) -> Result<(), ListareError> {
+        if self.serious || self.minor {
+            Err(ListareError::PathErrors { serious: self.serious })
         } else {
-            eprintln!("Could not read directory: {}", &dir.name);
+            Ok(())
         }
     }
-    Ok(())
 }
 
 #[derive(Debug)]
 pub enum ListareError {
     Unknown,
     Generic(String),
+    LimitExceeded(usize), // more entries than the given limit would have been listed
+    PathErrors { serious: bool }, // some paths could not be listed, and were reported as they were found
+    Cancelled,                    // the user backed out of --choose
+    Write(std::io::Error),        // the listing could not be written out
 }
 
 impl std::error::Error for ListareError {}
 impl fmt::Display for ListareError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ListareError::Unknown => write!(f, "An unknown error occurred"),
+            ListareError::Unknown => write!(f, "{}", messages::text(Message::UnknownError)),
             ListareError::Generic(msg) => write!(f, "{}", msg),
+            ListareError::LimitExceeded(limit) => {
+                write!(f, "{}", messages::format(Message::LimitExceeded, &limit.to_string()))
+            }
+            ListareError::PathErrors { .. } => write!(f, "{}", messages::text(Message::PathErrors)),
+            ListareError::Cancelled => write!(f, "{}", messages::text(Message::Cancelled)),
+            ListareError::Write(err) => {
+                write!(f, "listare: {}", messages::format(Message::WriteError, &io_error_text(err)))
+            }
         }
     }
 }
 
-fn split_files_dirs(paths: &[String]) -> (Vec<EntryData>, Vec<EntryData>) {
-    let mut files = Vec::new();
-    let mut dirs = Vec::new();
+/// The entries for paths named on the command line, reporting those that cannot be accessed
+/// Each is named by its path, less `strip_prefix` when the path starts with it.
+fn named_entries(paths: &[String], strip_prefix: Option<&path::Path>, problems: &mut Problems) -> Vec<EntryData> {
+    paths
+        .iter()
+        .filter_map(|path| match EntryData::from_path_str(path) {
+            Ok(mut entry) => {
+                if let Some(rest) = strip_prefix.and_then(|prefix| entry.path.strip_prefix(prefix).ok()) {
+                    entry.name = if rest.as_os_str().is_empty() {
+                        ".".to_string()
+                    } else {
+                        rest.to_string_lossy().into_owned()
+                    };
+                }
+                Some(entry)
+            }
+            Err(err) => {
+                problems.serious(Message::CannotAccess, path::Path::new(path), &err);
+                None
+            }
+        })
+        .collect()
+}
+
+fn split_files_dirs(entries: Vec<EntryData>) -> (Vec<EntryData>, Vec<EntryData>) {
+    entries.into_iter().partition(|entry| !entry.metadata.is_dir())
+}
 
-    for path in paths {
-        if let Ok(entry) = EntryData::from_path_str(path) {
-            if entry.metadata.is_dir() {
-                dirs.push(entry);
-            } else {
-                files.push(entry);
+/// Print the paths of the entries the user picks among those that would be listed
+fn choose_entries(args: &Arguments, out: &mut Output) -> Result<(), ListareError> {
+    let mut problems = Problems::default();
+    let entries = named_entries(&args.paths, args.strip_prefix.as_deref(), &mut problems);
+    let (files, dirs) = if args.list_dir_content { split_files_dirs(entries) } else { (entries, Vec::new()) };
+
+    let mut candidates = sort::sort_entries(files, args.sort, args.byte_order, &cell::name_width(args));
+    for dir in &dirs {
+        match vfs::read_dir(&dir.path) {
+            Ok(mut dir_iter) => {
+                // the user picks among every entry, so they are all read
+                let (dir_entries, mut skipped, _) = read_dir_entries(dir, &mut dir_iter, None, args, &mut problems);
+                let children = get_children(dir, dir_entries, args.show_implied, args, &mut problems, &mut skipped);
+                candidates.extend(sort::sort_entries(children, args.sort, args.byte_order, &cell::name_width(args)));
             }
+            Err(err) => problems.serious(Message::CannotOpenDirectory, &dir.path, &err),
         }
     }
 
-    (files, dirs)
+    for entry in choose::choose(&candidates)? {
+        outln!(out, "{}", entry.path.display())?;
+    }
+    problems.into_result()
 }
 
+/// List as `args` asks, on stdout or in the `--output` file
 pub fn run(args: &Arguments) -> Result<(), ListareError> {
+    match &args.output {
+        Some(path) => {
+            let file = fs::File::create(path).map_err(|e| {
+                let action = messages::format(Message::CannotCreate, &format!("'{}'", path.display()));
+                ListareError::Generic(format!("listare: {}: {}", action, io_error_text(&e)))
+            })?;
+            run_to(args, &mut io::BufWriter::new(file))
+        }
+        None => run_to(args, &mut io::stdout().lock()),
+    }
+}
+
+/// List as `args` asks, writing the listing to `out`
+/// Warnings and errors are still reported on stderr. `out` is flushed at the
+/// end, so that a failure to write the last of a buffered listing is returned.
+pub fn run_to(args: &Arguments, out: &mut dyn io::Write) -> Result<(), ListareError> {
+    let mut out = Output::new(out, if args.zero { '\0' } else { '\n' });
+    let result = list_paths(args, &mut out);
+    // failing to write is worse than failing to list some paths
+    io::Write::flush(&mut out).map_err(ListareError::Write).and(result)
+}
+
+fn list_paths(args: &Arguments, out: &mut Output) -> Result<(), ListareError> {
+    if args.choose {
+        return choose_entries(args, out);
+    }
+    let mut counter = EntryCounter::new(args.max_entries);
+    let mut problems = Problems::default();
+    let entries = named_entries(&args.paths, args.strip_prefix.as_deref(), &mut problems);
+
     if args.list_dir_content {
-        let (files, dirs) = split_files_dirs(&args.paths);
+        let (files, dirs) = split_files_dirs(entries);
         let had_files = !files.is_empty();
+        counter.add(&files)?;
 
         if had_files {
-            list_entries(files, args);
+            list_entries(files, args.sort, args, false, out)?;
         }
 
         if !dirs.is_empty() {
-            if had_files {
-                println!();
+            if had_files && !args.json_lines {
+                outln!(out)?;
             }
 
-            let headings: bool = had_files || (dirs.len() > 1);
-            list_dirs(&dirs, args, headings)?;
+            // as in ls, headings are shown whenever more than one path was named
+            let headings: bool = args.paths.len() > 1;
+            list_dirs(&dirs, args, headings, &mut counter, &mut problems, out)?;
         }
     } else {
-        let entries = args
-            .paths
-            .iter()
-            .filter_map(|path| EntryData::from_path_str(path).ok())
-            .collect();
-        list_entries(entries, args);
+        counter.add(&entries)?;
+        list_entries(entries, args.sort, args, false, out)?;
     }
 
-    Ok(())
+    if args.warn_insecure && counter.insecure > 0 {
+        eprintln!("listare: {}", messages::format(Message::Insecure, &counter.insecure.to_string()));
+    }
+
+    problems.into_result()
 }
@@ -1,127 +1,472 @@
-use crate::{Arguments, EntryData};
-use std::fmt;
+use crate::messages::{self, Message};
+use crate::scale::{ColorScale, Gradient};
+use crate::treesize::TreeSizes;
+use crate::{capability, controls, hyperlink, pathutil, posix, size, tabulate, timestyle, vfs, width, xattr, Arguments, EntryData, ListareError, Output};
+use chrono::Datelike;
+use std::fmt::{self, Write};
+use std::path::Path;
 use std::os::unix::fs::{FileTypeExt, MetadataExt};
-use std::time::SystemTime;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs::Metadata;
+use std::time::{Duration, SystemTime};
 
+/// The unit of integer timestamps printed instead of formatted dates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EpochUnit {
+    Seconds,
+    Milliseconds,
+    Nanoseconds,
+}
 
-struct Config {
-    size_width: usize,
-    user_width: usize,
-    group_width: usize,
-    nlinks_width: usize,
+/// A kind of access to a file, shown by `--can`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Execute, // or search, for directories
+}
+
+/// Which of the timestamps of a file is shown and sorted by
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeField {
+    Modified,
+    Accessed,
+    Changed, // status change, e.g. of permissions or ownership
+    Birth,
+}
+
+impl TimeField {
+    /// The timestamp of a file
+    /// Birth time is only available on some systems and filesystems.
+    pub(crate) fn get(self, metadata: &Metadata) -> std::io::Result<SystemTime> {
+        match self {
+            TimeField::Modified => metadata.modified(),
+            TimeField::Accessed => metadata.accessed(),
+            TimeField::Changed => change_time(metadata)
+                .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::InvalidData)),
+            TimeField::Birth => metadata.created(),
+        }
+    }
+}
+
+/// The status change time, which std only exposes as raw fields
+pub(crate) fn change_time(metadata: &Metadata) -> Option<SystemTime> {
+    let nanos = Duration::from_nanos(metadata.ctime_nsec() as u64);
+    match u64::try_from(metadata.ctime()) {
+        Ok(secs) => SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs) + nanos),
+        Err(_) => SystemTime::UNIX_EPOCH
+            .checked_sub(Duration::from_secs(metadata.ctime().unsigned_abs()))?
+            .checked_add(nanos),
+    }
+}
+
+/// A column of the long format
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Field {
+    Inode,
+    Blocks,
+    Octal,
+    Mode,
+    Access,
+    Links,
+    Owner,
+    Group,
+    Context,
+    Capabilities,
+    FsAttributes,
+    Size,
+    Time,
+    ModifiedTime,
+    AccessTime,
+    ChangeTime,
+    BirthTime,
+    Annotation,
+    Name,
+}
+
+impl Field {
+    /// Look up a field by the name used for it in the config file
+    pub fn from_name(name: &str) -> Option<Field> {
+        match name {
+            "inode" => Some(Field::Inode),
+            "blocks" => Some(Field::Blocks),
+            "octal" => Some(Field::Octal),
+            "mode" => Some(Field::Mode),
+            "can" => Some(Field::Access),
+            "links" => Some(Field::Links),
+            "owner" => Some(Field::Owner),
+            "group" => Some(Field::Group),
+            "context" => Some(Field::Context),
+            "capabilities" => Some(Field::Capabilities),
+            "fsattr" => Some(Field::FsAttributes),
+            "size" => Some(Field::Size),
+            "time" => Some(Field::Time),
+            "mtime" => Some(Field::ModifiedTime),
+            "atime" => Some(Field::AccessTime),
+            "ctime" => Some(Field::ChangeTime),
+            "birth" => Some(Field::BirthTime),
+            "annotation" => Some(Field::Annotation),
+            "name" => Some(Field::Name),
+            _ => None,
+        }
+    }
+
+    /// The title of the field's column in the `--header` line, in the user's language
+    fn title(self) -> &'static str {
+        messages::text(match self {
+            Field::Inode => Message::TitleInode,
+            Field::Blocks => Message::TitleBlocks,
+            Field::Octal => Message::TitleOctal,
+            Field::Mode => Message::TitlePermissions,
+            Field::Access => Message::TitleCan,
+            Field::Links => Message::TitleLinks,
+            Field::Owner => Message::TitleOwner,
+            Field::Group => Message::TitleGroup,
+            Field::Context => Message::TitleContext,
+            Field::Capabilities => Message::TitleCapabilities,
+            Field::FsAttributes => Message::TitleAttributes,
+            Field::Size => Message::TitleSize,
+            Field::Time => Message::TitleDate,
+            Field::ModifiedTime => Message::TitleModified,
+            Field::AccessTime => Message::TitleAccessed,
+            Field::ChangeTime => Message::TitleChanged,
+            Field::BirthTime => Message::TitleBirth,
+            Field::Annotation => Message::TitleAnnotation,
+            Field::Name => Message::TitleName,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Alignment {
+    Left,
+    Right,
+}
+
+/// How a field is laid out in the long format
+#[derive(Clone, Copy, Debug)]
+struct FieldDescriptor {
+    field: Field,
+    align: Alignment,
+}
+
+/// The fields of the long format in the order they are printed, with their default alignment
+/// The name is always last and never padded.
+const FIELDS: [FieldDescriptor; 19] = [
+    FieldDescriptor { field: Field::Inode, align: Alignment::Right },
+    FieldDescriptor { field: Field::Blocks, align: Alignment::Right },
+    FieldDescriptor { field: Field::Octal, align: Alignment::Left },
+    FieldDescriptor { field: Field::Mode, align: Alignment::Left },
+    FieldDescriptor { field: Field::Access, align: Alignment::Left },
+    FieldDescriptor { field: Field::Links, align: Alignment::Right },
+    FieldDescriptor { field: Field::Owner, align: Alignment::Left },
+    FieldDescriptor { field: Field::Group, align: Alignment::Left },
+    FieldDescriptor { field: Field::Context, align: Alignment::Left },
+    FieldDescriptor { field: Field::Capabilities, align: Alignment::Left },
+    FieldDescriptor { field: Field::FsAttributes, align: Alignment::Left },
+    FieldDescriptor { field: Field::Size, align: Alignment::Right },
+    FieldDescriptor { field: Field::Time, align: Alignment::Left },
+    FieldDescriptor { field: Field::ModifiedTime, align: Alignment::Left },
+    FieldDescriptor { field: Field::AccessTime, align: Alignment::Left },
+    FieldDescriptor { field: Field::ChangeTime, align: Alignment::Left },
+    FieldDescriptor { field: Field::BirthTime, align: Alignment::Left },
+    FieldDescriptor { field: Field::Annotation, align: Alignment::Left },
+    FieldDescriptor { field: Field::Name, align: Alignment::Left },
+];
+
+/// The fields shown for the given arguments, with any alignment overrides applied
+fn active_fields(args: &Arguments) -> Vec<FieldDescriptor> {
+    FIELDS
+        .iter()
+        .filter(|d| match d.field {
+            Field::Inode => args.show_inode,
+            Field::Blocks => args.show_blocks,
+            Field::Octal => args.octal_permissions,
+            Field::Access => !args.can.is_empty(),
+            Field::Owner => args.show_owner,
+            Field::Group => args.show_group,
+            Field::Context => args.show_context,
+            Field::Capabilities => args.show_capabilities,
+            Field::FsAttributes => args.show_fs_attributes,
+            Field::Time => !args.all_times,
+            Field::ModifiedTime | Field::AccessTime | Field::ChangeTime | Field::BirthTime => args.all_times,
+            Field::Annotation => args.annotate_cmd.is_some(),
+            _ => true,
+        })
+        .map(|d| {
+            // the alignment of `time` also applies to each time of `--all-times`
+            let time = match d.field {
+                Field::ModifiedTime | Field::AccessTime | Field::ChangeTime | Field::BirthTime => {
+                    args.long_alignment.get(&Field::Time)
+                }
+                _ => None,
+            };
+            let align = args.long_alignment.get(&d.field).or(time).copied().unwrap_or(d.align);
+            FieldDescriptor { field: d.field, align }
+        })
+        .collect()
+}
+
+/// The security context of a file, or a placeholder where there is none
+pub(crate) fn context_text(path: &Path) -> String {
+    xattr::security_context(path).unwrap_or_else(|| PLACEHOLDER.to_string())
+}
+
+/// Whether a user, given by effective uid and groups, has an access to a file
+/// Only the mode is consulted, not ACLs or capabilities other than root's.
+fn can(mode: u32, owner: u32, group: u32, (uid, groups): (u32, &[u32]), access: Access) -> bool {
+    let (read, write, execute) = (0o4, 0o2, 0o1);
+    let bit = match access {
+        Access::Read => read,
+        Access::Write => write,
+        Access::Execute => execute,
+    };
+    if uid == 0 {
+        // root may execute files only when someone may, but search any directory
+        return access != Access::Execute || mode & libc::S_IFMT == libc::S_IFDIR || mode & 0o111 != 0;
+    }
+    let class = if uid == owner {
+        mode >> 6
+    } else if groups.contains(&group) {
+        mode >> 3
+    } else {
+        mode
+    };
+    class & bit != 0
+}
+
+/// The `--can` column, e.g. `rw-`, with a character for each access asked about
+fn access_text(mode: u32, owner: u32, group: u32, user: (u32, &[u32]), accesses: &[Access]) -> String {
+    accesses
+        .iter()
+        .map(|&access| match (can(mode, owner, group, user, access), access) {
+            (false, _) => '-',
+            (true, Access::Read) => 'r',
+            (true, Access::Write) => 'w',
+            (true, Access::Execute) => 'x',
+        })
+        .collect()
+}
+
+/// The file type character for the type bits of a mode
+fn file_type_char(mode: u32) -> char {
+    match mode & libc::S_IFMT {
+        libc::S_IFREG => '-',
+        libc::S_IFDIR => 'd',
+        libc::S_IFLNK => 'l',
+        libc::S_IFCHR => 'c',
+        libc::S_IFBLK => 'b',
+        libc::S_IFIFO => 'p',
+        libc::S_IFSOCK => 's',
+        _ => '?',
+    }
+}
+
+/// A description of the file type for the type bits of a mode
+pub(crate) fn file_type_name(mode: u32) -> &'static str {
+    match mode & libc::S_IFMT {
+        libc::S_IFREG => "regular file",
+        libc::S_IFDIR => "directory",
+        libc::S_IFLNK => "symbolic link",
+        libc::S_IFCHR => "character device",
+        libc::S_IFBLK => "block device",
+        libc::S_IFIFO => "fifo",
+        libc::S_IFSOCK => "socket",
+        _ => "unknown",
+    }
+}
+
+/// Render a mode as the 10 character string used by ls, e.g. `drwxr-sr-t`
+/// Set-user-ID, set-group-ID and sticky bits replace the execute character of
+/// their triplet with `s`/`t`, or `S`/`T` when the execute bit is not set.
+pub(crate) fn mode_string(mode: u32) -> String {
+    let triplets = [
+        (0o400, 0o200, 0o100, libc::S_ISUID, 's'),
+        (0o040, 0o020, 0o010, libc::S_ISGID, 's'),
+        (0o004, 0o002, 0o001, libc::S_ISVTX, 't'),
+    ];
+
+    let mut text = String::with_capacity(10);
+    text.push(file_type_char(mode));
+    for (read, write, exec, special, special_char) in triplets {
+        text.push(if mode & read != 0 { 'r' } else { '-' });
+        text.push(if mode & write != 0 { 'w' } else { '-' });
+        text.push(match (mode & exec != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    text
 }
 
-#[allow(dead_code)]
+/// The character following the mode of a file with the given extended attributes
+/// As in GNU ls, `+` marks an ACL and `.` a security context and nothing else.
+/// As in BSD ls, `@` marks any other extended attributes.
+fn xattr_marker(names: &[String]) -> Option<char> {
+    if names.iter().any(|n| xattr::ACL_NAMES.contains(&n.as_str())) {
+        Some('+')
+    } else if names.iter().any(|n| n != xattr::SELINUX_NAME) {
+        Some('@')
+    } else if !names.is_empty() {
+        Some('.')
+    } else {
+        None
+    }
+}
+
+/// Shown in place of a field that cannot be rendered
+const PLACEHOLDER: &str = "?";
+
+/// Seconds and nanoseconds since the epoch, with times before it counted back from it
+/// None if the seconds do not fit in an i64.
+pub(crate) fn unix_time(timestamp: &SystemTime) -> Option<(i64, u32)> {
+    match timestamp.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(durn) => Some((i64::try_from(durn.as_secs()).ok()?, durn.subsec_nanos())),
+        Err(e) => {
+            let durn = e.duration();
+            let secs = -i64::try_from(durn.as_secs()).ok()?;
+            match durn.subsec_nanos() {
+                0 => Some((secs, 0)),
+                nanos => Some((secs.checked_sub(1)?, 1_000_000_000 - nanos)),
+            }
+        }
+    }
+}
+
+/// Appended to the group of entries whose group the user is a member of
+const GROUP_MEMBER_MARKER: char = '*';
+
 struct EntryDisplayer<'a> {
     entry: &'a EntryData,
     arguments: &'a Arguments,
-    config: &'a Config,
+    annotation: Option<&'a str>,
+    tree_size: Option<u64>, // of everything beneath a directory, with --total-size
 }
 
 impl<'a> EntryDisplayer<'a> {
     //! Display long format details for an entry
     //! https://www.gnu.org/software/coreutils/manual/html_node/What-information-is-listed.html
-    fn write_file_type(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let ft = self.entry.metadata.file_type();
-        write!(
-            f,
-            "{}",
-            if ft.is_dir() {
-                'd'
-            } else if ft.is_symlink() {
-                'l'
-            } else if ft.is_char_device() {
-                'c'
-            } else if ft.is_block_device() {
-                'b'
-            } else if ft.is_fifo() {
-                'p'
-            } else if ft.is_socket() {
-                's'
-            } else if ft.is_file() {
-                '-'
-            } else {
-                '?'
-            }
-        )
-    }
-
-    fn write_file_mode(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mode = self.entry.metadata.mode();
-        let perms = [
-            (0o400, 'r'),
-            (0o200, 'w'),
-            (0o100, 'x'),
-            (0o040, 'r'),
-            (0o020, 'w'),
-            (0o010, 'x'),
-            (0o004, 'r'),
-            (0o002, 'w'),
-            (0o001, 'x'),
-        ];
-
-        for perm in perms.iter() {
-            if mode & perm.0 != 0 {
-                write!(f, "{}", perm.1)?;
-            } else {
-                write!(f, "-")?;
-            }
+    fn mode_text(&self) -> String {
+        let mut text = mode_string(self.entry.metadata.mode());
+        // filesystems without extended attributes simply get no marker
+        if let Some(marker) = xattr::list(&self.entry.path).ok().and_then(|names| xattr_marker(&names)) {
+            text.push(marker);
         }
+        text
+    }
 
-        Ok(())
+    fn user_text(&self) -> String {
+        if self.arguments.numeric_ids {
+            return self.entry.metadata.uid().to_string();
+        }
+        // users missing from the user database, e.g. deleted ones, are shown by number
+        let uid = self.entry.metadata.uid();
+        users::get_user_by_uid(uid)
+            .map(|u| self.owner_name(&u.name().to_string_lossy()))
+            .unwrap_or_else(|| uid.to_string())
+    }
+
+    /// A user or group name, cut to `--max-owner-width`
+    fn owner_name(&self, name: &str) -> String {
+        match self.arguments.max_owner_width {
+            Some(max) => width::truncate(name, max, self.arguments.ambiguous_wide),
+            None => name.to_string(),
+        }
     }
 
-    fn write_nlinks(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // right align the nlinks using the config width
-        write!(f, "{:width$}", self.entry.metadata.nlink(), width = self.config.nlinks_width)
-    }
-    
-    fn write_user(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // left align the user
-        let user = users::get_user_by_uid(self.entry.metadata.uid())
-            .map(|u| u.name().to_string_lossy().to_string())
-            .unwrap_or_default();
-        write!(f, "{:width$}", user, width = self.config.user_width)
-    }
-    
-    fn write_group(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let group = users::get_group_by_gid(self.entry.metadata.gid())
-            .map(|g| g.name().to_string_lossy().to_string())
-            .unwrap_or_default();
-        write!(f, "{:width$}", group, width = self.config.group_width)
-    }
-    
-    fn write_size(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let size = if self.entry.metadata.is_dir() {
-            0
+    fn group_text(&self) -> String {
+        let gid = self.entry.metadata.gid();
+        let mut group = if self.arguments.numeric_ids {
+            gid.to_string()
         } else {
-            self.entry.metadata.len()
+            users::get_group_by_gid(gid)
+                .map(|g| self.owner_name(&g.name().to_string_lossy()))
+                .unwrap_or_else(|| gid.to_string())
         };
-        write!(f, "{:width$}", size, width = self.config.size_width)
+        if self.arguments.mark_groups && posix::process_groups().contains(&gid) {
+            group.push(GROUP_MEMBER_MARKER);
+        }
+        group
     }
 
-    fn write_timestamp(&self, f: &mut fmt::Formatter, timestamp: &std::time::SystemTime) -> fmt::Result {
-        // a timestamp is considered recent if it is less than 6 months old, and is not dated in the future
+    fn size_text(&self) -> String {
+        let file_type = self.entry.metadata.file_type();
+        if file_type.is_char_device() || file_type.is_block_device() {
+            let rdev = self.entry.metadata.rdev();
+            return format!("{}, {}", libc::major(rdev), libc::minor(rdev));
+        }
+        size::format_size(self.size(), self.arguments.size_format)
+    }
+
+    /// The size shown for the entry, that of its whole tree with `--total-size`
+    fn size(&self) -> u64 {
+        self.tree_size.unwrap_or(self.entry.metadata.len())
+    }
+
+    fn epoch_text(&self, timestamp: &SystemTime, unit: EpochUnit) -> String {
+        // timestamps before the epoch are negative
+        let nanos = match timestamp.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(durn) => durn.as_nanos() as i128,
+            Err(e) => -(e.duration().as_nanos() as i128),
+        };
+        let value = match unit {
+            EpochUnit::Seconds => nanos.div_euclid(1_000_000_000),
+            EpochUnit::Milliseconds => nanos.div_euclid(1_000_000),
+            EpochUnit::Nanoseconds => nanos,
+        };
+        value.to_string()
+    }
+
+    fn timestamp_text(&self, timestamp: &SystemTime) -> String {
+        if let Some(unit) = self.arguments.epoch {
+            return self.epoch_text(timestamp, unit);
+        }
+
         let now = SystemTime::now();
+        // a timestamp is considered recent if it is less than 6 months old, and is not dated in the future
         let six_months = 60 * 60 * 24 * 30 * 6;
-        let is_recent = now.duration_since(*timestamp).unwrap().as_secs() < six_months;
-        let durn = timestamp.duration_since(SystemTime::UNIX_EPOCH).expect("Could not get duration");
-        let dt = chrono::DateTime::from_timestamp(durn.as_secs() as i64, 0).expect("Could not create datetime");
-        let dt = dt.with_timezone(&chrono::Local);
-        
-        if is_recent {
-            write!(f, "{}", dt.format("%b %e %H:%M"))
-        } else {
-            write!(f, "{}", dt.format("%b %e  %Y"))
+        let is_recent = match now.duration_since(*timestamp) {
+            Ok(age) => age.as_secs() < six_months,
+            Err(_) => false,
+        };
+        let format = match self.arguments.time_style.format(is_recent) {
+            Some(format) => format,
+            None => return timestyle::relative(*timestamp, now),
+        };
+        let dt = match unix_time(timestamp).and_then(|(secs, nanos)| chrono::DateTime::from_timestamp(secs, nanos)) {
+            Some(dt) => dt.with_timezone(&chrono::Local),
+            None => return self.placeholder("timestamp out of range"),
+        };
+
+        // chrono reports some formatting problems only while writing
+        let mut text = String::new();
+        let format = timestyle::localize(format, dt.month0() as usize);
+        match write!(text, "{}", dt.format(&format)) {
+            Ok(()) => text,
+            Err(_) => self.placeholder("could not format timestamp"),
         }
     }
 
-    fn write_modified(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.write_timestamp(f, &self.entry.metadata.modified().expect("Coult not get modified time"))
+    fn time_text(&self, field: TimeField) -> String {
+        match field.get(&self.entry.metadata) {
+            Ok(time) => self.timestamp_text(&time),
+            // not a problem with this file, so not worth a warning for each one
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => PLACEHOLDER.to_string(),
+            Err(e) => self.placeholder(&crate::io_error_text(&e)),
+        }
+    }
+
+    /// Warn that a field of this entry cannot be shown, returning the text shown in its place
+    fn placeholder(&self, reason: &str) -> String {
+        eprintln!("listare: {}: {}", self.entry.path.display(), reason);
+        PLACEHOLDER.to_string()
     }
 
     fn get_link_target(&self) -> Result<EntryData, std::io::Error> {
-        let link = std::fs::read_link(&self.entry.path)?;
+        let link = vfs::read_link(&self.entry.path)?;
         if link.is_absolute() {
             EntryData::from_path(link)
         }
@@ -130,75 +475,637 @@ impl<'a> EntryDisplayer<'a> {
             EntryData::from_relative_path(parent, link)
         }
     }
-    
+
+    /// A symlink target as it is shown, without control characters if they are hidden
+    fn target_text(&self, target: &Path) -> String {
+        controls::hide(&self.target_path(target), self.arguments.hide_control_chars).into_owned()
+    }
+
+    /// A symlink target, normalized or made relative to another directory if requested
+    fn target_path(&self, target: &Path) -> String {
+        if let Some(base) = &self.arguments.targets_relative_to {
+            let parent = std::path::absolute(&self.entry.path)
+                .ok()
+                .and_then(|path| path.parent().map(Path::to_path_buf));
+            if let Some(parent) = parent {
+                return pathutil::relative_to(&parent.join(target), base).to_string_lossy().into_owned();
+            }
+        }
+        if self.arguments.normalize_targets {
+            return pathutil::normalize(target).to_string_lossy().into_owned();
+        }
+        target.to_string_lossy().into_owned()
+    }
+
     fn write_name(&self, f: &mut fmt::Formatter) -> fmt::Result {
         // get the colored name of the entry
-        let name = self.entry.colored_name();
+        let uri = self.arguments.hyperlink.then(|| hyperlink::uri(&self.entry.path));
+        let name = hyperlink::Linked(uri.as_deref(), self.entry.colored_name(self.arguments));
+        if let Some(icons) = &self.arguments.icons {
+            write!(f, "{}", icons.prefix(self.entry))?;
+        }
         // if the entry is a symlink use a format of "name -> target"
         // otherwise, just print the name
         if self.entry.metadata.file_type().is_symlink() {
-            let target = self.get_link_target().map(|e| e.colored_path()).map_err(|_| fmt::Error)?;
-            write!(f, "{} -> {}", name, target)
+            match self.get_link_target() {
+                Ok(target) => write!(f, "{} -> {}", name, self.arguments.theme.paint(&target, &self.target_text(&target.path))),
+                // the target of a dangling link is still worth showing
+                Err(_) => match vfs::read_link(&self.entry.path) {
+                    Ok(link) => write!(f, "{} -> {}", name, self.target_text(&link)),
+                    Err(_) => write!(f, "{} -> ?", name),
+                },
+            }
         } else {
-            write!(f, "{}", name)
+            write!(f, "{}{}", name, self.entry.suffix(self.arguments))
+        }
+    }
+
+    /// The name with any link target or suffix, without color
+    fn plain_name(&self) -> String {
+        let name = self.entry.shown_name(self.arguments);
+        if self.entry.metadata.file_type().is_symlink() {
+            let target = vfs::read_link(&self.entry.path).map_or_else(|_| "?".to_string(), |t| self.target_text(&t));
+            format!("{} -> {}", name, target)
+        } else {
+            format!("{}{}", name, self.entry.suffix(self.arguments))
+        }
+    }
+
+    /// The number of columns taken by the name, including any link target
+    fn name_width(&self) -> usize {
+        let ambiguous_wide = self.arguments.ambiguous_wide;
+        let mut width = width::display_width(&self.entry.shown_name(self.arguments), ambiguous_wide);
+        if let Some(icons) = &self.arguments.icons {
+            width += width::display_width(&icons.prefix(self.entry), ambiguous_wide);
+        }
+        if self.entry.metadata.file_type().is_symlink() {
+            let target = vfs::read_link(&self.entry.path)
+                .map(|t| width::display_width(&self.target_text(&t), ambiguous_wide))
+                .unwrap_or(1);
+            width + " -> ".len() + target
+        } else {
+            width + self.entry.suffix(self.arguments).len()
+        }
+    }
+
+    /// Where `--color-scale` places the entry on the gradient of a field, None for fields without one
+    fn scale_value(&self, field: Field) -> Option<f64> {
+        let time_field = match field {
+            Field::Size => {
+                let file_type = self.entry.metadata.file_type();
+                // the numbers of a device are not a size
+                if file_type.is_char_device() || file_type.is_block_device() {
+                    return None;
+                }
+                return Some(self.size() as f64);
+            }
+            Field::Time => self.arguments.time_field,
+            Field::ModifiedTime => TimeField::Modified,
+            Field::AccessTime => TimeField::Accessed,
+            Field::ChangeTime => TimeField::Changed,
+            Field::BirthTime => TimeField::Birth,
+            _ => return None,
+        };
+        let time = time_field.get(&self.entry.metadata).ok()?;
+        Some(match time.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(durn) => durn.as_secs_f64(),
+            Err(e) => -e.duration().as_secs_f64(),
+        })
+    }
+
+    /// The text of a padded field
+    fn field_text(&self, field: Field) -> String {
+        match field {
+            Field::Inode => self.entry.metadata.ino().to_string(),
+            Field::Blocks => size::format_blocks(self.entry.metadata.blocks(), self.arguments.block_format),
+            Field::Octal => format!("{:04o}", self.entry.metadata.mode() & 0o7777),
+            Field::Mode => self.mode_text(),
+            Field::Access => {
+                let metadata = &self.entry.metadata;
+                let user = (users::get_effective_uid(), posix::process_groups());
+                access_text(metadata.mode(), metadata.uid(), metadata.gid(), user, &self.arguments.can)
+            }
+            Field::Links => self.entry.metadata.nlink().to_string(),
+            Field::Owner => self.user_text(),
+            Field::Group => self.group_text(),
+            Field::Context => context_text(&self.entry.path),
+            Field::Capabilities => capability::capabilities(&self.entry.path).unwrap_or_else(|| "-".to_string()),
+            #[cfg(all(feature = "fs-attributes", target_os = "linux"))]
+            Field::FsAttributes => crate::fsattr::text(&self.entry.path, &self.entry.metadata),
+            #[cfg(not(all(feature = "fs-attributes", target_os = "linux")))]
+            Field::FsAttributes => "-".to_string(),
+            Field::Size => self.size_text(),
+            Field::Time => self.time_text(self.arguments.time_field),
+            Field::ModifiedTime => self.time_text(TimeField::Modified),
+            Field::AccessTime => self.time_text(TimeField::Accessed),
+            Field::ChangeTime => self.time_text(TimeField::Changed),
+            Field::BirthTime => self.time_text(TimeField::Birth),
+            Field::Annotation => self.annotation.unwrap_or_default().to_string(),
+            Field::Name => String::new(),
         }
     }
-    
 }
 
-impl<'a> fmt::Display for EntryDisplayer<'a> {
+/// One line of the long format
+/// The text of each field is computed ahead of time so that column widths can
+/// be measured before anything is printed.
+struct Row<'a> {
+    displayer: EntryDisplayer<'a>,
+    cells: Vec<String>,
+}
+
+struct Config {
+    fields: Vec<FieldDescriptor>,
+    widths: Vec<usize>,
+    gradients: HashMap<Field, Gradient>, // of the fields `--color-scale` colors
+}
+
+/// The fields dropped by `--auto-shrink`, least important first
+const SHRINK_ORDER: [Field; 3] = [Field::Group, Field::Links, Field::Owner];
+
+impl Config {
+    /// The length of the longest line, given the widest name
+    fn line_len(&self, name_width: usize) -> usize {
+        self.fields
+            .iter()
+            .zip(&self.widths)
+            .map(|(d, &width)| if d.field == Field::Name { name_width } else { width })
+            .sum::<usize>()
+            + self.fields.len().saturating_sub(1)
+    }
+
+    /// Drop fields in [`SHRINK_ORDER`] until lines fit within `max_line_length`
+    fn shrink(&mut self, rows: &mut [Row], max_line_length: usize) {
+        let name_width = rows.iter().map(|r| r.displayer.name_width()).max().unwrap_or(0);
+        for field in SHRINK_ORDER {
+            if self.line_len(name_width) <= max_line_length {
+                return;
+            }
+            if let Some(idx) = self.fields.iter().position(|d| d.field == field) {
+                self.fields.remove(idx);
+                self.widths.remove(idx);
+                for row in rows.iter_mut() {
+                    row.cells.remove(idx);
+                }
+            }
+        }
+    }
+}
+
+struct RowDisplayer<'a> {
+    row: &'a Row<'a>,
+    config: &'a Config,
+}
+
+/// The `--header` line, with the title of each field aligned as its column
+struct HeaderDisplayer<'a> {
+    config: &'a Config,
+}
+
+impl fmt::Display for HeaderDisplayer<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.write_file_type(f)?;
-        self.write_file_mode(f)?;
-        write!(f, " ")?;
-        self.write_nlinks(f)?;
-        write!(f, " ")?;
-        self.write_user(f)?;
-        write!(f, " ")?;
-        self.write_group(f)?;
-        write!(f, " ")?;
-        self.write_size(f)?;
-        write!(f, " ")?;
-        self.write_modified(f)?;
-        write!(f, " ")?;
-        self.write_name(f)?;
+        for (i, (descriptor, &width)) in self.config.fields.iter().zip(&self.config.widths).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            let title = descriptor.field.title();
+            match (descriptor.field, descriptor.align) {
+                (Field::Name, _) => write!(f, "{}", title)?,
+                (_, Alignment::Left) => write!(f, "{:<width$}", title, width = width)?,
+                (_, Alignment::Right) => write!(f, "{:>width$}", title, width = width)?,
+            }
+        }
         Ok(())
     }
 }
 
-pub fn longformat_tabulate_entries(entries: &[EntryData], _args: &Arguments) {
-    let mut cfg = Config {
-        size_width: 1,
-        user_width: 1,
-        group_width: 1,
-        nlinks_width: 1,
-    };
+impl<'a> fmt::Display for RowDisplayer<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fields = self.config.fields.iter().zip(&self.row.cells).zip(&self.config.widths);
+        for (i, ((descriptor, cell), &width)) in fields.enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            // padded by display width, as owner names may hold wide characters
+            let padding = width.saturating_sub(width::display_width(cell, self.row.displayer.arguments.ambiguous_wide));
+            let gradient = self.config.gradients.get(&descriptor.field);
+            let cell = match gradient.zip(self.row.displayer.scale_value(descriptor.field)) {
+                Some((gradient, value)) => Cow::Owned(gradient.paint(cell, value).to_string()),
+                None => Cow::Borrowed(cell.as_str()),
+            };
+            match (descriptor.field, descriptor.align) {
+                (Field::Name, _) => self.row.displayer.write_name(f)?,
+                (_, Alignment::Left) => write!(f, "{}{:padding$}", cell, "", padding = padding)?,
+                (_, Alignment::Right) => write!(f, "{:padding$}{}", "", cell, padding = padding)?,
+            }
+        }
+        Ok(())
+    }
+}
 
-    // go through the etries and find the max width for each field
-    for entry in entries {
-        cfg.size_width = cfg.size_width.max(entry.metadata.len().to_string().len());
-        // todo USER AND GROUP is slow - extract this
-        cfg.user_width = cfg.user_width.max(
-            users::get_user_by_uid(entry.metadata.uid())
-                .map(|u| u.name().len())
-                .unwrap_or_default(),
-        );
-        cfg.group_width = cfg.group_width.max(
-            users::get_group_by_gid(entry.metadata.gid())
-                .map(|g| g.name().len())
-                .unwrap_or_default(),
-        );
-        cfg.nlinks_width = cfg.nlinks_width.max(entry.metadata.nlink().to_string().len());
-    }
-
-    for entry in entries {
-        println!(
-            "{}",
-            EntryDisplayer {
+/// The total of allocated blocks that starts the long format of a directory's contents
+pub(crate) struct Total {
+    seen: HashSet<(u64, u64)>, // with --count-links-once, the files with several links counted so far
+    blocks: u64,
+    bytes: u64,
+}
+
+impl Total {
+    pub(crate) fn new() -> Self {
+        Total { seen: HashSet::new(), blocks: 0, bytes: 0 }
+    }
+
+    /// Count an entry in the total
+    pub(crate) fn add(&mut self, metadata: &Metadata, args: &Arguments) {
+        // as in du, a file with several links among the entries is counted once
+        if !args.count_links_once || metadata.nlink() < 2 || self.seen.insert((metadata.dev(), metadata.ino())) {
+            self.blocks += metadata.blocks();
+            self.bytes += metadata.len();
+        }
+    }
+
+    pub(crate) fn print(&self, args: &Arguments, out: &mut Output) -> Result<(), ListareError> {
+        if args.markdown {
+            return Ok(());
+        }
+        let blocks = size::format_blocks(self.blocks, args.block_format);
+        let mut total = format!("{} {}", messages::text(Message::Total), blocks);
+        if args.total_apparent {
+            total.push_str(&format!(" (apparent {})", size::format_size(self.bytes, args.block_format)));
+        }
+        outln!(out, "{}", total)
+    }
+}
+
+/// Print entries in long format
+/// Listings of a directory's contents start with the total of allocated blocks, as in ls,
+/// followed by the total of the entries' sizes in the same units with `--total-apparent`.
+/// Each of `separators` is a line printed before the entry at its index.
+pub fn longformat_tabulate_entries(
+    entries: &[EntryData],
+    args: &Arguments,
+    show_total: bool,
+    separators: &[(usize, String)],
+    out: &mut Output,
+) -> Result<(), ListareError> {
+    if show_total {
+        let mut total = Total::new();
+        for entry in entries {
+            total.add(entry.metadata(), args);
+        }
+        total.print(args, out)?;
+    }
+
+    let fields = active_fields(args);
+
+    let annotations = args
+        .annotate_cmd
+        .as_ref()
+        .map(|cmd| crate::annotate::annotate(entries, cmd));
+
+    // an inode is counted in the first of the trees it is met in, in the order listed
+    let mut tree_sizes = args.total_size.then(|| TreeSizes::new(args.count_links_once));
+
+    let mut rows: Vec<Row> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let tree_size = tree_sizes.as_mut().and_then(|sizes| match entry.name.as_str() {
+                // the implied `..` keeps its own size, as walking it would walk the parent's whole
+                // tree, e.g. all of / or of a home directory; `..` named on the command line is walked
+                ".." if entry.path != Path::new("..") => None,
+                // the implied `.` holds the others, which would otherwise count for nothing
+                "." => Some(TreeSizes::new(args.count_links_once).size(&entry.path, &entry.metadata)),
+                _ => Some(sizes.size(&entry.path, &entry.metadata)),
+            });
+            let displayer = EntryDisplayer {
                 entry,
-                arguments: _args,
-                config: &cfg
+                arguments: args,
+                annotation: annotations.as_ref().map(|a| a[i].as_str()),
+                // the size of anything else is its own, even when it was counted in an earlier tree
+                tree_size: tree_size.filter(|_| entry.metadata.is_dir()),
+            };
+            let cells = fields
+                .iter()
+                .map(|d| controls::hide(&displayer.field_text(d.field), args.hide_control_chars).into_owned())
+                .collect();
+            Row { displayer, cells }
+        })
+        .collect();
+
+    // find the max width of each field, making room for the titles of a header
+    let mut widths: Vec<usize> = if args.header {
+        fields.iter().map(|d| width::display_width(d.field.title(), false)).collect()
+    } else {
+        vec![0; fields.len()]
+    };
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(&row.cells) {
+            *width = (*width).max(width::display_width(cell, args.ambiguous_wide));
+        }
+    }
+    let mut cfg = Config { fields, widths, gradients: HashMap::new() };
+    if args.auto_shrink {
+        cfg.shrink(&mut rows, args.max_line_length);
+    }
+    if let Some(scale) = args.color_scale {
+        cfg.gradients = gradients(&rows, &cfg.fields, scale);
+    }
+
+    if args.markdown {
+        return markdown_table(&rows, &cfg, args.ambiguous_wide, out);
+    }
+    if args.header {
+        outln!(out, "{}", HeaderDisplayer { config: &cfg })?;
+    }
+
+    if args.long_grid {
+        return tabulate_records(&rows, &cfg, args, separators, out);
+    }
+
+    let mut separators = separators.iter().peekable();
+    for (idx, row) in rows.iter().enumerate() {
+        while let Some((_, line)) = separators.next_if(|(at, _)| *at == idx) {
+            outln!(out, "{}", line)?;
+        }
+        outln!(out, "{}", RowDisplayer { row, config: &cfg })?;
+        if args.list_xattrs {
+            for (name, size) in xattr_sizes(&row.displayer.entry.path) {
+                outln!(out, "\t{}\t{:>4}", controls::hide(&name, args.hide_control_chars), size)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A rendered long format record, laid out side by side with others by `--grid`
+struct RecordCell {
+    text: String,
+    width: usize, // columns taken by the text, without its color codes
+}
+
+impl fmt::Display for RecordCell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)?;
+        crate::pad(f, self.width)
+    }
+}
+
+impl tabulate::CharacterLength for RecordCell {
+    fn characters_long(&self) -> usize {
+        self.width
+    }
+}
+
+/// The gradients of the size and time fields among `fields`, from the smallest and largest values of `rows`
+fn gradients(rows: &[Row], fields: &[FieldDescriptor], scale: ColorScale) -> HashMap<Field, Gradient> {
+    let mut gradients = HashMap::new();
+    for descriptor in fields {
+        let field = descriptor.field;
+        let values = rows.iter().filter_map(|row| row.displayer.scale_value(field));
+        let gradient = match field {
+            Field::Size if scale.sizes() => Gradient::sizes(values),
+            Field::Time | Field::ModifiedTime | Field::AccessTime | Field::ChangeTime | Field::BirthTime
+                if scale.ages() =>
+            {
+                Gradient::ages(values)
             }
-        );
+            _ => None,
+        };
+        if let Some(gradient) = gradient {
+            gradients.insert(field, gradient);
+        }
+    }
+    gradients
+}
+
+/// Lay out long format records in as many columns as fit, each group between separators as its own grid
+fn tabulate_records(
+    rows: &[Row],
+    cfg: &Config,
+    args: &Arguments,
+    separators: &[(usize, String)],
+    out: &mut Output,
+) -> Result<(), ListareError> {
+    let cells: Vec<RecordCell> = rows
+        .iter()
+        .map(|row| RecordCell {
+            text: RowDisplayer { row, config: cfg }.to_string(),
+            width: cfg.line_len(row.displayer.name_width()),
+        })
+        .collect();
+    let grid = |cells| {
+        tabulate::Tabulator::new(cells, args.max_line_length, tabulate::TabulateOrientation::Columns).tab_size(args.tab_size)
+    };
+
+    let mut start = 0;
+    for (end, line) in separators {
+        if start < *end {
+            outln!(out, "{}", grid(&cells[start..*end]))?;
+        }
+        outln!(out, "{}", line)?;
+        start = *end;
+    }
+    if start < cells.len() {
+        outln!(out, "{}", grid(&cells[start..]))?;
+    }
+    Ok(())
+}
+
+/// Characters that Markdown would otherwise read as emphasis, links, HTML or the end of a cell
+const MARKDOWN_SPECIAL: &[char] = &['\\', '`', '*', '_', '[', ']', '<', '|', '~'];
+
+/// `text` as the content of a Markdown table cell
+/// Control characters are always hidden, as a newline would end the row.
+fn markdown_cell(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in controls::hide(text, true).chars() {
+        if MARKDOWN_SPECIAL.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Print rows as a GitHub-flavored Markdown table, titled by their fields
+/// Cells are padded so that the table also lines up as plain text.
+fn markdown_table(rows: &[Row], cfg: &Config, ambiguous_wide: bool, out: &mut Output) -> Result<(), ListareError> {
+    let titles = cfg.fields.iter().map(|d| markdown_cell(d.field.title())).collect();
+    let lines: Vec<Vec<String>> = std::iter::once(titles)
+        .chain(rows.iter().map(|row| {
+            cfg.fields
+                .iter()
+                .zip(&row.cells)
+                .map(|(d, cell)| match d.field {
+                    Field::Name => markdown_cell(&row.displayer.plain_name()),
+                    _ => markdown_cell(cell),
+                })
+                .collect()
+        }))
+        .collect();
+    // a delimiter needs at least three dashes
+    let mut widths = vec![3; cfg.fields.len()];
+    for line in &lines {
+        for (width, cell) in widths.iter_mut().zip(line) {
+            *width = (*width).max(width::display_width(cell, ambiguous_wide));
+        }
+    }
+
+    let row_text = |cells: Vec<String>| {
+        let cells: Vec<String> = cells
+            .into_iter()
+            .zip(cfg.fields.iter().zip(&widths))
+            .map(|(cell, (d, &width))| {
+                let padding = width.saturating_sub(width::display_width(&cell, ambiguous_wide));
+                match d.align {
+                    Alignment::Left => format!("{}{:padding$}", cell, "", padding = padding),
+                    Alignment::Right => format!("{:padding$}{}", "", cell, padding = padding),
+                }
+            })
+            .collect();
+        format!("| {} |", cells.join(" | "))
+    };
+    let mut lines = lines.into_iter();
+    if let Some(titles) = lines.next() {
+        outln!(out, "{}", row_text(titles))?;
+    }
+    let delimiters: Vec<String> = cfg
+        .fields
+        .iter()
+        .zip(&widths)
+        .map(|(d, &width)| match d.align {
+            Alignment::Left => "-".repeat(width),
+            Alignment::Right => format!("{}:", "-".repeat(width - 1)),
+        })
+        .collect();
+    outln!(out, "| {} |", delimiters.join(" | "))?;
+    for line in lines {
+        outln!(out, "{}", row_text(line))?;
+    }
+    Ok(())
+}
+
+/// The names of the extended attributes of a file with the sizes of their values
+/// Attributes that cannot be read are left out, as they are from the mode marker.
+fn xattr_sizes(path: &Path) -> Vec<(String, usize)> {
+    xattr::list(path)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|name| {
+            let size = xattr::size(path, &name).ok()?;
+            Some((name, size))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{access_text, markdown_cell, mode_string, unix_time, xattr_marker, Access};
+    use std::time::{Duration, SystemTime};
+
+    const FILE_TYPES: [(u32, char); 7] = [
+        (libc::S_IFREG, '-'),
+        (libc::S_IFDIR, 'd'),
+        (libc::S_IFLNK, 'l'),
+        (libc::S_IFCHR, 'c'),
+        (libc::S_IFBLK, 'b'),
+        (libc::S_IFIFO, 'p'),
+        (libc::S_IFSOCK, 's'),
+    ];
+
+    /// Reference rendering of one permission triplet, following coreutils' filemodestring
+    fn triplet(bits: u32, special: bool, set: char, unset: char) -> String {
+        let r = if bits & 4 != 0 { 'r' } else { '-' };
+        let w = if bits & 2 != 0 { 'w' } else { '-' };
+        let x = match (bits & 1 != 0, special) {
+            (true, true) => set,
+            (false, true) => unset,
+            (true, false) => 'x',
+            (false, false) => '-',
+        };
+        format!("{}{}{}", r, w, x)
+    }
+
+    #[test]
+    fn renders_every_mode_of_every_file_type() {
+        for (type_bits, type_char) in FILE_TYPES {
+            for perms in 0..=0o7777u32 {
+                let expected = format!(
+                    "{}{}{}{}",
+                    type_char,
+                    triplet(perms >> 6 & 7, perms & 0o4000 != 0, 's', 'S'),
+                    triplet(perms >> 3 & 7, perms & 0o2000 != 0, 's', 'S'),
+                    triplet(perms & 7, perms & 0o1000 != 0, 't', 'T'),
+                );
+                assert_eq!(mode_string(type_bits | perms), expected, "mode {:o}", type_bits | perms);
+            }
+        }
+    }
+
+    #[test]
+    fn renders_common_modes() {
+        assert_eq!(mode_string(libc::S_IFREG | 0o644), "-rw-r--r--");
+        assert_eq!(mode_string(libc::S_IFDIR | 0o755), "drwxr-xr-x");
+        assert_eq!(mode_string(libc::S_IFDIR | 0o1777), "drwxrwxrwt");
+        assert_eq!(mode_string(libc::S_IFDIR | 0o1770), "drwxrwx--T");
+        assert_eq!(mode_string(libc::S_IFREG | 0o4755), "-rwsr-xr-x");
+        assert_eq!(mode_string(libc::S_IFREG | 0o4644), "-rwSr--r--");
+        assert_eq!(mode_string(libc::S_IFDIR | 0o2775), "drwxrwsr-x");
+        assert_eq!(mode_string(libc::S_IFREG | 0o2664), "-rw-rwSr--");
+        assert_eq!(mode_string(libc::S_IFLNK | 0o777), "lrwxrwxrwx");
+        assert_eq!(mode_string(0o644), "?rw-r--r--");
+    }
+
+    #[test]
+    fn checks_access_by_owner_group_and_others() {
+        const ALL: [Access; 3] = [Access::Read, Access::Write, Access::Execute];
+        let file = libc::S_IFREG | 0o754;
+        let groups: &[u32] = &[100, 20];
+        assert_eq!(access_text(file, 1000, 100, (1000, groups), &ALL), "rwx");
+        assert_eq!(access_text(file, 1001, 20, (1000, groups), &ALL), "r-x");
+        assert_eq!(access_text(file, 1001, 30, (1000, groups), &ALL), "r--");
+        // the owner's bits apply to the owner even when the group's allow more
+        assert_eq!(access_text(libc::S_IFREG | 0o077, 1000, 100, (1000, groups), &ALL), "---");
+        assert_eq!(access_text(file, 1001, 30, (1000, groups), &[Access::Write]), "-");
+    }
+
+    #[test]
+    fn lets_root_do_anything_but_execute_unexecutable_files() {
+        const ALL: [Access; 3] = [Access::Read, Access::Write, Access::Execute];
+        assert_eq!(access_text(libc::S_IFREG, 1000, 100, (0, &[0]), &ALL), "rw-");
+        assert_eq!(access_text(libc::S_IFREG | 0o001, 1000, 100, (0, &[0]), &ALL), "rwx");
+        assert_eq!(access_text(libc::S_IFDIR, 1000, 100, (0, &[0]), &ALL), "rwx");
+    }
+
+    #[test]
+    fn splits_timestamps_around_the_epoch() {
+        let epoch = SystemTime::UNIX_EPOCH;
+        assert_eq!(unix_time(&epoch), Some((0, 0)));
+        assert_eq!(unix_time(&(epoch + Duration::new(5, 250))), Some((5, 250)));
+        assert_eq!(unix_time(&(epoch - Duration::new(5, 0))), Some((-5, 0)));
+        // half a second before the epoch is -1s plus half a second
+        assert_eq!(unix_time(&(epoch - Duration::from_millis(500))), Some((-1, 500_000_000)));
+    }
+
+    #[test]
+    fn marks_acls_contexts_and_other_attributes() {
+        let marker = |names: &[&str]| xattr_marker(&names.iter().map(|n| n.to_string()).collect::<Vec<_>>());
+        assert_eq!(marker(&[]), None);
+        assert_eq!(marker(&["security.selinux"]), Some('.'));
+        assert_eq!(marker(&["user.comment"]), Some('@'));
+        assert_eq!(marker(&["security.selinux", "user.comment"]), Some('@'));
+        assert_eq!(marker(&["system.posix_acl_access", "user.comment"]), Some('+'));
+        assert_eq!(marker(&["system.posix_acl_default"]), Some('+'));
+    }
+
+    #[test]
+    fn escapes_markdown_cells() {
+        assert_eq!(markdown_cell("notes.txt"), "notes.txt");
+        assert_eq!(markdown_cell("__init__.py"), "\\_\\_init\\_\\_.py");
+        assert_eq!(markdown_cell("a|b"), "a\\|b");
+        assert_eq!(markdown_cell("[x](<y>)"), "\\[x\\](\\<y>)");
+        assert_eq!(markdown_cell("back\\slash"), "back\\\\slash");
+        assert_eq!(markdown_cell("two\nlines"), "two?lines");
     }
 }
@@ -0,0 +1,153 @@
+//! Colors from `LS_COLORS`, as dircolors sets it for GNU ls
+//!
+//! The variable is a list of `key=SGR` pairs separated by colons, such as
+//! `di=01;34:ln=01;36:*.tar=01;31`. Two-letter keys name types of files and
+//! `*` keys suffixes of names; the types not given keep the defaults of GNU ls.
+//! Like GNU ls, `ln=target` colors a symlink as what it points to, and a
+//! dangling one with `or`. Regular files with more than one hard link get
+//! the color of `mh`, when one is given, so that shared inodes stand out
+//! before they are edited. The terminal codes of `lc`, `rc` and `ec` are not
+//! supported, names are always colored with `ESC[...m` and reset with `ESC[0m`.
+use std::collections::HashMap;
+use std::os::unix::fs::MetadataExt;
+
+use crate::EntryData;
+
+/// The colors of GNU ls before `LS_COLORS` changes them
+const DEFAULTS: &[(&str, &str)] = &[
+    ("di", "01;34"),
+    ("ln", "01;36"),
+    ("pi", "33"),
+    ("so", "01;35"),
+    ("bd", "01;33"),
+    ("cd", "01;33"),
+    ("do", "01;35"),
+    ("ex", "01;32"),
+    ("su", "37;41"),
+    ("sg", "30;43"),
+    ("st", "37;44"),
+    ("ow", "34;42"),
+    ("tw", "30;42"),
+];
+
+/// The colors given by `LS_COLORS`
+#[derive(Clone, Debug)]
+pub struct LsColors {
+    types: HashMap<String, String>,   // by two-letter key, e.g. `di`
+    suffixes: Vec<(String, String)>,  // in lower case, in the order given
+    link_target: bool,                // `ln=target`
+}
+
+impl LsColors {
+    /// Read the value of `LS_COLORS`, skipping the pairs that are not understood
+    pub fn parse(text: &str) -> LsColors {
+        let mut colors = LsColors {
+            types: DEFAULTS.iter().map(|&(key, sgr)| (key.to_string(), sgr.to_string())).collect(),
+            suffixes: Vec::new(),
+            link_target: false,
+        };
+        for (key, sgr) in text.split(':').filter_map(|pair| pair.split_once('=')) {
+            if let Some(suffix) = key.strip_prefix('*') {
+                colors.suffixes.push((suffix.to_lowercase(), sgr.to_string()));
+            } else if key == "ln" && sgr == "target" {
+                colors.link_target = true;
+            } else if key.len() == 2 {
+                colors.types.insert(key.to_string(), sgr.to_string());
+            }
+        }
+        colors
+    }
+
+    /// The SGR parameters of a type, None where it has no color
+    fn colored(&self, key: &str) -> Option<&str> {
+        // as in GNU ls, zero is no color at all
+        self.types.get(key).map(String::as_str).filter(|sgr| !matches!(*sgr, "" | "0" | "00"))
+    }
+
+    /// The key of the type of a file with `mode` and `nlink` hard links, as GNU ls chooses it
+    fn type_key(&self, mode: u32, nlink: u64) -> &'static str {
+        let has = |bits: u32| mode & bits == bits;
+        match mode & libc::S_IFMT {
+            libc::S_IFREG if has(libc::S_ISUID) && self.colored("su").is_some() => "su",
+            libc::S_IFREG if has(libc::S_ISGID) && self.colored("sg").is_some() => "sg",
+            libc::S_IFREG if mode & 0o111 != 0 && self.colored("ex").is_some() => "ex",
+            libc::S_IFREG if nlink > 1 && self.colored("mh").is_some() => "mh",
+            libc::S_IFREG => "fi",
+            libc::S_IFDIR if has(libc::S_ISVTX | libc::S_IWOTH) && self.colored("tw").is_some() => "tw",
+            libc::S_IFDIR if has(libc::S_IWOTH) && self.colored("ow").is_some() => "ow",
+            libc::S_IFDIR if has(libc::S_ISVTX) && self.colored("st").is_some() => "st",
+            libc::S_IFDIR => "di",
+            libc::S_IFLNK => "ln",
+            libc::S_IFIFO => "pi",
+            libc::S_IFSOCK => "so",
+            libc::S_IFBLK => "bd",
+            libc::S_IFCHR => "cd",
+            _ => "no",
+        }
+    }
+
+    /// The SGR parameters to color the name of `entry` with, None to leave it as it is
+    pub(crate) fn sgr(&self, entry: &EntryData) -> Option<&str> {
+        let metadata = entry.metadata();
+        let (mut mode, mut nlink) = (metadata.mode(), metadata.nlink());
+        if metadata.is_symlink() {
+            match entry.target_metadata() {
+                Some(target) if self.link_target => (mode, nlink) = (target.mode(), target.nlink()),
+                Some(_) => {}
+                // a dangling link is an orphan
+                None if self.link_target || self.colored("or").is_some() => return self.colored("or"),
+                None => {}
+            }
+        }
+        let key = self.type_key(mode, nlink);
+        // only files with no other type are colored by their names
+        if key == "fi" {
+            let name = entry.name().to_lowercase();
+            if let Some((_, sgr)) = self.suffixes.iter().rev().find(|(suffix, _)| name.ends_with(suffix.as_str())) {
+                return Some(sgr.as_str()).filter(|sgr| !sgr.is_empty());
+            }
+        }
+        self.colored(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LsColors;
+
+    #[test]
+    fn colors_files_with_several_links() {
+        let colors = LsColors::parse("mh=44;37");
+        assert_eq!(colors.type_key(libc::S_IFREG | 0o644, 2), "mh");
+        assert_eq!(colors.type_key(libc::S_IFREG | 0o644, 1), "fi");
+        // the type of a file comes before its links
+        assert_eq!(colors.type_key(libc::S_IFREG | 0o755, 2), "ex");
+        assert_eq!(colors.type_key(libc::S_IFDIR | 0o755, 2), "di");
+    }
+
+    #[test]
+    fn keeps_the_defaults_not_given() {
+        let colors = LsColors::parse("di=01;33:*.TAR=01;31:*.tar=31:ex=:bogus:xyz=1");
+        assert_eq!(colors.colored("di"), Some("01;33"));
+        assert_eq!(colors.colored("ln"), Some("01;36"));
+        assert_eq!(colors.colored("ex"), None);
+        assert_eq!(colors.colored("xyz"), None);
+        // the last of the suffixes matching a name wins
+        assert_eq!(colors.suffixes, [(".tar".to_string(), "01;31".to_string()), (".tar".to_string(), "31".to_string())]);
+        assert!(!colors.link_target);
+        assert!(LsColors::parse("ln=target").link_target);
+    }
+
+    #[test]
+    fn chooses_types_as_gnu_ls() {
+        let colors = LsColors::parse("ow=00");
+        assert_eq!(colors.type_key(libc::S_IFREG | 0o4755, 1), "su");
+        assert_eq!(colors.type_key(libc::S_IFREG | 0o755, 1), "ex");
+        assert_eq!(colors.type_key(libc::S_IFREG | 0o644, 1), "fi");
+        assert_eq!(colors.type_key(libc::S_IFDIR | 0o1777, 2), "tw");
+        // a type without a color falls back to the next one
+        assert_eq!(colors.type_key(libc::S_IFDIR | 0o777, 2), "di");
+        assert_eq!(colors.type_key(libc::S_IFREG | 0o644, 2), "fi");
+        assert_eq!(colors.type_key(libc::S_IFIFO | 0o644, 1), "pi");
+    }
+}
@@ -1,30 +1,34 @@
+#![deny(clippy::unwrap_used, clippy::expect_used)]
 // mod posix;
-use clap::{Arg, ArgAction, Command};
-use listare;
-
-fn get_terminal_width() -> Option<usize> {
-    if let Some(winsize) = listare::posix::get_winsize() {
-        Some(winsize.cols)
-    } else if let Ok(val) = std::env::var("COLUMNS") {
-        if let Ok(num) = val.parse::<usize>() {
-            if num > 0 {
-                Some(num)
-            } else {
-                None
-            }
-        } else {
-            None
-        }
-    } else {
-        None
-    }
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::io::IsTerminal;
+
+#[cfg(feature = "memprofile")]
+#[global_allocator]
+static ALLOCATOR: listare::memprofile::Counting = listare::memprofile::Counting;
+
+fn random_seed() -> u64 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    now.as_nanos() as u64 ^ ((std::process::id() as u64) << 32)
 }
 
 fn build_command() -> Command {
-    Command::new("listare")
+    let command = Command::new("listare")
         .version("0.1.0")
         .author("Derek Wisong <derekwisong@gmail.com>")
         .about("My version of `ls`")
+        // -h is used for human readable sizes, as in ls
+        .disable_help_flag(true)
+        // a `help` subcommand would hide files named help
+        .disable_help_subcommand(true)
+        .arg(
+            Arg::new("help")
+                .long("help")
+                .action(ArgAction::Help)
+                .help("Print help"),
+        )
         .arg(
             Arg::new("files")
                 .value_name("FILE")
@@ -37,7 +41,26 @@ fn build_command() -> Command {
                 .short('a')
                 .long("all")
                 .action(ArgAction::SetTrue)
-                .help("Show hidden files (do not ignore entries starting with .)"),
+                .help("Show hidden files and the implied . and .. (do not ignore entries starting with .)"),
+        )
+        .arg(
+            Arg::new("almost-all")
+                .short('A')
+                .long("almost-all")
+                .action(ArgAction::SetTrue)
+                .help("Show hidden files, but not the implied . and .."),
+        )
+        .arg(
+            Arg::new("count-skipped")
+                .long("count-skipped")
+                .action(ArgAction::SetTrue)
+                .help("After each directory, print how many entries were hidden or could not be read"),
+        )
+        .arg(
+            Arg::new("dirs-first-page")
+                .long("dirs-first-page")
+                .action(ArgAction::SetTrue)
+                .help("Only list directories, in a grid, quickly enough to run on every keystroke of a shell completion"),
         )
         .arg(
             Arg::new("directory")
@@ -46,6 +69,75 @@ fn build_command() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("List directories themselves, not their contents"),
         )
+        .arg(
+            Arg::new("inode")
+                .short('i')
+                .long("inode")
+                .action(ArgAction::SetTrue)
+                .help("Print the index number of each file"),
+        )
+        .arg(
+            Arg::new("classify")
+                .short('F')
+                .long("classify")
+                .action(ArgAction::SetTrue)
+                .help("Append an indicator of the file type to names: '/' for directories, '*' for executables, '@' for symlinks, '|' for FIFOs and '=' for sockets"),
+        )
+        .arg(
+            Arg::new("slash")
+                .short('p')
+                .action(ArgAction::SetTrue)
+                .help("Append '/' to the names of directories; the last of -p and -F wins"),
+        )
+        .arg(
+            Arg::new("warn-insecure")
+                .long("warn-insecure")
+                .action(ArgAction::SetTrue)
+                .help("Mark world-writable and setuid files with '!' and count them on stderr"),
+        )
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .action(ArgAction::SetTrue)
+                .help("In long format, print a line of column titles above the entries"),
+        )
+        .arg(
+            Arg::new("octal-permissions")
+                .long("octal-permissions")
+                .action(ArgAction::SetTrue)
+                .help("In long format, also print the permissions as an octal number"),
+        )
+        .arg(
+            Arg::new("xattrs")
+                .short('@')
+                .long("xattrs")
+                .action(ArgAction::SetTrue)
+                .help("In long format, print the names and value sizes of extended attributes below each entry"),
+        )
+        .arg(
+            Arg::new("can")
+                .long("can")
+                .value_name("ACCESSES")
+                .value_parser(["read", "write", "exec"])
+                .value_delimiter(',')
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_values(["read", "write", "exec"])
+                .help("In long format, show whether you can read, write and execute each file, or only the accesses listed"),
+        )
+        .arg(
+            Arg::new("capabilities")
+                .long("capabilities")
+                .action(ArgAction::SetTrue)
+                .help("In long format, print the file capabilities of each file, e.g. cap_net_bind_service+ep"),
+        )
+        .arg(
+            Arg::new("context")
+                .short('Z')
+                .long("context")
+                .action(ArgAction::SetTrue)
+                .help("Print the security context of each file"),
+        )
         .arg(
             Arg::new("long")
                 .short('l')
@@ -53,43 +145,801 @@ fn build_command() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Use a long listing format"),
         )
+        .arg(
+            Arg::new("grid")
+                .long("grid")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("xattrs")
+                .help("With -l, lay out the records side by side in columns when the line is wide enough"),
+        )
+        .arg(
+            Arg::new("no-owner-long")
+                .short('g')
+                .action(ArgAction::SetTrue)
+                .help("Like -l, but do not list the owner"),
+        )
+        .arg(
+            Arg::new("no-group-long")
+                .short('o')
+                .action(ArgAction::SetTrue)
+                .help("Like -l, but do not list group information"),
+        )
+        .arg(
+            Arg::new("no-group")
+                .short('G')
+                .long("no-group")
+                .action(ArgAction::SetTrue)
+                .help("In a long listing, don't print group names"),
+        )
+        .arg(
+            Arg::new("numeric-uid-gid")
+                .short('n')
+                .long("numeric-uid-gid")
+                .action(ArgAction::SetTrue)
+                .help("Like -l, but list numeric user and group IDs"),
+        )
+        .arg(
+            Arg::new("mark-groups")
+                .long("mark-groups")
+                .action(ArgAction::SetTrue)
+                .help("With -l, mark the group with '*' when you are a member of it"),
+        )
+        .arg(
+            Arg::new("normalize-targets")
+                .long("normalize-targets")
+                .action(ArgAction::SetTrue)
+                .help("With -l, show symlink targets with '.' and 'dir/..' components removed"),
+        )
+        .arg(
+            Arg::new("relative-to")
+                .long("relative-to")
+                .value_name("DIR")
+                .value_parser(clap::value_parser!(std::path::PathBuf))
+                .help("With -l, show symlink targets as paths relative to DIR"),
+        )
+        .arg(
+            Arg::new("strip-prefix")
+                .long("strip-prefix")
+                .value_name("DIR")
+                .value_parser(clap::value_parser!(std::path::PathBuf))
+                .help("Print the paths given on the command line without their leading DIR, e.g. for rsync or tar file lists"),
+        )
+        .arg(
+            Arg::new("human-readable")
+                .short('h')
+                .long("human-readable")
+                .action(ArgAction::SetTrue)
+                .help("With -l, print sizes like 1K 234M 2G etc."),
+        )
+        .arg(
+            Arg::new("si")
+                .long("si")
+                .action(ArgAction::SetTrue)
+                .overrides_with_all(["human-readable", "block-size"])
+                .help("Like -h, but use powers of 1000 not 1024"),
+        )
+        .arg(
+            Arg::new("auto-shrink")
+                .long("auto-shrink")
+                .action(ArgAction::SetTrue)
+                .help("With -l, drop the group, link count and owner columns, in that order, until lines fit the terminal"),
+        )
+        .arg(
+            Arg::new("size")
+                .short('s')
+                .long("size")
+                .action(ArgAction::SetTrue)
+                .help("Print the allocated size of each file, in blocks"),
+        )
+        .arg(
+            Arg::new("total-apparent")
+                .long("total-apparent")
+                .action(ArgAction::SetTrue)
+                .help("In long format, follow the total of allocated blocks with the total of file sizes"),
+        )
+        .arg(
+            Arg::new("total-size")
+                .long("total-size")
+                .action(ArgAction::SetTrue)
+                .help("In long format, show the size of a directory as the apparent size of everything in it, recursively, without following symlinks (slow)"),
+        )
+        .arg(
+            Arg::new("count-links-once")
+                .long("count-links-once")
+                .action(ArgAction::SetTrue)
+                .help("In long format, count a file with several hard links once in the total and in --total-size, as du does"),
+        )
+        .arg(
+            Arg::new("block-size")
+                .long("block-size")
+                .value_name("SIZE")
+                .value_parser(listare::SizeFormat::parse)
+                .overrides_with_all(["human-readable", "si"])
+                .help("Scale sizes by SIZE before printing them, e.g. '--block-size=M'"),
+        )
+        .arg(
+            Arg::new("ambiguous-wide")
+                .long("ambiguous-wide")
+                .action(ArgAction::SetTrue)
+                .help("Count characters of ambiguous width (e.g. § ° ±) as two columns, as CJK terminals draw them"),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .value_name("WHEN")
+                .value_parser(["always", "yes", "force", "auto", "tty", "if-tty", "never", "no", "none"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("always")
+                .help("Color names always, never, or auto (only on a terminal, unless NO_COLOR, CLICOLOR=0 or CLICOLOR_FORCE says otherwise); --color alone means always"),
+        )
+        .arg(
+            Arg::new("hyperlink")
+                .long("hyperlink")
+                .value_name("WHEN")
+                .value_parser(["always", "yes", "force", "auto", "tty", "if-tty", "never", "no", "none"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("always")
+                .help("Make names clickable file:// links always, never, or auto (only on a terminal); --hyperlink alone means always"),
+        )
+        .arg(
+            Arg::new("icons")
+                .long("icons")
+                .action(ArgAction::SetTrue)
+                .help("Put an icon before each name, chosen by its name, extension or type (needs a Nerd Font)"),
+        )
+        .arg(
+            Arg::new("theme")
+                .long("theme")
+                .value_name("FILE")
+                .value_parser(|path: &str| listare::Palette::load(std::path::Path::new(path)))
+                .help("Replace the built-in colors with those of a TOML theme file, which may use 256 and 24-bit colors"),
+        )
+        .arg(
+            Arg::new("color-scale")
+                .long("color-scale")
+                .value_name("COLUMNS")
+                .value_parser(["age", "size", "all"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("all")
+                .help("In the long format, color sizes and times brighter the larger and more recent they are among the entries; --color-scale alone means all"),
+        )
+        .arg(
+            Arg::new("background")
+                .long("background")
+                .value_name("SHADE")
+                .value_parser(["light", "dark", "auto"])
+                .default_value("auto")
+                .help("Pick colors readable on a light or dark terminal background, detected if auto and colors are used"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("WORD")
+                .value_parser(["across", "commas", "horizontal", "long", "single-column", "verbose", "vertical"])
+                .help("Lay out entries across (-x), horizontal (-x), commas (-m), long (-l), single-column (-1), verbose (-l) or vertical (-C); the last of these options wins"),
+        )
+        .arg(
+            Arg::new("columns")
+                .short('C')
+                .action(ArgAction::SetTrue)
+                .help("List entries by columns, like --format=vertical"),
+        )
         .arg(
             Arg::new("bylines")
                 .short('x')
                 .action(ArgAction::SetTrue)
-                .help("List entries by lines instead of by columns"),
+                .help("List entries by lines instead of by columns, like --format=across"),
+        )
+        .arg(
+            Arg::new("width")
+                .short('w')
+                .long("width")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Fit lines within N columns instead of the width of the terminal or COLUMNS; 0 for no limit"),
+        )
+        .arg(
+            Arg::new("tabsize")
+                .short('T')
+                .long("tabsize")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Pad between grid columns with tabs to stops every N columns where they are shorter than spaces; 0 for spaces only"),
+        )
+        .arg(
+            Arg::new("commas")
+                .short('m')
+                .action(ArgAction::SetTrue)
+                .help("List entries separated by commas, like --format=commas"),
+        )
+        .arg(
+            Arg::new("single-column")
+                .short('1')
+                .action(ArgAction::SetTrue)
+                .help("List one entry per line, like --format=single-column"),
         )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .value_name("WORD")
+                .value_parser(["name", "width", "size", "time", "random", "inode", "none"])
+                .default_value("name")
+                .overrides_with_all(["sort-size", "sort-time"])
+                .help("Sort by WORD instead of name"),
+        )
+        .arg(
+            Arg::new("sort-time")
+                .short('t')
+                .action(ArgAction::SetTrue)
+                .overrides_with_all(["sort", "sort-size"])
+                .help("Sort by time, newest first; see --time"),
+        )
+        .arg(
+            Arg::new("sort-size")
+                .short('S')
+                .action(ArgAction::SetTrue)
+                .overrides_with_all(["sort", "sort-time"])
+                .help("Sort by file size, largest first"),
+        )
+        .arg(
+            Arg::new("separators")
+                .long("separators")
+                .value_name("STYLE")
+                .value_parser(["blank", "labeled"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("labeled")
+                .help("When sorting by time or size, separate groups of entries by age or magnitude"),
+        )
+        .arg(
+            Arg::new("time")
+                .long("time")
+                .value_name("WORD")
+                .value_parser([
+                    "atime", "access", "use", "ctime", "status", "birth", "creation", "mtime", "modification",
+                ])
+                .help("Show and sort by the access, status change, birth or modification time"),
+        )
+        .arg(
+            Arg::new("hide-control-chars")
+                .short('q')
+                .long("hide-control-chars")
+                .action(ArgAction::SetTrue)
+                .overrides_with("show-control-chars")
+                .help("Print control characters in names and other fields as '?' (the default on a terminal)"),
+        )
+        .arg(
+            Arg::new("show-control-chars")
+                .long("show-control-chars")
+                .action(ArgAction::SetTrue)
+                .overrides_with("hide-control-chars")
+                .help("Print control characters in names as they are, even on a terminal"),
+        )
+        .arg(
+            Arg::new("plain")
+                .long("plain")
+                .action(ArgAction::SetTrue)
+                .help("Print the same output whatever the environment: C locale, no color, byte order, long-iso times, 80 columns and English messages (also LISTARE_PLAIN=1)"),
+        )
+        .arg(
+            Arg::new("all-times")
+                .long("all-times")
+                .action(ArgAction::SetTrue)
+                .help("In long format, show the modification, access, status change and birth times in their own columns"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64))
+                .help("Seed for --sort=random, to reproduce a previous order"),
+        )
+        .arg(
+            Arg::new("annotate-cmd")
+                .long("annotate-cmd")
+                .value_name("CMD")
+                .help("In the long format, run CMD for each entry ({} is replaced by its path) and show the first line of output (slow)"),
+        )
+        .arg(
+            Arg::new("time-style")
+                .long("time-style")
+                .value_name("STYLE")
+                .value_parser(listare::TimeStyle::parse)
+                .help("Show times using full-iso, long-iso, iso, locale, relative, precise or +FORMAT (a second line after a newline applies to recent files); overrides TIME_STYLE"),
+        )
+        .arg(
+            Arg::new("time-zone")
+                .long("time-zone")
+                .value_name("ZONE")
+                .value_parser(listare::parse_time_zone)
+                .help("Show times in ZONE, UTC or an IANA name such as Europe/Paris, instead of the local time zone"),
+        )
+        .arg(
+            Arg::new("format-string")
+                .long("format-string")
+                .value_name("TEMPLATE")
+                .value_parser(listare::FormatString::parse)
+                .help("Print each entry on its own line as TEMPLATE, with stat-like directives such as %n (name), %s (size), %U (owner), %A (mode), %y (modification time) and %N (name and link target)"),
+        )
+        .arg(
+            Arg::new("choose")
+                .long("choose")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["json-lines", "format-string", "long", "grid"])
+                .help("Pick entries on the terminal with the arrow keys, space to mark and Enter to print their paths, e.g. vim $(listare --choose)"),
+        )
+        .arg(
+            Arg::new("zero")
+                .long("zero")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("grid")
+                .help("End each line with a NUL instead of a newline, one entry per line, without color or hidden control characters, as for xargs -0"),
+        )
+        .arg(
+            Arg::new("json-lines")
+                .long("json-lines")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["format-string", "grid", "xattrs"])
+                .help("Print each entry as a JSON object on its own line; with --sort=none, entries are printed as they are read"),
+        )
+        .arg(
+            Arg::new("markdown")
+                .long("markdown")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["json-lines", "format-string", "choose", "zero", "grid", "xattrs"])
+                .help("Print the long format fields as a GitHub-flavored Markdown table, without color"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(std::path::PathBuf))
+                .help("Write the listing to FILE instead of stdout, without color"),
+        )
+        .arg(
+            Arg::new("full-time")
+                .long("full-time")
+                .action(ArgAction::SetTrue)
+                .help("Like -l --time-style=full-iso"),
+        )
+        .arg(
+            Arg::new("epoch")
+                .long("epoch")
+                .value_name("UNIT")
+                .value_parser(["s", "ms", "ns"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("s")
+                .help("Print timestamps as integers since the epoch in UNIT"),
+        )
+        .arg(
+            Arg::new("max-owner-width")
+                .long("max-owner-width")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("In long format, cut owner and group names longer than N columns, ending them with '…'"),
+        )
+        .arg(
+            Arg::new("max-entries")
+                .long("max-entries")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Stop with an error instead of listing more than N entries"),
+        )
+        .arg(
+            Arg::new("max-memory")
+                .long("max-memory")
+                .value_name("SIZE")
+                .value_parser(parse_memory)
+                .help("List directories whose entries would take more than SIZE bytes (e.g. 512M) in unsorted batches, as they are read, with the long format's total last"),
+        )
+        .arg(
+            Arg::new("timing")
+                .long("timing")
+                .action(ArgAction::SetTrue)
+                .help("Print how long the listing took on stderr, and its peak memory in memprofile builds"),
+        )
+        .arg(
+            Arg::new("info")
+                .long("info")
+                .value_name("FILE")
+                .conflicts_with("files")
+                .help("Print a detailed, stat-like report on FILE instead of listing"),
+        )
+        .arg(
+            Arg::new("print-shell-init")
+                .long("print-shell-init")
+                .value_name("SHELL")
+                .value_parser(["bash", "zsh", "fish"])
+                .conflicts_with_all(["files", "info"])
+                .help("Print aliases, a cd-and-list function and completions to load into SHELL (bash, zsh or fish)"),
+        );
+
+    #[cfg(feature = "fs-attributes")]
+    let command = command.arg(
+        Arg::new("fs-attributes")
+            .long("fs-attributes")
+            .action(ArgAction::SetTrue)
+            .help("In long format, print filesystem specific attributes such as btrfs compression and nocow"),
+    );
+
+    command
+}
+
+/// A `--max-memory` limit in bytes, given as for `--block-size`
+fn parse_memory(spec: &str) -> Result<u64, String> {
+    match listare::SizeFormat::parse(spec) {
+        Ok(listare::SizeFormat::Units { size, .. }) => Ok(size),
+        Ok(_) => Err(format!("not a number of bytes: {}", spec)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn get_matches(config: &listare::config::Config) -> ArgMatches {
+    let args = match config.expand_aliases(std::env::args_os().collect()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    build_command().get_matches_from(args)
 }
 
-fn parse_args() -> listare::Arguments {
-    let command = build_command();
-    let matches = command.get_matches();
+/// The command line position of a flag, None if it was not given there
+fn flag_index(matches: &ArgMatches, id: &str) -> Option<usize> {
+    match matches.value_source(id) {
+        Some(clap::parser::ValueSource::CommandLine) => matches.index_of(id),
+        _ => None,
+    }
+}
 
-    listare::Arguments {
-        max_line_length: get_terminal_width().unwrap_or(80),
-        paths: matches.get_many("files").unwrap().cloned().collect(),
-        list_dir_content: !matches.get_flag("directory"),
-        show_hidden: matches.get_flag("all"),
-        by_lines: matches.get_flag("bylines"),
-        long_format: matches.get_flag("long"),
+/// The layout asked for by `--format` or the flags standing for its values, `default` if none is
+/// As in GNU ls, the last of them on the command line wins. As in POSIX ls, -g,
+/// -o and -n imply -l, as does --full-time.
+fn layout(matches: &ArgMatches, default: listare::Layout) -> listare::Layout {
+    use listare::Layout;
+    let flags = [
+        ("long", Layout::Long),
+        ("no-owner-long", Layout::Long),
+        ("no-group-long", Layout::Long),
+        ("numeric-uid-gid", Layout::Long),
+        ("full-time", Layout::Long),
+        ("columns", Layout::Vertical),
+        ("bylines", Layout::Across),
+        ("commas", Layout::Commas),
+        ("single-column", Layout::SingleColumn),
+    ];
+    let format = matches.get_one::<String>("format").map(|word| match word.as_str() {
+        "across" | "horizontal" => Layout::Across,
+        "commas" => Layout::Commas,
+        "long" | "verbose" => Layout::Long,
+        "single-column" => Layout::SingleColumn,
+        _ => Layout::Vertical,
+    });
+    flags
+        .iter()
+        .filter_map(|&(id, layout)| Some((flag_index(matches, id)?, layout)))
+        .chain(flag_index(matches, "format").zip(format))
+        .max_by_key(|&(index, _)| index)
+        .map_or(default, |(_, layout)| layout)
+}
+
+/// Whether names are colored
+/// `--color=always` and `--color=never` decide over everything else. Plain
+/// output, NUL-terminated lines, Markdown and files are not colored otherwise.
+/// Then come the conventions of the environment: a non-empty `NO_COLOR` turns
+/// colors off, `CLICOLOR_FORCE` other than 0 turns them on even in a pipe, and
+/// `CLICOLOR=0` turns them off. Failing all of these, only a terminal gets colors.
+fn color(matches: &ArgMatches) -> bool {
+    match matches.get_one::<String>("color").map(String::as_str) {
+        Some("always" | "yes" | "force") => true,
+        Some("never" | "no" | "none") => false,
+        _ if is_plain(matches) || matches.get_flag("zero") || matches.get_flag("markdown") => false,
+        _ if matches.contains_id("output") => false,
+        _ if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) => false,
+        _ if std::env::var_os("CLICOLOR_FORCE").is_some_and(|value| !value.is_empty() && value != "0") => true,
+        _ if std::env::var_os("CLICOLOR").is_some_and(|value| value == "0") => false,
+        _ => std::io::stdout().is_terminal(),
+    }
+}
+
+/// The indicators asked for by -F or -p, the last of them on the command line winning as in GNU ls
+fn indicator_style(matches: &ArgMatches) -> listare::IndicatorStyle {
+    [("classify", listare::IndicatorStyle::Classify), ("slash", listare::IndicatorStyle::Slash)]
+        .iter()
+        .filter_map(|&(id, style)| Some((flag_index(matches, id)?, style)))
+        .max_by_key(|&(index, _)| index)
+        .map_or(listare::IndicatorStyle::None, |(_, style)| style)
+}
+
+/// Whether names are hyperlinks, as asked by `--hyperlink`
+fn hyperlink(matches: &ArgMatches, to_terminal: bool) -> bool {
+    match matches.get_one::<String>("hyperlink").map(String::as_str) {
+        Some("always" | "yes" | "force") => true,
+        Some("auto" | "tty" | "if-tty") => to_terminal && !is_plain(matches),
+        _ => false,
     }
 }
 
+/// Whether `--plain` or `LISTARE_PLAIN` asks for output that does not depend on the environment
+fn is_plain(matches: &ArgMatches) -> bool {
+    matches.get_flag("plain") || std::env::var("LISTARE_PLAIN").is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+fn parse_args(matches: &ArgMatches, config: &listare::config::Config) -> Result<listare::Arguments, clap::Error> {
+    let plain = is_plain(matches);
+    let zero = matches.get_flag("zero");
+    let output = matches.get_one::<std::path::PathBuf>("output");
+    let to_terminal = output.is_none() && std::io::stdout().is_terminal();
+    let (size_format, block_format) = if matches.get_flag("si") {
+        (listare::SizeFormat::Si, listare::SizeFormat::Si)
+    } else if matches.get_flag("human-readable") {
+        (listare::SizeFormat::Human, listare::SizeFormat::Human)
+    } else if let Some(format) = matches.get_one::<listare::SizeFormat>("block-size") {
+        (*format, *format)
+    } else if plain {
+        (listare::SizeFormat::BYTES, listare::SizeFormat::Units { size: 1024, suffix: "" })
+    } else {
+        listare::SizeFormat::from_env()
+    };
+
+    // --full-time always implies -l, but the last of it and --time-style picks the style
+    let full_time_last = matches.get_flag("full-time")
+        && matches.index_of("full-time") > matches.index_of("time-style");
+    let time_style = match matches.get_one::<listare::TimeStyle>("time-style") {
+        _ if full_time_last => listare::TimeStyle::full_iso(),
+        Some(style) => style.clone(),
+        None if plain => listare::TimeStyle::long_iso(),
+        None => match listare::TimeStyle::from_env() {
+            Some(Ok(style)) => style,
+            Some(Err(e)) => {
+                eprintln!("TIME_STYLE: {}", e);
+                std::process::exit(2);
+            }
+            None => listare::TimeStyle::Locale,
+        },
+    };
+
+    let dirs_only = matches.get_flag("dirs-first-page");
+    let time_field = match matches.get_one::<String>("time").map(String::as_str) {
+        Some("atime" | "access" | "use") => listare::TimeField::Accessed,
+        Some("ctime" | "status") => listare::TimeField::Changed,
+        Some("birth" | "creation") => listare::TimeField::Birth,
+        _ => listare::TimeField::Modified,
+    };
+
+    // as in ls, a listing read by another program has one entry per line, e.g. for grep
+    let layout = match layout(matches, if to_terminal { listare::Layout::Vertical } else { listare::Layout::SingleColumn }) {
+        _ if matches.get_flag("markdown") => listare::Layout::Long,
+        // --dirs-first-page is a quick look, never in the long format
+        listare::Layout::Long if dirs_only => listare::Layout::Vertical,
+        listare::Layout::Long => listare::Layout::Long,
+        // a grid or list would end its lines with newlines
+        _ if zero => listare::Layout::SingleColumn,
+        layout => layout,
+    };
+    // the annotations are a column of the long format, however it was asked for
+    let annotate_cmd = matches.get_one::<String>("annotate-cmd").cloned();
+    if annotate_cmd.is_some() && layout != listare::Layout::Long {
+        build_command()
+            .error(clap::error::ErrorKind::ArgumentConflict, "--annotate-cmd can only be used with the long format")
+            .exit();
+    }
+
+    let palette = matches.get_one::<listare::Palette>("theme").cloned().unwrap_or_else(|| config.palette.clone());
+    let ls_colors = std::env::var("LS_COLORS").ok().map(|text| listare::LsColors::parse(&text));
+    let paths = matches.get_many::<String>("files").ok_or_else(|| {
+        build_command().error(clap::error::ErrorKind::MissingRequiredArgument, "no file to list")
+    })?;
+
+    Ok(listare::Arguments {
+        max_line_length: if let Some(&width) = matches.get_one::<usize>("width") {
+            if width == 0 { usize::MAX } else { width }
+        } else if plain {
+            listare::line_length(None, None)
+        } else {
+            listare::line_length(
+                std::env::var("COLUMNS").ok().as_deref(),
+                listare::posix::get_winsize().map(|w| w.cols),
+            )
+        },
+        // names in a file or pipe are kept whole for the programs reading them
+        truncate_names: to_terminal && !plain,
+        paths: paths.cloned().collect(),
+        list_dir_content: !matches.get_flag("directory"),
+        show_hidden: matches.get_flag("all") || matches.get_flag("almost-all"),
+        // as in GNU ls, the last of -a and -A wins
+        show_implied: flag_index(matches, "all") > flag_index(matches, "almost-all"),
+        count_skipped: matches.get_flag("count-skipped"),
+        time_field,
+        all_times: matches.get_flag("all-times"),
+        separators: match matches.get_one::<String>("separators").map(String::as_str) {
+            Some("blank") => Some(listare::SeparatorStyle::Blank),
+            Some("labeled") => Some(listare::SeparatorStyle::Labeled),
+            _ => None,
+        },
+        ambiguous_wide: matches.get_flag("ambiguous-wide"),
+        // names could otherwise write escape sequences to the terminal
+        hide_control_chars: if matches.get_flag("show-control-chars") {
+            false
+        } else {
+            matches.get_flag("hide-control-chars") || (!plain && !zero && to_terminal)
+        },
+        theme: listare::Theme {
+            background: match matches.get_one::<String>("background").map(String::as_str) {
+                Some("light") => listare::Background::Light,
+                Some("dark") => listare::Background::Dark,
+                // querying the terminal could take longer than the whole listing
+                _ if dirs_only || plain => listare::Background::Dark,
+                // the terminal is only asked when a built-in color readable on it will be printed
+                _ if !color(matches) || ls_colors.is_some() || palette.covers_background() => {
+                    listare::Background::Dark
+                }
+                _ => listare::Background::detect(),
+            },
+            rules: config.color_rules.clone(),
+            uid: users::get_current_uid(),
+            palette,
+            ls_colors,
+        },
+        hyperlink: hyperlink(matches, to_terminal),
+        icons: matches.get_flag("icons").then(|| config.icons.clone()),
+        color_scale: match matches.get_one::<String>("color-scale").map(String::as_str) {
+            Some("age") => Some(listare::ColorScale::Age),
+            Some("size") => Some(listare::ColorScale::Size),
+            Some(_) => Some(listare::ColorScale::All),
+            None => None,
+        },
+        filter: None,
+        tab_size: matches.get_one::<usize>("tabsize").copied().unwrap_or(0),
+        layout,
+        dirs_only,
+        header: matches.get_flag("header"),
+        long_grid: matches.get_flag("grid"),
+        format_string: matches.get_one::<listare::FormatString>("format-string").cloned(),
+        json_lines: matches.get_flag("json-lines"),
+        output: output.cloned(),
+        markdown: matches.get_flag("markdown"),
+        choose: matches.get_flag("choose"),
+        zero,
+        warn_insecure: matches.get_flag("warn-insecure"),
+        indicator_style: indicator_style(matches),
+        sort: match matches.get_one::<String>("sort").map(String::as_str) {
+            _ if matches.get_flag("sort-time") => listare::SortKey::Time(time_field),
+            _ if matches.get_flag("sort-size") => listare::SortKey::Size,
+            Some("size") => listare::SortKey::Size,
+            Some("width") => listare::SortKey::Width,
+            Some("inode") => listare::SortKey::Inode,
+            Some("none") => listare::SortKey::None,
+            Some("time") => listare::SortKey::Time(time_field),
+            Some("random") => listare::SortKey::Random {
+                seed: matches.get_one::<u64>("seed").copied().unwrap_or_else(random_seed),
+            },
+            _ => listare::SortKey::Name,
+        },
+        // output for programs must not change with the locale
+        byte_order: plain || matches.get_flag("json-lines"),
+        annotate_cmd,
+        max_entries: matches.get_one::<usize>("max-entries").copied(),
+        max_memory: matches.get_one::<u64>("max-memory").copied(),
+        epoch: match matches.get_one::<String>("epoch").map(String::as_str) {
+            Some("s") => Some(listare::EpochUnit::Seconds),
+            Some("ms") => Some(listare::EpochUnit::Milliseconds),
+            Some("ns") => Some(listare::EpochUnit::Nanoseconds),
+            _ => None,
+        },
+        size_format,
+        block_format,
+        time_style,
+        show_inode: matches.get_flag("inode"),
+        show_context: matches.get_flag("context"),
+        show_capabilities: matches.get_flag("capabilities"),
+        show_fs_attributes: cfg!(feature = "fs-attributes") && matches.get_flag("fs-attributes"),
+        octal_permissions: matches.get_flag("octal-permissions"),
+        can: matches
+            .get_many::<String>("can")
+            .into_iter()
+            .flatten()
+            .map(|access| match access.as_str() {
+                "read" => listare::Access::Read,
+                "write" => listare::Access::Write,
+                _ => listare::Access::Execute,
+            })
+            .collect(),
+        list_xattrs: matches.get_flag("xattrs"),
+        long_alignment: config.long_alignment.clone(),
+        auto_shrink: matches.get_flag("auto-shrink"),
+        numeric_ids: matches.get_flag("numeric-uid-gid"),
+        max_owner_width: matches.get_one::<usize>("max-owner-width").copied(),
+        show_blocks: matches.get_flag("size"),
+        total_apparent: matches.get_flag("total-apparent"),
+        total_size: matches.get_flag("total-size"),
+        count_links_once: matches.get_flag("count-links-once"),
+        mark_groups: matches.get_flag("mark-groups"),
+        show_owner: !matches.get_flag("no-owner-long"),
+        show_group: !(matches.get_flag("no-group-long") || matches.get_flag("no-group")),
+        normalize_targets: matches.get_flag("normalize-targets"),
+        targets_relative_to: matches
+            .get_one::<std::path::PathBuf>("relative-to")
+            .and_then(|dir| std::path::absolute(dir).ok()),
+        strip_prefix: matches.get_one::<std::path::PathBuf>("strip-prefix").cloned(),
+    })
+}
+
+/// The `--timing` report of a run that took `elapsed`
+fn timing(elapsed: std::time::Duration) -> String {
+    let report = format!("listed in {:.3} s", elapsed.as_secs_f64());
+    #[cfg(feature = "memprofile")]
+    let report = format!("{}, peak memory {} bytes", report, listare::memprofile::peak());
+    report
+}
+
 fn main() {
-    let args = parse_args();
+    let config = listare::config::Config::load().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let matches = get_matches(&config);
+
+    // chrono's local time follows TZ, so every timestamp printed is in this zone
+    if let Some(zone) = matches.get_one::<String>("time-zone") {
+        std::env::set_var("TZ", zone);
+    }
+
+    // messages follow the locale variables, and C gives English
+    if is_plain(&matches) {
+        std::env::set_var("LC_ALL", "C");
+    }
+    colored::control::set_override(color(&matches));
 
     // sorting by name is done with strcoll, which is locale-aware
     let _ = listare::posix::setlocale(listare::posix::Locale::UserPreferred);
 
-    match listare::run(&args) {
+    let shell = matches.get_one::<String>("print-shell-init").map(String::as_str);
+    let result = match (matches.get_one::<String>("info"), shell) {
+        (Some(file), _) => listare::info::print_info(file),
+        (None, Some("bash")) => listare::shell::print_integration(listare::shell::Shell::Bash, build_command()),
+        (None, Some("fish")) => listare::shell::print_integration(listare::shell::Shell::Fish, build_command()),
+        (None, Some(_)) => listare::shell::print_integration(listare::shell::Shell::Zsh, build_command()),
+        (None, None) => {
+            let start = std::time::Instant::now();
+            let args = parse_args(&matches, &config).unwrap_or_else(|e| e.exit());
+            let result = listare::run(&args);
+            if matches.get_flag("timing") {
+                eprintln!("listare: {}", timing(start.elapsed()));
+            }
+            result
+        }
+    };
+
+    match result {
         Err(listare::ListareError::Generic(msg)) => {
             eprintln!("{}", msg);
             std::process::exit(1);
         }
-        Err(listare::ListareError::Unknown) => {
-            eprintln!("An unknown error occurred");
+        Err(e @ listare::ListareError::Unknown) => {
+            eprintln!("{}", e);
             std::process::exit(1);
         },
+        Err(e @ listare::ListareError::LimitExceeded(_)) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+        // each path has already been reported
+        Err(listare::ListareError::Cancelled) => std::process::exit(1),
+        Err(listare::ListareError::PathErrors { serious }) => {
+            std::process::exit(if serious { 2 } else { 1 });
+        }
+        // the reader went away, e.g. `listare | head`, and wants no more output
+        Err(listare::ListareError::Write(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+        Err(e @ listare::ListareError::Write(_)) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
         Ok(_) => {}
     };
 }
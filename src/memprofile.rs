@@ -0,0 +1,61 @@
+//! Allocation tracking for builds with the `memprofile` feature
+//!
+//! [`Counting`] wraps the system allocator and keeps the number of bytes in use
+//! and the most ever in use, so that `--timing` can report the peak memory of
+//! a run. Only the binary installs it as the global allocator.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static IN_USE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, counting the bytes allocated through it
+pub struct Counting;
+
+impl Counting {
+    fn allocated(size: usize) {
+        let in_use = IN_USE.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(in_use, Ordering::Relaxed);
+    }
+
+    fn freed(size: usize) {
+        IN_USE.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Counting::allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Counting::allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Counting::freed(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Counting::freed(layout.size());
+            Counting::allocated(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// The most bytes allocated at once so far
+pub fn peak() -> usize {
+    PEAK.load(Ordering::Relaxed)
+}
@@ -0,0 +1,405 @@
+//! Translations of the messages listare prints
+//!
+//! The catalog is chosen by the language of `LC_ALL`, `LC_MESSAGES` or `LANG`,
+//! the first one set winning, and is built into the binary. Languages without
+//! a catalog get the English messages. Each `{}` in a message stands for one
+//! of its arguments, in order, such as a quoted path or a count.
+use std::sync::OnceLock;
+
+/// A message printed to the user
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Message {
+    CannotAccess,
+    CannotOpenDirectory,
+    ReadingDirectory,
+    Total,
+    Hidden,
+    Unreadable,
+    Insecure,
+    Batched,
+    CannotCreate,
+    NeedsTerminal,
+    WriteError,
+    UnknownError,
+    LimitExceeded,
+    PathErrors,
+    Cancelled,
+    TitleInode,
+    TitleBlocks,
+    TitleOctal,
+    TitlePermissions,
+    TitleCan,
+    TitleLinks,
+    TitleOwner,
+    TitleGroup,
+    TitleContext,
+    TitleCapabilities,
+    TitleAttributes,
+    TitleSize,
+    TitleDate,
+    TitleModified,
+    TitleAccessed,
+    TitleChanged,
+    TitleBirth,
+    TitleAnnotation,
+    TitleName,
+    AgeUnknown,
+    AgeFuture,
+    AgeToday,
+    AgeYesterday,
+    AgeThisWeek,
+    AgeThisMonth,
+    AgeThisYear,
+    AgeOlder,
+    SizeUnderTen,
+    SizeRange,
+    SizeAndOver,
+}
+
+impl Message {
+    /// Every message, for checking the catalogs
+    #[cfg(test)]
+    const ALL: [Message; 45] = [
+        Message::CannotAccess,
+        Message::CannotOpenDirectory,
+        Message::ReadingDirectory,
+        Message::Total,
+        Message::Hidden,
+        Message::Unreadable,
+        Message::Insecure,
+        Message::Batched,
+        Message::CannotCreate,
+        Message::NeedsTerminal,
+        Message::WriteError,
+        Message::UnknownError,
+        Message::LimitExceeded,
+        Message::PathErrors,
+        Message::Cancelled,
+        Message::TitleInode,
+        Message::TitleBlocks,
+        Message::TitleOctal,
+        Message::TitlePermissions,
+        Message::TitleCan,
+        Message::TitleLinks,
+        Message::TitleOwner,
+        Message::TitleGroup,
+        Message::TitleContext,
+        Message::TitleCapabilities,
+        Message::TitleAttributes,
+        Message::TitleSize,
+        Message::TitleDate,
+        Message::TitleModified,
+        Message::TitleAccessed,
+        Message::TitleChanged,
+        Message::TitleBirth,
+        Message::TitleAnnotation,
+        Message::TitleName,
+        Message::AgeUnknown,
+        Message::AgeFuture,
+        Message::AgeToday,
+        Message::AgeYesterday,
+        Message::AgeThisWeek,
+        Message::AgeThisMonth,
+        Message::AgeThisYear,
+        Message::AgeOlder,
+        Message::SizeUnderTen,
+        Message::SizeRange,
+        Message::SizeAndOver,
+    ];
+
+    fn english(self) -> &'static str {
+        match self {
+            Message::CannotAccess => "cannot access {}",
+            Message::CannotOpenDirectory => "cannot open directory {}",
+            Message::ReadingDirectory => "reading directory {}",
+            Message::Total => "total",
+            Message::Hidden => "+{} hidden",
+            Message::Unreadable => "+{} unreadable",
+            Message::Insecure => "{} world-writable or setuid files",
+            Message::Batched => "listing {} in unsorted batches to stay within --max-memory",
+            Message::CannotCreate => "cannot create {}",
+            Message::NeedsTerminal => "--choose needs a terminal",
+            Message::WriteError => "write error: {}",
+            Message::UnknownError => "An unknown error occurred",
+            Message::LimitExceeded => "Listing stopped: more than {} entries",
+            Message::PathErrors => "Some files could not be listed",
+            Message::Cancelled => "Nothing was chosen",
+            Message::TitleInode => "Inode",
+            Message::TitleBlocks => "Blocks",
+            Message::TitleOctal => "Octal",
+            Message::TitlePermissions => "Permissions",
+            Message::TitleCan => "Can",
+            Message::TitleLinks => "Links",
+            Message::TitleOwner => "Owner",
+            Message::TitleGroup => "Group",
+            Message::TitleContext => "Context",
+            Message::TitleCapabilities => "Capabilities",
+            Message::TitleAttributes => "Attributes",
+            Message::TitleSize => "Size",
+            Message::TitleDate => "Date",
+            Message::TitleModified => "Modified",
+            Message::TitleAccessed => "Accessed",
+            Message::TitleChanged => "Changed",
+            Message::TitleBirth => "Birth",
+            Message::TitleAnnotation => "Annotation",
+            Message::TitleName => "Name",
+            Message::AgeUnknown => "Unknown",
+            Message::AgeFuture => "Future",
+            Message::AgeToday => "Today",
+            Message::AgeYesterday => "Yesterday",
+            Message::AgeThisWeek => "This week",
+            Message::AgeThisMonth => "This month",
+            Message::AgeThisYear => "This year",
+            Message::AgeOlder => "Older",
+            Message::SizeUnderTen => "under 10 bytes",
+            Message::SizeRange => "{} to {}",
+            Message::SizeAndOver => "{} and over",
+        }
+    }
+}
+
+type Catalog = [(Message, &'static str)];
+
+const FRENCH: &Catalog = &[
+    (Message::CannotAccess, "impossible d'accéder à {}"),
+    (Message::CannotOpenDirectory, "impossible d'ouvrir le répertoire {}"),
+    (Message::ReadingDirectory, "lecture du répertoire {}"),
+    (Message::Total, "total"),
+    (Message::Hidden, "+{} cachés"),
+    (Message::Unreadable, "+{} illisibles"),
+    (Message::Insecure, "{} fichiers modifiables par tous ou setuid"),
+    (Message::Batched, "{} est listé par lots non triés pour rester sous --max-memory"),
+    (Message::CannotCreate, "impossible de créer {}"),
+    (Message::NeedsTerminal, "--choose a besoin d'un terminal"),
+    (Message::WriteError, "erreur d'écriture : {}"),
+    (Message::UnknownError, "Une erreur inconnue est survenue"),
+    (Message::LimitExceeded, "Listage interrompu : plus de {} entrées"),
+    (Message::PathErrors, "Certains fichiers n'ont pas pu être listés"),
+    (Message::Cancelled, "Rien n'a été choisi"),
+    (Message::TitleInode, "Inode"),
+    (Message::TitleBlocks, "Blocs"),
+    (Message::TitleOctal, "Octal"),
+    (Message::TitlePermissions, "Permissions"),
+    (Message::TitleCan, "Peut"),
+    (Message::TitleLinks, "Liens"),
+    (Message::TitleOwner, "Propriétaire"),
+    (Message::TitleGroup, "Groupe"),
+    (Message::TitleContext, "Contexte"),
+    (Message::TitleCapabilities, "Capacités"),
+    (Message::TitleAttributes, "Attributs"),
+    (Message::TitleSize, "Taille"),
+    (Message::TitleDate, "Date"),
+    (Message::TitleModified, "Modifié"),
+    (Message::TitleAccessed, "Accédé"),
+    (Message::TitleChanged, "Changé"),
+    (Message::TitleBirth, "Création"),
+    (Message::TitleAnnotation, "Annotation"),
+    (Message::TitleName, "Nom"),
+    (Message::AgeUnknown, "Inconnu"),
+    (Message::AgeFuture, "Futur"),
+    (Message::AgeToday, "Aujourd'hui"),
+    (Message::AgeYesterday, "Hier"),
+    (Message::AgeThisWeek, "Cette semaine"),
+    (Message::AgeThisMonth, "Ce mois-ci"),
+    (Message::AgeThisYear, "Cette année"),
+    (Message::AgeOlder, "Plus ancien"),
+    (Message::SizeUnderTen, "moins de 10 octets"),
+    (Message::SizeRange, "{} à {}"),
+    (Message::SizeAndOver, "{} et plus"),
+];
+
+const GERMAN: &Catalog = &[
+    (Message::CannotAccess, "Zugriff auf {} nicht möglich"),
+    (Message::CannotOpenDirectory, "Verzeichnis {} kann nicht geöffnet werden"),
+    (Message::ReadingDirectory, "Lesen von Verzeichnis {}"),
+    (Message::Total, "insgesamt"),
+    (Message::Hidden, "+{} versteckt"),
+    (Message::Unreadable, "+{} nicht lesbar"),
+    (Message::Insecure, "{} Dateien für alle schreibbar oder setuid"),
+    (Message::Batched, "{} wird in unsortierten Teilen aufgelistet, um unter --max-memory zu bleiben"),
+    (Message::CannotCreate, "{} kann nicht erstellt werden"),
+    (Message::NeedsTerminal, "--choose braucht ein Terminal"),
+    (Message::WriteError, "Schreibfehler: {}"),
+    (Message::UnknownError, "Ein unbekannter Fehler ist aufgetreten"),
+    (Message::LimitExceeded, "Auflistung abgebrochen: mehr als {} Einträge"),
+    (Message::PathErrors, "Einige Dateien konnten nicht aufgelistet werden"),
+    (Message::Cancelled, "Nichts wurde ausgewählt"),
+    (Message::TitleInode, "Inode"),
+    (Message::TitleBlocks, "Blöcke"),
+    (Message::TitleOctal, "Oktal"),
+    (Message::TitlePermissions, "Rechte"),
+    (Message::TitleCan, "Darf"),
+    (Message::TitleLinks, "Links"),
+    (Message::TitleOwner, "Besitzer"),
+    (Message::TitleGroup, "Gruppe"),
+    (Message::TitleContext, "Kontext"),
+    (Message::TitleCapabilities, "Capabilities"),
+    (Message::TitleAttributes, "Attribute"),
+    (Message::TitleSize, "Größe"),
+    (Message::TitleDate, "Datum"),
+    (Message::TitleModified, "Geändert"),
+    (Message::TitleAccessed, "Zugegriffen"),
+    (Message::TitleChanged, "Status"),
+    (Message::TitleBirth, "Erstellt"),
+    (Message::TitleAnnotation, "Anmerkung"),
+    (Message::TitleName, "Name"),
+    (Message::AgeUnknown, "Unbekannt"),
+    (Message::AgeFuture, "Zukunft"),
+    (Message::AgeToday, "Heute"),
+    (Message::AgeYesterday, "Gestern"),
+    (Message::AgeThisWeek, "Diese Woche"),
+    (Message::AgeThisMonth, "Diesen Monat"),
+    (Message::AgeThisYear, "Dieses Jahr"),
+    (Message::AgeOlder, "Älter"),
+    (Message::SizeUnderTen, "unter 10 Bytes"),
+    (Message::SizeRange, "{} bis {}"),
+    (Message::SizeAndOver, "{} und mehr"),
+];
+
+const SPANISH: &Catalog = &[
+    (Message::CannotAccess, "no se puede acceder a {}"),
+    (Message::CannotOpenDirectory, "no se puede abrir el directorio {}"),
+    (Message::ReadingDirectory, "leyendo el directorio {}"),
+    (Message::Total, "total"),
+    (Message::Hidden, "+{} ocultos"),
+    (Message::Unreadable, "+{} ilegibles"),
+    (Message::Insecure, "{} archivos modificables por todos o setuid"),
+    (Message::Batched, "{} se lista en lotes sin ordenar para no superar --max-memory"),
+    (Message::CannotCreate, "no se puede crear {}"),
+    (Message::NeedsTerminal, "--choose necesita una terminal"),
+    (Message::WriteError, "error de escritura: {}"),
+    (Message::UnknownError, "Se produjo un error desconocido"),
+    (Message::LimitExceeded, "Listado detenido: más de {} entradas"),
+    (Message::PathErrors, "Algunos archivos no se pudieron listar"),
+    (Message::Cancelled, "No se eligió nada"),
+    (Message::TitleInode, "Inodo"),
+    (Message::TitleBlocks, "Bloques"),
+    (Message::TitleOctal, "Octal"),
+    (Message::TitlePermissions, "Permisos"),
+    (Message::TitleCan, "Puede"),
+    (Message::TitleLinks, "Enlaces"),
+    (Message::TitleOwner, "Propietario"),
+    (Message::TitleGroup, "Grupo"),
+    (Message::TitleContext, "Contexto"),
+    (Message::TitleCapabilities, "Capacidades"),
+    (Message::TitleAttributes, "Atributos"),
+    (Message::TitleSize, "Tamaño"),
+    (Message::TitleDate, "Fecha"),
+    (Message::TitleModified, "Modificado"),
+    (Message::TitleAccessed, "Accedido"),
+    (Message::TitleChanged, "Cambiado"),
+    (Message::TitleBirth, "Creación"),
+    (Message::TitleAnnotation, "Anotación"),
+    (Message::TitleName, "Nombre"),
+    (Message::AgeUnknown, "Desconocido"),
+    (Message::AgeFuture, "Futuro"),
+    (Message::AgeToday, "Hoy"),
+    (Message::AgeYesterday, "Ayer"),
+    (Message::AgeThisWeek, "Esta semana"),
+    (Message::AgeThisMonth, "Este mes"),
+    (Message::AgeThisYear, "Este año"),
+    (Message::AgeOlder, "Más antiguo"),
+    (Message::SizeUnderTen, "menos de 10 bytes"),
+    (Message::SizeRange, "{} a {}"),
+    (Message::SizeAndOver, "{} o más"),
+];
+
+/// The language code of a locale name, e.g. `fr` for `fr_FR.UTF-8`
+fn language(locale: &str) -> &str {
+    let end = locale.find(['_', '.', '@']).unwrap_or(locale.len());
+    &locale[..end]
+}
+
+fn catalog_for(language: &str) -> Option<&'static Catalog> {
+    match language {
+        "fr" => Some(FRENCH),
+        "de" => Some(GERMAN),
+        "es" => Some(SPANISH),
+        _ => None,
+    }
+}
+
+/// The catalog of the user's language, looked up once
+fn catalog() -> Option<&'static Catalog> {
+    static CATALOG: OnceLock<Option<&'static Catalog>> = OnceLock::new();
+    *CATALOG.get_or_init(|| {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())?;
+        catalog_for(language(&locale))
+    })
+}
+
+fn lookup(catalog: Option<&Catalog>, message: Message) -> &'static str {
+    catalog
+        .and_then(|catalog| catalog.iter().find(|(m, _)| *m == message))
+        .map_or(message.english(), |(_, text)| text)
+}
+
+/// The text of a message without an argument
+pub(crate) fn text(message: Message) -> &'static str {
+    lookup(catalog(), message)
+}
+
+/// The text of a message with its argument filled in
+pub(crate) fn format(message: Message, argument: &str) -> String {
+    format_all(message, &[argument])
+}
+
+/// The text of a message with each of its arguments filled in, in order
+pub(crate) fn format_all(message: Message, arguments: &[&str]) -> String {
+    fill(text(message), arguments)
+}
+
+fn fill(text: &str, arguments: &[&str]) -> String {
+    let mut parts = text.split("{}");
+    let mut formatted = parts.next().unwrap_or_default().to_string();
+    for (part, argument) in parts.zip(arguments.iter().chain(std::iter::repeat(&""))) {
+        formatted.push_str(argument);
+        formatted.push_str(part);
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{catalog_for, fill, language, lookup, Message, FRENCH, GERMAN, SPANISH};
+
+    #[test]
+    fn finds_the_language_of_locales() {
+        assert_eq!(language("fr_FR.UTF-8"), "fr");
+        assert_eq!(language("de.UTF-8"), "de");
+        assert_eq!(language("sr@latin"), "sr");
+        assert_eq!(language("C"), "C");
+        assert!(catalog_for("en").is_none());
+        assert!(catalog_for("C").is_none());
+    }
+
+    #[test]
+    fn falls_back_to_english() {
+        assert_eq!(lookup(None, Message::Total), "total");
+        assert_eq!(lookup(Some(GERMAN), Message::Total), "insgesamt");
+        assert_eq!(lookup(Some(&[]), Message::Hidden), "+{} hidden");
+    }
+
+    #[test]
+    fn catalogs_are_complete() {
+        for catalog in [FRENCH, GERMAN, SPANISH] {
+            for message in Message::ALL {
+                let text = catalog.iter().find(|(m, _)| *m == message).map(|(_, text)| *text);
+                let text = text.unwrap_or_else(|| panic!("{:?} is missing", message));
+                // every message keeps its arguments
+                assert_eq!(text.matches("{}").count(), message.english().matches("{}").count(), "{}", text);
+            }
+            assert_eq!(catalog.len(), Message::ALL.len());
+        }
+    }
+
+    #[test]
+    fn fills_in_arguments_in_order() {
+        assert_eq!(fill(Message::SizeRange.english(), &["10", "100"]), "10 to 100");
+        assert_eq!(fill(Message::CannotAccess.english(), &["'{}'"]), "cannot access '{}'");
+        assert_eq!(fill(Message::SizeRange.english(), &["10"]), "10 to ");
+    }
+}
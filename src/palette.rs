@@ -0,0 +1,129 @@
+//! Theme files replacing the built-in colors
+//!
+//! A theme is a TOML file given with `--theme` or as `theme` in the config
+//! file. Its `[types]` give styles to kinds of files, and its `[extensions]`
+//! to regular files by extension, ahead of the built-in extensions. Styles can
+//! use the 256-color palette and 24-bit colors, as in the config file's rules.
+//!
+//! ```toml
+//! [types]
+//! directory = "bold 33"
+//! symlink = "#5fd7ff"
+//! executable = "bold 118"
+//! archive = "black on 214"
+//!
+//! [extensions]
+//! md = "underline"
+//! lock = "dimmed"
+//! ```
+//!
+//! Kinds of files not in the theme keep their built-in colors.
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::config::ConfigError;
+use crate::theme::Category;
+use crate::Style;
+
+/// Styles replacing the built-in colors
+#[derive(Clone, Debug, Default)]
+pub struct Palette {
+    types: HashMap<Category, Style>,
+    extensions: HashMap<String, Style>, // in lower case, without the dot
+}
+
+impl Palette {
+    /// Read the theme file at `path`
+    pub fn load(path: &Path) -> Result<Palette, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        Palette::parse(&text).map_err(|msg| ConfigError::Parse(path.to_path_buf(), msg))
+    }
+
+    pub fn parse(text: &str) -> Result<Palette, String> {
+        let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+        let mut palette = Palette::default();
+
+        for (section, styles) in &table {
+            let styles = styles.as_table().ok_or(format!("{} must be a table", section))?;
+            for (name, style) in styles {
+                let style = style
+                    .as_str()
+                    .and_then(Style::parse)
+                    .ok_or(format!("{}.{} must be a valid style", section, name))?;
+                match section.as_str() {
+                    "types" => {
+                        let &(_, category) = Category::NAMES
+                            .iter()
+                            .find(|(known, _)| known == name)
+                            .ok_or(format!("types: unknown type {}", name))?;
+                        palette.types.insert(category, style);
+                    }
+                    "extensions" => {
+                        let extension = name.trim_start_matches('.').to_ascii_lowercase();
+                        palette.extensions.insert(extension, style);
+                    }
+                    _ => return Err(format!("unknown section {}", section)),
+                }
+            }
+        }
+
+        Ok(palette)
+    }
+
+    /// The style the theme gives to a category of files, if any
+    pub(crate) fn style(&self, category: Category) -> Option<Style> {
+        self.types.get(&category).copied()
+    }
+
+    /// Whether the theme styles every category whose built-in color depends on the background
+    pub fn covers_background(&self) -> bool {
+        Category::ON_BACKGROUND.iter().all(|category| self.types.contains_key(category))
+    }
+
+    /// The style the theme gives to files with the extension of `name`, if any
+    pub(crate) fn extension(&self, name: &str) -> Option<Style> {
+        // a leading dot starts a hidden name, not an extension
+        match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => {
+                self.extensions.get(&extension.to_ascii_lowercase()).copied()
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Palette;
+    use crate::theme::Category;
+    use crate::Style;
+
+    #[test]
+    fn parses_types_and_extensions() {
+        let palette = Palette::parse("[types]\ndirectory = \"bold 33\"\n[extensions]\n\".MD\" = \"#ff0000\"\n");
+        let palette = palette.unwrap_or_default();
+        assert_eq!(palette.style(Category::Directory), Style::parse("bold 33"));
+        assert_eq!(palette.style(Category::Symlink), None);
+        assert_eq!(palette.extension("README.md"), Style::parse("#ff0000"));
+        assert_eq!(palette.extension(".md"), None);
+        assert_eq!(palette.extension("notes.txt"), None);
+        assert!(!palette.covers_background());
+    }
+
+    #[test]
+    fn covers_the_background_with_every_such_type() {
+        let palette = Palette::parse("[types]\nsymlink = \"red\"\naudio = \"red\"\n").unwrap_or_default();
+        assert!(!palette.covers_background());
+        let palette = Palette::parse("[types]\nsymlink = \"red\"\naudio = \"red\"\nsource = \"red\"\n");
+        assert!(palette.unwrap_or_default().covers_background());
+    }
+
+    #[test]
+    fn rejects_unknown_names_and_bad_styles() {
+        let error = |text: &str| Palette::parse(text).err();
+        assert_eq!(error("[types]\nfolder = \"red\"").as_deref(), Some("types: unknown type folder"));
+        assert_eq!(error("[types]\ndirectory = \"mauve\"").as_deref(), Some("types.directory must be a valid style"));
+        assert_eq!(error("[colors]\nrs = \"red\"").as_deref(), Some("unknown section colors"));
+        assert_eq!(error("types = 1").as_deref(), Some("types must be a table"));
+    }
+}
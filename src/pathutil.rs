@@ -0,0 +1,117 @@
+//! Lexical path manipulation, without touching the filesystem
+//!
+//! Symlinks are not resolved, so `a/../b` is taken to mean `b` even if `a` is
+//! a link elsewhere. This is what we want when showing what a link says.
+use std::path::{Component, Path, PathBuf};
+
+/// Remove `.` components and fold `name/..` pairs
+/// Leading `..` components of a relative path are kept, while `..` at the
+/// root of an absolute path is dropped. An empty result is `.`.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut parts: Vec<Component> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match parts.last() {
+                Some(Component::Normal(_)) => {
+                    parts.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => parts.push(component),
+            },
+            _ => parts.push(component),
+        }
+    }
+
+    if parts.is_empty() {
+        return PathBuf::from(".");
+    }
+    parts.iter().collect()
+}
+
+/// Express `path` relative to the directory `base`
+/// Both are normalized first and must be either both absolute or both relative;
+/// otherwise `path` is returned normalized but unchanged.
+pub fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let path = normalize(path);
+    let base = normalize(base);
+    if path.is_absolute() != base.is_absolute() {
+        return path;
+    }
+
+    let path_parts: Vec<Component> = path.components().filter(|c| *c != Component::CurDir).collect();
+    let base_parts: Vec<Component> = base.components().filter(|c| *c != Component::CurDir).collect();
+    let common = path_parts
+        .iter()
+        .zip(&base_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    // a relative base that climbs higher than the path cannot be walked back down
+    if base_parts[common..].contains(&Component::ParentDir) {
+        return path;
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in common..base_parts.len() {
+        relative.push("..");
+    }
+    for part in &path_parts[common..] {
+        relative.push(part);
+    }
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn norm(path: &str) -> String {
+        normalize(Path::new(path)).to_string_lossy().into_owned()
+    }
+
+    fn rel(path: &str, base: &str) -> String {
+        relative_to(Path::new(path), Path::new(base)).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn normalizes_relative_paths() {
+        assert_eq!(norm("../../x/./y"), "../../x/y");
+        assert_eq!(norm("a/b/../c"), "a/c");
+        assert_eq!(norm("a/../../b"), "../b");
+        assert_eq!(norm("./a/"), "a");
+        assert_eq!(norm("a/.."), ".");
+        assert_eq!(norm("."), ".");
+        assert_eq!(norm("../.."), "../..");
+    }
+
+    #[test]
+    fn normalizes_absolute_paths() {
+        assert_eq!(norm("/a/./b/../c"), "/a/c");
+        assert_eq!(norm("/../a"), "/a");
+        assert_eq!(norm("/a/.."), "/");
+        assert_eq!(norm("//a//b"), "/a/b");
+    }
+
+    #[test]
+    fn makes_paths_relative_to_a_base() {
+        assert_eq!(rel("/srv/data/x", "/srv/links"), "../data/x");
+        assert_eq!(rel("/srv/data/x", "/srv/data"), "x");
+        assert_eq!(rel("/srv/data", "/srv/data"), ".");
+        assert_eq!(rel("/srv", "/srv/data/deep"), "../..");
+        assert_eq!(rel("/a/b", "/"), "a/b");
+        assert_eq!(rel("/srv/./data/../x", "/srv/y/.."), "x");
+        assert_eq!(rel("a/b", "a/c"), "../b");
+        assert_eq!(rel("x", "."), "x");
+    }
+
+    #[test]
+    fn leaves_paths_that_cannot_be_made_relative() {
+        assert_eq!(rel("/a/b", "c"), "/a/b");
+        assert_eq!(rel("a/b", "/c"), "a/b");
+        assert_eq!(rel("a", "../b"), "a");
+    }
+}
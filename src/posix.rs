@@ -39,12 +39,15 @@ pub fn get_winsize() -> Option<WinSize> {
 }
 
 pub fn strcoll(a: &str, b: &str) -> std::cmp::Ordering {
-    let result = unsafe {
-        libc::strcoll(
-            a.as_ptr() as *const libc::c_char,
-            b.as_ptr() as *const libc::c_char,
-        )
-    };
+    // strcoll requires null terminated strings
+    let a = std::ffi::CString::new(a).unwrap_or_default();
+    let b = std::ffi::CString::new(b).unwrap_or_default();
+    strcoll_cstr(&a, &b)
+}
+
+#[cfg(not(feature = "no-c-locale"))]
+pub fn strcoll_cstr(a: &std::ffi::CStr, b: &std::ffi::CStr) -> std::cmp::Ordering {
+    let result = unsafe { libc::strcoll(a.as_ptr(), b.as_ptr()) };
 
     if result < 0 {
         std::cmp::Ordering::Less
@@ -55,6 +58,16 @@ pub fn strcoll(a: &str, b: &str) -> std::cmp::Ordering {
     }
 }
 
+/// Compare using the Unicode Collation Algorithm with its default (root) ordering
+#[cfg(feature = "no-c-locale")]
+pub fn strcoll_cstr(a: &std::ffi::CStr, b: &std::ffi::CStr) -> std::cmp::Ordering {
+    thread_local! {
+        // the collator caches its tables between comparisons
+        static COLLATOR: std::cell::RefCell<feruca::Collator> = std::cell::RefCell::new(feruca::Collator::default());
+    }
+    COLLATOR.with(|collator| collator.borrow_mut().collate(a.to_bytes(), b.to_bytes()))
+}
+
 #[derive(Debug)]
 pub enum LocaleError {
     NullByte,        // the provided input locale contains a null byte
@@ -79,7 +92,8 @@ pub enum Locale<'a> {
     Named(&'a str),
 }
 
-pub fn setlocale(locale: Locale) -> Result<&str, LocaleError> {
+#[cfg(not(feature = "no-c-locale"))]
+pub fn setlocale(locale: Locale<'_>) -> Result<&str, LocaleError> {
     let locale = match locale {
         Locale::UserPreferred => "",
         Locale::Named(locale) => locale,
@@ -100,3 +114,171 @@ pub fn setlocale(locale: Locale) -> Result<&str, LocaleError> {
         },
     }
 }
+
+/// With `no-c-locale` there is no locale to set, and names are collated with the UCA
+#[cfg(feature = "no-c-locale")]
+pub fn setlocale(_locale: Locale<'_>) -> Result<&str, LocaleError> {
+    Ok("C")
+}
+
+/// The effective group ID and supplementary group IDs of the process
+/// The list is read once and cached, since it cannot change while listing.
+pub fn process_groups() -> &'static [libc::gid_t] {
+    static GROUPS: std::sync::OnceLock<Vec<libc::gid_t>> = std::sync::OnceLock::new();
+    GROUPS.get_or_init(|| {
+        let mut groups = vec![unsafe { libc::getegid() }];
+        let count = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+        if count > 0 {
+            let mut supplementary: Vec<libc::gid_t> = vec![0; count as usize];
+            let count = unsafe { libc::getgroups(count, supplementary.as_mut_ptr()) };
+            supplementary.truncate(count.max(0) as usize);
+            groups.extend(supplementary);
+        }
+        groups
+    })
+}
+
+/// The magic number identifying the type of the filesystem holding `path`
+/// Symlinks are followed, so a dangling link has no filesystem type.
+pub fn filesystem_type(path: &std::path::Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    match unsafe { libc::statfs(path.as_ptr(), &mut stat) } {
+        0 => Some(stat.f_type as u64),
+        _ => None,
+    }
+}
+
+/// Ask the controlling terminal for its background color with the OSC 11 query
+/// Returns the raw reply, or None if stdout is not a terminal or no reply came in time.
+pub fn query_background_color(timeout: std::time::Duration) -> Option<Vec<u8>> {
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+
+    if unsafe { libc::isatty(libc::STDOUT_FILENO) } != 1 {
+        return None;
+    }
+    let mut tty = std::fs::OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+    let fd = tty.as_raw_fd();
+
+    // read the reply as it arrives, without echoing it
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+        return None;
+    }
+    let mut raw = original;
+    raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+        return None;
+    }
+
+    let mut reply = Vec::new();
+    if tty.write_all(b"\x1b]11;?\x1b\\").is_ok() {
+        let deadline = std::time::Instant::now() + timeout;
+        let mut buf = [0u8; 64];
+        loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            let mut poll_fd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+            if remaining.is_zero() || unsafe { libc::poll(&mut poll_fd, 1, remaining.as_millis() as i32) } <= 0 {
+                break;
+            }
+            match tty.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(count) => reply.extend_from_slice(&buf[..count]),
+            }
+            // the reply ends with BEL or ST
+            if reply.ends_with(b"\x07") || reply.ends_with(b"\x1b\\") {
+                break;
+            }
+        }
+    }
+
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &original) };
+    if reply.is_empty() {
+        None
+    } else {
+        Some(reply)
+    }
+}
+
+/// The controlling terminal, switched to raw input until dropped
+/// It is opened directly so that it can be used while stdout is a pipe.
+pub struct RawTerminal {
+    pub tty: std::fs::File,
+    original: libc::termios,
+}
+
+impl RawTerminal {
+    /// Open the controlling terminal, reading keys as they are typed without echoing them
+    pub fn open() -> Option<RawTerminal> {
+        use std::os::fd::AsRawFd;
+
+        let tty = std::fs::OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(tty.as_raw_fd(), &mut original) } != 0 {
+            return None;
+        }
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(tty.as_raw_fd(), libc::TCSANOW, &raw) } != 0 {
+            return None;
+        }
+        Some(RawTerminal { tty, original })
+    }
+
+    /// The size of the terminal
+    pub fn size(&self) -> Option<WinSize> {
+        use std::os::fd::AsRawFd;
+
+        let mut w = WinSizeInternal { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+        match unsafe { libc::ioctl(self.tty.as_raw_fd(), libc::TIOCGWINSZ, &mut w) } {
+            0 if w.ws_row > 0 && w.ws_col > 0 => Some(WinSize {
+                rows: w.ws_row as usize,
+                cols: w.ws_col as usize,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        use std::os::fd::AsRawFd;
+        unsafe { libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSANOW, &self.original) };
+    }
+}
+
+/// The name of this host, as gethostname(2) gives it
+pub fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+        return None;
+    }
+    let name = std::ffi::CStr::from_bytes_until_nul(&buf).ok()?;
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// The month names of the current locale's `LC_TIME`, abbreviated or in full
+#[cfg(not(feature = "no-c-locale"))]
+pub fn month_names(abbreviated: bool) -> Option<[String; 12]> {
+    let first = if abbreviated { libc::ABMON_1 } else { libc::MON_1 };
+    let mut names: [String; 12] = Default::default();
+    for (month, name) in names.iter_mut().enumerate() {
+        let text = unsafe { libc::nl_langinfo(first + month as libc::nl_item) };
+        if text.is_null() {
+            return None;
+        }
+        *name = unsafe { std::ffi::CStr::from_ptr(text) }.to_string_lossy().into_owned();
+    }
+    Some(names)
+}
+
+/// With `no-c-locale` there is no locale, and chrono's English names are used
+#[cfg(feature = "no-c-locale")]
+pub fn month_names(_abbreviated: bool) -> Option<[String; 12]> {
+    None
+}
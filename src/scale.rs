@@ -0,0 +1,127 @@
+//! Gradients on the size and time columns, for `--color-scale`
+//!
+//! As in eza, each value is colored by where it falls between the smallest and
+//! largest of the listing, so the largest and most recent files stand out the
+//! brightest. Sizes are placed on a logarithmic scale, since a few large files
+//! would otherwise leave every other one at the dim end.
+use colored::{ColoredString, Colorize};
+
+/// The columns `--color-scale` colors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorScale {
+    Age,  // the time columns
+    Size, // the size column
+    All,  // both
+}
+
+impl ColorScale {
+    pub(crate) fn ages(self) -> bool {
+        self != ColorScale::Size
+    }
+
+    pub(crate) fn sizes(self) -> bool {
+        self != ColorScale::Age
+    }
+}
+
+/// The share of a color's brightness given to the smallest value
+const DIMMEST: f64 = 0.35;
+
+/// The colors of the values of one column
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Gradient {
+    min: f64,
+    max: f64,
+    color: (u8, u8, u8), // given to the largest value
+    logarithmic: bool,
+}
+
+impl Gradient {
+    /// The gradient across `values`, None if there are none
+    fn new(values: impl Iterator<Item = f64>, color: (u8, u8, u8), logarithmic: bool) -> Option<Gradient> {
+        let values = values.map(|value| placed(value, logarithmic));
+        let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| (min.min(v), max.max(v)));
+        (min <= max).then_some(Gradient { min, max, color, logarithmic })
+    }
+
+    /// The gradient for sizes in bytes
+    pub(crate) fn sizes(sizes: impl Iterator<Item = f64>) -> Option<Gradient> {
+        Gradient::new(sizes, (0x5f, 0xff, 0x5f), true)
+    }
+
+    /// The gradient for timestamps in seconds since the epoch
+    pub(crate) fn ages(timestamps: impl Iterator<Item = f64>) -> Option<Gradient> {
+        Gradient::new(timestamps, (0x5f, 0xaf, 0xff), false)
+    }
+
+    /// Where `value` falls between the smallest and largest, from 0 to 1
+    fn fraction(&self, value: f64) -> f64 {
+        let value = placed(value, self.logarithmic);
+        if self.max > self.min {
+            ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+        } else {
+            // every value is the same, and as large as any
+            1.0
+        }
+    }
+
+    /// The color of `value`
+    fn rgb(&self, value: f64) -> (u8, u8, u8) {
+        let brightness = DIMMEST + (1.0 - DIMMEST) * self.fraction(value);
+        let (r, g, b) = self.color;
+        let scale = |channel: u8| (channel as f64 * brightness).round() as u8;
+        (scale(r), scale(g), scale(b))
+    }
+
+    /// Color `text`, which shows `value`
+    pub(crate) fn paint(&self, text: &str, value: f64) -> ColoredString {
+        let (r, g, b) = self.rgb(value);
+        text.truecolor(r, g, b)
+    }
+}
+
+/// Where `value` is placed on a gradient
+fn placed(value: f64, logarithmic: bool) -> f64 {
+    if logarithmic {
+        value.max(0.0).ln_1p()
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorScale, Gradient};
+
+    #[test]
+    fn spans_the_values_given() {
+        let gradient = Gradient::ages([4.0, 2.0, 3.0].into_iter());
+        assert_eq!(gradient.map(|g| (g.min, g.max)), Some((2.0, 4.0)));
+        assert_eq!(Gradient::ages(std::iter::empty()), None);
+    }
+
+    #[test]
+    fn brightens_toward_the_largest_value() {
+        let gradient = Gradient { min: 0.0, max: 10.0, color: (200, 100, 0), logarithmic: false };
+        assert_eq!(gradient.rgb(10.0), (200, 100, 0));
+        assert_eq!(gradient.rgb(0.0), (70, 35, 0));
+        assert_eq!(gradient.rgb(5.0), (135, 68, 0));
+        // a single value is shown at full brightness
+        let flat = Gradient { min: 3.0, max: 3.0, color: (200, 100, 0), logarithmic: false };
+        assert_eq!(flat.rgb(3.0), (200, 100, 0));
+    }
+
+    #[test]
+    fn scales_sizes_logarithmically() {
+        let gradient = Gradient::sizes([0.0, 1e3, 1e6].into_iter());
+        // a thousandth of the largest size is halfway there
+        assert_eq!(gradient.map(|g| (g.fraction(1e3) * 100.0).round()), Some(50.0));
+    }
+
+    #[test]
+    fn chooses_columns() {
+        assert!(ColorScale::All.ages() && ColorScale::All.sizes());
+        assert!(ColorScale::Age.ages() && !ColorScale::Age.sizes());
+        assert!(!ColorScale::Size.ages() && ColorScale::Size.sizes());
+    }
+}
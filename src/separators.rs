@@ -0,0 +1,113 @@
+//! Separators between groups of entries sorted by time or size
+//!
+//! Entries sorted by time are grouped by age (today, yesterday, this week, ...)
+//! and entries sorted by size by their order of magnitude. Since the sort puts
+//! each group in one run, a separator is only needed where the group changes.
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local};
+
+use crate::messages::{self, Message};
+use crate::{size, EntryData, SizeFormat, SortKey};
+
+/// How groups are separated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeparatorStyle {
+    Blank,   // an empty line between groups
+    Labeled, // a line naming each group
+}
+
+/// The lines to print before the entry at each index, in order, for entries already sorted by `key`
+/// Sort keys that do not group entries get no separators.
+pub(crate) fn separators(entries: &[EntryData], key: SortKey, style: SeparatorStyle) -> Vec<(usize, String)> {
+    let label: Box<dyn Fn(&EntryData) -> String> = match key {
+        SortKey::Time(field) => {
+            let now = Local::now();
+            Box::new(move |e| age_label(field.get(&e.metadata).ok(), now))
+        }
+        SortKey::Size => Box::new(|e| size_label(e.metadata.len())),
+        _ => return Vec::new(),
+    };
+
+    let mut lines = Vec::new();
+    let mut previous: Option<String> = None;
+    for (idx, entry) in entries.iter().enumerate() {
+        let current = label(entry);
+        if previous.as_ref() == Some(&current) {
+            continue;
+        }
+        match (style, previous.is_some()) {
+            (SeparatorStyle::Blank, true) => lines.push((idx, String::new())),
+            (SeparatorStyle::Blank, false) => {}
+            (SeparatorStyle::Labeled, true) => {
+                // an empty line of its own, so that it ends as every line does, e.g. with --zero
+                lines.push((idx, String::new()));
+                lines.push((idx, format!("-- {} --", current)));
+            }
+            (SeparatorStyle::Labeled, false) => lines.push((idx, format!("-- {} --", current))),
+        }
+        previous = Some(current);
+    }
+    lines
+}
+
+/// The age group of a timestamp, by calendar day in the local timezone
+fn age_label(time: Option<SystemTime>, now: DateTime<Local>) -> String {
+    let time = match time {
+        Some(time) => DateTime::<Local>::from(time),
+        None => return messages::text(Message::AgeUnknown).to_string(),
+    };
+    let days = (now.date_naive() - time.date_naive()).num_days();
+    let label = match days {
+        _ if time > now => Message::AgeFuture,
+        0 => Message::AgeToday,
+        1 => Message::AgeYesterday,
+        2..=6 => Message::AgeThisWeek,
+        7..=30 => Message::AgeThisMonth,
+        31..=365 => Message::AgeThisYear,
+        _ => Message::AgeOlder,
+    };
+    messages::text(label).to_string()
+}
+
+/// The order of magnitude of a size, e.g. `100k to 1.0M`
+fn size_label(bytes: u64) -> String {
+    let exp = bytes.checked_ilog10().unwrap_or(0);
+    if exp == 0 {
+        return messages::text(Message::SizeUnderTen).to_string();
+    }
+    let low = size::format_size(10u64.pow(exp), SizeFormat::Si);
+    match 10u64.checked_pow(exp + 1) {
+        Some(high) => messages::format_all(Message::SizeRange, &[&low, &size::format_size(high, SizeFormat::Si)]),
+        None => messages::format(Message::SizeAndOver, &low),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_sizes_by_order_of_magnitude() {
+        assert_eq!(size_label(0), "under 10 bytes");
+        assert_eq!(size_label(9), "under 10 bytes");
+        assert_eq!(size_label(10), "10 to 100");
+        assert_eq!(size_label(999), "100 to 1.0k");
+        assert_eq!(size_label(123_456), "100k to 1.0M");
+        assert_eq!(size_label(u64::MAX), "10E and over");
+    }
+
+    #[test]
+    fn labels_times_by_calendar_day() {
+        let now = Local::now();
+        let days_ago = |days: i64| Some(SystemTime::from(now - chrono::Duration::days(days)));
+        assert_eq!(age_label(days_ago(0), now), "Today");
+        assert_eq!(age_label(days_ago(1), now), "Yesterday");
+        assert_eq!(age_label(days_ago(3), now), "This week");
+        assert_eq!(age_label(days_ago(20), now), "This month");
+        assert_eq!(age_label(days_ago(200), now), "This year");
+        assert_eq!(age_label(days_ago(2000), now), "Older");
+        assert_eq!(age_label(days_ago(-1), now), "Future");
+        assert_eq!(age_label(None, now), "Unknown");
+    }
+}
@@ -0,0 +1,63 @@
+//! Shell integration snippets (`listare --print-shell-init SHELL`)
+//!
+//! The snippet defines aliases, a function that changes directory and lists
+//! it, and loads completions. Flags are looked up in the command line
+//! definition, and completions are generated from it, so the snippet always
+//! matches the flags of the running binary.
+use std::io::{self, Write};
+
+use clap::Command;
+use clap_complete::Shell as CompletionShell;
+
+use crate::{ListareError, Output};
+
+/// The shells snippets can be generated for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    fn completion_shell(self) -> CompletionShell {
+        match self {
+            Shell::Bash => CompletionShell::Bash,
+            Shell::Zsh => CompletionShell::Zsh,
+            Shell::Fish => CompletionShell::Fish,
+        }
+    }
+}
+
+/// The short form of a flag, e.g. `-l` for the argument with id `long`
+fn short_flag(command: &Command, id: &str) -> Result<String, ListareError> {
+    command
+        .get_arguments()
+        .find(|arg| arg.get_id() == id)
+        .and_then(|arg| arg.get_short())
+        .map(|short| format!("-{}", short))
+        .ok_or_else(|| ListareError::Generic(format!("listare: no short flag for '{}'", id)))
+}
+
+/// Print the snippet for `shell`, to be loaded with e.g. `eval "$(listare --print-shell-init zsh)"`
+pub fn print_integration(shell: Shell, mut command: Command) -> Result<(), ListareError> {
+    let name = command.get_name().to_string();
+    let long = short_flag(&command, "long")?;
+    let all = short_flag(&command, "all")?;
+
+    // colors are left to listare's own detection, so the aliases work in pipes too
+    let stdout = &mut io::stdout();
+    let out = &mut Output::text(stdout);
+    outln!(out, "alias ls='{name}'")?;
+    outln!(out, "alias ll='{name} {long}'")?;
+    outln!(out, "alias la='{name} {long} {all}'")?;
+    match shell {
+        Shell::Bash | Shell::Zsh => outln!(out, "lcd() {{ builtin cd \"$@\" && {name}; }}")?,
+        Shell::Fish => outln!(out, "function lcd; builtin cd $argv; and {name}; end")?,
+    }
+
+    // the completions are written in one go, so failures are found by flushing
+    let mut script = Vec::new();
+    clap_complete::generate(shell.completion_shell(), &mut command, name, &mut script);
+    out.write_all(&script).and_then(|()| out.flush()).map_err(ListareError::Write)
+}
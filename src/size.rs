@@ -0,0 +1,153 @@
+//! Formatting of file sizes and block counts
+//!
+//! Sizes are scaled according to a [`SizeFormat`], which is either one of the
+//! human readable formats or a fixed unit given as in `--block-size=SIZE`.
+//! The same parsing is used for the command line and for the `LS_BLOCK_SIZE`
+//! and `BLOCK_SIZE` environment variables.
+use std::fmt;
+
+/// How sizes are printed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizeFormat {
+    /// Powers of 1024 (`1.5K`, `12M`)
+    Human,
+    /// Powers of 1000 (`1.6k`, `13M`)
+    Si,
+    /// A number of units of `size` bytes, rounded up and followed by `suffix`
+    Units { size: u64, suffix: &'static str },
+}
+
+const BINARY_SUFFIXES: [&str; 8] = ["K", "M", "G", "T", "P", "E", "Z", "Y"];
+const SI_SUFFIXES: [&str; 8] = ["k", "M", "G", "T", "P", "E", "Z", "Y"];
+const DECIMAL_UNIT_SUFFIXES: [&str; 8] = ["kB", "MB", "GB", "TB", "PB", "EB", "ZB", "YB"];
+const IEC_UNIT_SUFFIXES: [&str; 8] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB", "ZiB", "YiB"];
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SizeFormatError(String);
+
+impl fmt::Display for SizeFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid block size: {}", self.0)
+    }
+}
+
+impl std::error::Error for SizeFormatError {}
+
+impl SizeFormat {
+    /// Exact number of bytes
+    pub const BYTES: SizeFormat = SizeFormat::Units { size: 1, suffix: "" };
+
+    /// Parse a block size such as `1024`, `K`, `1M`, `MB`, `KiB`, `human-readable` or `si`
+    ///
+    /// As in GNU ls, a unit given without a number is printed after each size,
+    /// while a size with a number (`1K`) prints bare numbers.
+    pub fn parse(spec: &str) -> Result<SizeFormat, SizeFormatError> {
+        let error = || SizeFormatError(spec.to_string());
+
+        match spec {
+            "human-readable" => return Ok(SizeFormat::Human),
+            "si" => return Ok(SizeFormat::Si),
+            _ => {}
+        }
+
+        // a leading quote asks for thousands separators, which are not supported
+        let spec_body = spec.strip_prefix('\'').unwrap_or(spec);
+        let digits = spec_body.len() - spec_body.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let (number, unit) = spec_body.split_at(digits);
+
+        let (multiplier, suffix) = if unit.is_empty() {
+            (1, "")
+        } else {
+            let mut chars = unit.chars();
+            let letter = chars.next().ok_or_else(error)?.to_ascii_uppercase();
+            let exp = BINARY_SUFFIXES
+                .iter()
+                .position(|s| s.starts_with(letter))
+                .ok_or_else(error)?;
+            let (base, suffix) = match chars.as_str() {
+                "" => (1024u64, BINARY_SUFFIXES[exp]),
+                "iB" => (1024u64, IEC_UNIT_SUFFIXES[exp]),
+                "B" => (1000u64, DECIMAL_UNIT_SUFFIXES[exp]),
+                _ => return Err(error()),
+            };
+            // the largest units do not fit in 64 bits
+            (base.checked_pow(exp as u32 + 1).ok_or_else(error)?, suffix)
+        };
+
+        if number.is_empty() {
+            if unit.is_empty() {
+                return Err(error());
+            }
+            return Ok(SizeFormat::Units { size: multiplier, suffix });
+        }
+
+        let size = number
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(multiplier))
+            .filter(|&n| n > 0)
+            .ok_or_else(error)?;
+        Ok(SizeFormat::Units { size, suffix: "" })
+    }
+
+    /// The formats for file sizes and block counts when none is given on the command line
+    ///
+    /// `LS_BLOCK_SIZE` and then `BLOCK_SIZE` apply to both. Otherwise file sizes
+    /// are in bytes and blocks are counted in units of 1024 bytes, or 512 bytes
+    /// if `POSIXLY_CORRECT` is set. Invalid values are ignored, as in GNU ls.
+    pub fn from_env() -> (SizeFormat, SizeFormat) {
+        let from_var = |name| std::env::var(name).ok().and_then(|v| SizeFormat::parse(&v).ok());
+
+        match from_var("LS_BLOCK_SIZE").or_else(|| from_var("BLOCK_SIZE")) {
+            Some(format) => (format, format),
+            None => {
+                let block = if std::env::var_os("POSIXLY_CORRECT").is_some() { 512 } else { 1024 };
+                (SizeFormat::BYTES, SizeFormat::Units { size: block, suffix: "" })
+            }
+        }
+    }
+}
+
+/// Format a size in bytes for display
+pub fn format_size(bytes: u64, format: SizeFormat) -> String {
+    match format {
+        SizeFormat::Human => human(bytes, 1024, &BINARY_SUFFIXES),
+        SizeFormat::Si => human(bytes, 1000, &SI_SUFFIXES),
+        SizeFormat::Units { size, suffix } => format!("{}{}", bytes.div_ceil(size), suffix),
+    }
+}
+
+/// Format a number of allocated 512 byte blocks for display
+pub fn format_blocks(blocks: u64, format: SizeFormat) -> String {
+    format_size(blocks.saturating_mul(512), format)
+}
+
+/// Scale `bytes` down to the largest unit it fills, rounding up like GNU ls does
+/// Values below 10 keep one decimal place.
+fn human(bytes: u64, base: u64, suffixes: &[&str]) -> String {
+    if bytes < base {
+        return bytes.to_string();
+    }
+
+    let base = base as f64;
+    let mut value = bytes as f64;
+    let mut exp = 0;
+    while value >= base && exp < suffixes.len() {
+        value /= base;
+        exp += 1;
+    }
+
+    if value < 10.0 {
+        let tenths = (value * 10.0).ceil();
+        if tenths < 100.0 {
+            return format!("{:.1}{}", tenths / 10.0, suffixes[exp - 1]);
+        }
+    }
+
+    let whole = value.ceil();
+    if whole >= base && exp < suffixes.len() {
+        // rounding up reached the next unit
+        return format!("1.0{}", suffixes[exp]);
+    }
+    format!("{}{}", whole, suffixes[exp - 1])
+}
@@ -0,0 +1,195 @@
+//! Ordering of entries
+//!
+//! Entries are ordered by the primary [`SortKey`] first. Entries the key
+//! considers equal are ordered by the locale's collation of their NFC-normalized
+//! names, or by the bytes of those when byte order is asked for, then by the raw
+//! bytes of their names, and finally by the raw bytes of their paths. Two
+//! distinct entries therefore never compare equal and the output is the same on
+//! every run, regardless of the order the filesystem returned the entries in.
+//! The exception is [`SortKey::Random`], which shuffles the entries and is only
+//! reproducible for a given seed.
+use std::{cmp::Ordering, ffi::CString, os::unix::{ffi::OsStrExt, fs::MetadataExt}, time::SystemTime};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{posix, EntryData, TimeField};
+
+/// The primary key entries are ordered by
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Width,
+    /// Newest first
+    Time(TimeField),
+    /// Largest first
+    Size,
+    Random { seed: u64 },
+    /// Lowest inode number first, close to the order of the files on disk
+    Inode,
+    /// The order the directory gives them in, for listing entries as they are read
+    None,
+}
+
+struct Keyed {
+    nfc: Option<String>,        // the NFC form of the name, if it differs from the name
+    collation: Option<CString>, // only built when names are collated
+    time: Option<SystemTime>,   // only looked up when sorting by time
+    width: usize,               // only measured when sorting by width
+    entry: EntryData,
+}
+
+impl Keyed {
+    fn new(entry: EntryData, key: SortKey, byte_order: bool, width: &Width) -> Self {
+        let time = match key {
+            SortKey::Time(field) => field.get(&entry.metadata).ok(),
+            _ => None,
+        };
+        let width = if key == SortKey::Width { width(&entry) } else { 0 };
+        // composed and decomposed forms of the same name (e.g. files created on
+        // macOS and Linux) compare equal, so that they sort next to each other
+        let nfc = (!unicode_normalization::is_nfc(&entry.name)).then(|| entry.name.nfc().collect());
+        let mut keyed = Keyed { nfc, collation: None, time, width, entry };
+        if !byte_order {
+            keyed.collation = Some(CString::new(keyed.nfc_name()).unwrap_or_default());
+        }
+        keyed
+    }
+
+    fn nfc_name(&self) -> &str {
+        self.nfc.as_deref().unwrap_or(&self.entry.name)
+    }
+}
+
+/// Below this many entries the cost of spreading work over threads outweighs the gain
+#[cfg(feature = "parallel")]
+const PARALLEL_THRESHOLD: usize = 10_000;
+
+fn compare(a: &Keyed, b: &Keyed, key: SortKey, byte_order: bool) -> Ordering {
+    let primary = match key {
+        SortKey::Name => Ordering::Equal,
+        SortKey::Width => a.width.cmp(&b.width),
+        // entries without the timestamp sort as the oldest
+        SortKey::Time(_) => b.time.cmp(&a.time),
+        SortKey::Size => b.entry.metadata.len().cmp(&a.entry.metadata.len()),
+        SortKey::Random { .. } => Ordering::Equal,
+        SortKey::Inode => a.entry.metadata.ino().cmp(&b.entry.metadata.ino()),
+        SortKey::None => Ordering::Equal,
+    };
+    primary
+        .then_with(|| match (&a.collation, &b.collation) {
+            (Some(a), Some(b)) if !byte_order => posix::strcoll_cstr(a, b),
+            _ => a.nfc_name().as_bytes().cmp(b.nfc_name().as_bytes()),
+        })
+        .then_with(|| a.entry.name.as_bytes().cmp(b.entry.name.as_bytes()))
+        .then_with(|| {
+            let a = a.entry.path.as_os_str().as_bytes();
+            let b = b.entry.path.as_os_str().as_bytes();
+            a.cmp(b)
+        })
+}
+
+/// A small seedable generator (splitmix64), good enough for shuffling
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A value in `0..bound`
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+/// Fisher-Yates shuffle, the same seed always giving the same order for the same input
+fn shuffle(entries: &mut [EntryData], seed: u64) {
+    let mut rng = SplitMix64(seed);
+    for i in (1..entries.len()).rev() {
+        entries.swap(i, rng.below(i + 1));
+    }
+}
+
+/// The columns an entry takes where it is listed, for sorting by width
+pub(crate) type Width<'a> = dyn Fn(&EntryData) -> usize + Sync + 'a;
+
+/// Sort entries by the given key, breaking ties as described in the module documentation
+/// With `byte_order`, names are compared by the bytes of their NFC form, as in the C locale.
+pub fn sort_entries(entries: Vec<EntryData>, key: SortKey, byte_order: bool, width: &Width) -> Vec<EntryData> {
+    if key == SortKey::None {
+        return entries;
+    }
+    if let SortKey::Random { seed } = key {
+        // start from a deterministic order so that a seed reproduces the same shuffle
+        let mut entries = sort_entries(entries, SortKey::Name, byte_order, width);
+        shuffle(&mut entries, seed);
+        return entries;
+    }
+
+    #[cfg(feature = "parallel")]
+    if entries.len() >= PARALLEL_THRESHOLD {
+        let mut keyed: Vec<Keyed> = entries.into_par_iter().map(|e| Keyed::new(e, key, byte_order, width)).collect();
+        keyed.par_sort_by(|a, b| compare(a, b, key, byte_order));
+        return keyed.into_par_iter().map(|k| k.entry).collect();
+    }
+
+    let mut keyed: Vec<Keyed> = entries.into_iter().map(|e| Keyed::new(e, key, byte_order, width)).collect();
+    keyed.sort_by(|a, b| compare(a, b, key, byte_order));
+    keyed.into_iter().map(|k| k.entry).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort_entries, Keyed, SortKey};
+    use crate::EntryData;
+
+    #[test]
+    fn normalizes_names_before_comparing() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let keyed = |name: &str, byte_order: bool| -> std::io::Result<Keyed> {
+            std::fs::write(dir.path().join(name), "")?;
+            Ok(Keyed::new(EntryData::from_path(dir.path().join(name))?, SortKey::Name, byte_order, &|_| 0))
+        };
+        assert_eq!(keyed("e\u{301}", true)?.nfc_name(), "\u{e9}");
+        assert!(keyed("\u{e9}", true)?.nfc.is_none());
+        // the collation key is only built when names are collated
+        assert!(keyed("e", true)?.collation.is_none());
+        assert_eq!(keyed("e\u{301}", false)?.collation, keyed("\u{e9}", false)?.collation);
+        Ok(())
+    }
+
+    #[test]
+    fn sorts_composed_and_decomposed_names_together() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let names = ["e\u{301}", "z", "\u{e9}", "e", "f"];
+        for name in names {
+            std::fs::write(dir.path().join(name), "")?;
+        }
+        let sorted = |names: &[&str], byte_order: bool| -> std::io::Result<Vec<String>> {
+            let entries = names
+                .iter()
+                .map(|name| EntryData::from_path(dir.path().join(name)))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(sort_entries(entries, SortKey::Name, byte_order, &|_| 0).into_iter().map(|e| e.name).collect())
+        };
+
+        for byte_order in [false, true] {
+            let sorted_names = sorted(&names, byte_order)?;
+            // the two forms are next to each other, the decomposed one first by its bytes
+            let composed = sorted_names.iter().position(|name| name == "\u{e9}");
+            let decomposed = sorted_names.iter().position(|name| name == "e\u{301}");
+            assert_eq!(decomposed.map(|i| i + 1), composed, "{:?}", sorted_names);
+            // whatever order they were read in
+            let reversed: Vec<&str> = names.iter().rev().copied().collect();
+            assert_eq!(sorted(&reversed, byte_order)?, sorted_names);
+        }
+        assert_eq!(sorted(&names, true)?, ["e", "f", "z", "e\u{301}", "\u{e9}"]);
+        Ok(())
+    }
+}
@@ -7,29 +7,34 @@ pub trait CharacterLength {
     fn characters_long(&self) -> usize;
 }
 
+/// Printed between the columns of a line
+const COLUMN_SEPARATOR: &str = "  ";
+
 #[derive(Debug)]
 struct ColumnConfiguration {
     num_columns: usize,     // number of columns
-    col_widths: Vec<usize>, // the lengths of each column
-    line_len: usize,        // the total length of the line
+    col_widths: Vec<usize>, // the widths of the cells of each column, without separators
+    line_len: usize,        // the total length of the line, separators included
     valid: bool,            // whether the configuration is valid
 }
 
 /// Create a vector of column configurations of increasing number of columns
-/// Each configuration is initialized with the minimum column width
+/// Each configuration is initialized with the minimum cell width
 fn init_column_configs(
     max_line_length: usize,
     num_items: usize,
-    min_col_width: usize,
+    min_cell_width: usize,
 ) -> Vec<ColumnConfiguration> {
     let mut configs: Vec<ColumnConfiguration> = Vec::new();
-    let max_columns = max(1, max_line_length / min_col_width);
+    // n columns take n cells and n - 1 separators
+    let separator = COLUMN_SEPARATOR.len();
+    let max_columns = max(1, max_line_length.saturating_add(separator) / (min_cell_width + separator));
     let max_columns = min(max_columns, num_items);
     for num_columns in 1..=max_columns {
         let config = ColumnConfiguration {
-            num_columns: num_columns,
-            col_widths: vec![min_col_width; num_columns],
-            line_len: num_columns * min_col_width,
+            num_columns,
+            col_widths: vec![min_cell_width; num_columns],
+            line_len: num_columns * min_cell_width + (num_columns - 1) * separator,
             valid: true,
         };
         configs.push(config);
@@ -61,6 +66,7 @@ pub struct Tabulator<'a, T> {
     data: &'a [T],
     max_line_length: usize,
     orientation: TabulateOrientation,
+    tab_size: usize, // 0 to pad with spaces only
 }
 
 impl<'a, T> Tabulator<'a, T> {
@@ -73,9 +79,8 @@ impl<'a, T> Tabulator<'a, T> {
         }
 
         // Create a column configuration for each possible number of columns
-        const MIN_COLUMN_WIDTH: usize = 3; // 1 char for name 2 separating white space
-        let mut configs =
-            init_column_configs(self.max_line_length, self.data.len(), MIN_COLUMN_WIDTH);
+        const MIN_CELL_WIDTH: usize = 1;
+        let mut configs = init_column_configs(self.max_line_length, self.data.len(), MIN_CELL_WIDTH);
 
         // iterate over each file and determine the column widths for each configuration
         for (file_idx, entry) in self.data.iter().enumerate() {
@@ -88,44 +93,54 @@ impl<'a, T> Tabulator<'a, T> {
                 let col_idx = match self.orientation {
                     TabulateOrientation::Rows => file_idx % config.num_columns,
                     TabulateOrientation::Columns => {
-                        file_idx
-                            / ((self.data.len() + config.num_columns - 1) / (config.num_columns))
+                        file_idx / self.data.len().div_ceil(config.num_columns)
                     }
                 };
-                // for horizontal use this instead:
-                // let col_idx = file_idx % config.num_columns;
-                // let col_idx = file_idx / ((self.data.len() + config.num_columns - 1) / (config.num_columns));
-                let real_len = entry.characters_long()
-                    + (if col_idx == config.num_columns - 1 {
-                        0
-                    } else {
-                        2
-                    });
+                let cell_width = entry.characters_long();
 
                 // update the config if the column width is too small
-                if config.col_widths[col_idx] < real_len {
-                    config.line_len += real_len - config.col_widths[col_idx];
-                    config.col_widths[col_idx] = real_len;
+                if config.col_widths[col_idx] < cell_width {
+                    config.line_len += cell_width - config.col_widths[col_idx];
+                    config.col_widths[col_idx] = cell_width;
                     // invalidate the configuration if the line length is too long
-                    config.valid = config.line_len < self.max_line_length;
+                    config.valid = config.line_len <= self.max_line_length;
                 }
             }
         }
 
         // find the configuration with the largest number of columns that fits within the line length
         let position = configs.iter().rposition(|config| config.valid).unwrap_or(0);
-        // TODO may panic when data empty (max columns will be 0, therefore configs will be empty)
-        let config = configs.remove(position);
-        Ok(config)
+        configs.into_iter().nth(position).ok_or(ConfigError::EmptyData)
     }
 
     pub fn new(data: &'a [T], max_line_length: usize, orientation: TabulateOrientation) -> Self {
         Tabulator {
             data,
             max_line_length,
-            orientation: orientation,
+            orientation,
+            tab_size: 0,
+        }
+    }
+
+    /// Pad between columns with tabs to stops every `tab_size` columns where they are shorter than spaces
+    pub fn tab_size(mut self, tab_size: usize) -> Self {
+        self.tab_size = tab_size;
+        self
+    }
+}
+
+/// Fill a line from column `from` to column `to`, as ls does with tabs where they save space
+fn fill(f: &mut std::fmt::Formatter, mut from: usize, to: usize, tab_size: usize) -> std::fmt::Result {
+    while from < to {
+        if tab_size > 0 && to / tab_size > (from + 1) / tab_size {
+            write!(f, "\t")?;
+            from += tab_size - from % tab_size;
+        } else {
+            write!(f, " ")?;
+            from += 1;
         }
     }
+    Ok(())
 }
 
 // implement Display for Tabulator
@@ -143,22 +158,24 @@ where
                 }
             },
         };
-        let rows = (self.data.len() / config.num_columns)
-            + if self.data.len() % config.num_columns != 0 {
-                1
-            } else {
-                0
-            };
+        let rows = self.data.len().div_ceil(config.num_columns);
         for row in 0..rows {
-            for col in 0..config.num_columns {
-                let idx = match self.orientation {
+            let indices: Vec<usize> = (0..config.num_columns)
+                .map(|col| match self.orientation {
                     TabulateOrientation::Rows => row * config.num_columns + col,
                     TabulateOrientation::Columns => row + (col * rows),
-                };
-                //let idx = row + (col * rows);
-                if idx < self.data.len() {
-                    let entry = &self.data[idx];
-                    write!(f, "{:width$}", entry, width = config.col_widths[col])?;
+                })
+                .take_while(|&idx| idx < self.data.len())
+                .collect();
+            let mut pos = 0;
+            for (col, &idx) in indices.iter().enumerate() {
+                let entry = &self.data[idx];
+                write!(f, "{}", entry)?;
+                // the last entry of a line is not padded, leaving no trailing spaces
+                if col < indices.len() - 1 {
+                    let next = pos + config.col_widths[col] + COLUMN_SEPARATOR.len();
+                    fill(f, pos + entry.characters_long(), next, self.tab_size)?;
+                    pos = next;
                 }
             }
             // if not the last row, print a newline
@@ -169,3 +186,146 @@ where
         Ok(())
     }
 }
+
+/// Lays out data in lines of items separated by commas, as `ls -m`
+pub struct CommaSeparated<'a, T> {
+    data: &'a [T],
+    max_line_length: usize,
+}
+
+impl<'a, T> CommaSeparated<'a, T> {
+    pub fn new(data: &'a [T], max_line_length: usize) -> Self {
+        CommaSeparated { data, max_line_length }
+    }
+}
+
+impl<T> std::fmt::Display for CommaSeparated<'_, T>
+where
+    T: std::fmt::Display,
+    T: CharacterLength,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut pos: usize = 0;
+        for (idx, item) in self.data.iter().enumerate() {
+            let len = item.characters_long();
+            if idx > 0 {
+                // as in ls, room is left for the comma and space that follow the item
+                if pos.saturating_add(len + 2) < self.max_line_length {
+                    write!(f, ", ")?;
+                    pos += 2;
+                } else {
+                    writeln!(f, ",")?;
+                    pos = 0;
+                }
+            }
+            write!(f, "{}", item)?;
+            pos += len;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CharacterLength, CommaSeparated, TabulateOrientation, Tabulator};
+    use std::fmt;
+
+    struct Name(&'static str);
+
+    impl fmt::Display for Name {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.pad(self.0)
+        }
+    }
+
+    impl CharacterLength for Name {
+        fn characters_long(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    fn layout(names: &[&'static str], width: usize, orientation: TabulateOrientation) -> String {
+        let names: Vec<Name> = names.iter().map(|&n| Name(n)).collect();
+        Tabulator::new(&names, width, orientation).to_string()
+    }
+
+    const NAMES: [&str; 5] = ["alpha", "beta", "gamma", "delta", "epsilon"];
+
+    #[test]
+    fn fills_columns_then_rows() {
+        assert_eq!(layout(&NAMES, 24, TabulateOrientation::Columns), "alpha  gamma  epsilon\nbeta   delta");
+        assert_eq!(layout(&NAMES, 24, TabulateOrientation::Rows), "alpha  beta     gamma\ndelta  epsilon");
+    }
+
+    #[test]
+    fn uses_lines_of_exactly_the_width() {
+        let line = NAMES.join("  ");
+        assert_eq!(layout(&NAMES, line.len(), TabulateOrientation::Rows), line);
+        assert_eq!(layout(&NAMES, line.len() - 1, TabulateOrientation::Rows), "alpha    beta  gamma  delta\nepsilon");
+    }
+
+    #[test]
+    fn leaves_no_trailing_spaces() {
+        let text = layout(&["a", "bb", "ccc", "dddd"], 10, TabulateOrientation::Columns);
+        assert_eq!(text, "a   ccc\nbb  dddd");
+    }
+
+    #[test]
+    fn degrades_to_one_column_when_names_are_wider_than_the_line() {
+        assert_eq!(layout(&NAMES, 4, TabulateOrientation::Columns), NAMES.join("\n"));
+        assert_eq!(layout(&NAMES, 0, TabulateOrientation::Rows), NAMES.join("\n"));
+        assert_eq!(layout(&NAMES, 1, TabulateOrientation::Columns), NAMES.join("\n"));
+    }
+
+    #[test]
+    fn unlimited_width_is_one_line() {
+        assert_eq!(layout(&NAMES, usize::MAX, TabulateOrientation::Columns), NAMES.join("  "));
+    }
+
+    #[test]
+    fn empty_data_is_empty() {
+        assert_eq!(layout(&[], 80, TabulateOrientation::Columns), "");
+    }
+
+    #[test]
+    fn pads_with_tabs_where_they_are_shorter() {
+        let names: Vec<Name> = ["a", "bbbbbbbbbbbb", "ccccccccccccccc", "d"].iter().map(|&n| Name(n)).collect();
+        let layout = |tab_size| Tabulator::new(&names, 30, TabulateOrientation::Rows).tab_size(tab_size).to_string();
+        // the second column starts at 17, one past a tab stop, so a space ends the padding
+        assert_eq!(layout(8), "a\t\t bbbbbbbbbbbb\nccccccccccccccc  d");
+        assert_eq!(layout(4), "a\t\t\t\t bbbbbbbbbbbb\nccccccccccccccc  d");
+        assert_eq!(layout(0), "a                bbbbbbbbbbbb\nccccccccccccccc  d");
+    }
+
+    /// A name wrapped in escape sequences that take no columns, as hyperlinks are
+    struct Linked(&'static str);
+
+    impl fmt::Display for Linked {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", self.0, self.0)
+        }
+    }
+
+    impl CharacterLength for Linked {
+        fn characters_long(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    #[test]
+    fn lays_out_by_the_columns_names_take_not_their_bytes() {
+        let names: Vec<Linked> = ["a", "bb", "ccc", "dddd"].iter().map(|&n| Linked(n)).collect();
+        let text = Tabulator::new(&names, 10, TabulateOrientation::Columns).to_string();
+        let link = |name: &'static str| Linked(name).to_string();
+        assert_eq!(text, format!("{}   {}\n{}  {}", link("a"), link("ccc"), link("bb"), link("dddd")));
+    }
+
+    #[test]
+    fn separates_with_commas_and_wraps() {
+        let names: Vec<Name> = NAMES.iter().map(|&n| Name(n)).collect();
+        assert_eq!(CommaSeparated::new(&names, usize::MAX).to_string(), NAMES.join(", "));
+        assert_eq!(CommaSeparated::new(&names, 22).to_string(), "alpha, beta, gamma,\ndelta, epsilon");
+        assert_eq!(CommaSeparated::new(&names, 0).to_string(), NAMES.join(",\n"));
+        assert_eq!(CommaSeparated::new(&names[..0], 80).to_string(), "");
+    }
+}
@@ -0,0 +1,430 @@
+//! How names are colored
+//!
+//! Names get a color from their file type, chosen to be readable on the
+//! terminal's [`Background`]. As with dircolors, executables, setuid and
+//! setgid files, and sticky or world-writable directories are told apart by
+//! their mode, and other regular files get a color from their extension. When
+//! `LS_COLORS` is set, its colors replace those (see [`LsColors`]). The `[[colors]]` rules of the config file are
+//! applied after that, the last rule matching a name replacing its color:
+//!
+//! ```toml
+//! [[colors]]
+//! glob = "*.lock"
+//! style = "dimmed"
+//!
+//! [[colors]]
+//! glob = "target/"   # a trailing slash matches only directories
+//! style = "bright black"
+//!
+//! [[colors]]
+//! owner = "other"      # files owned by someone else, or "root"
+//! style = "bold red"
+//! ```
+//!
+//! A rule with both a glob and an owner applies to the names matching both.
+//! Besides the 16 named colors, a style can use the 256-color palette by
+//! number, e.g. `bold 208`, or 24-bit colors, e.g. `#5f87ff`, and a background
+//! after `on`, e.g. `black on #ffd700`. A theme file can replace the built-in
+//! colors with such styles (see [`Palette`]), ahead of `LS_COLORS`.
+use std::fmt;
+use std::os::unix::fs::MetadataExt;
+
+use colored::{ColoredString, Colorize};
+
+use crate::{colors, Background, EntryData, LsColors, Palette};
+
+/// A color of a [`Style`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Color {
+    Ansi(colored::Color), // one of the 16 named colors
+    Fixed(u8),            // from the 256-color palette
+    Rgb(u8, u8, u8),      // 24-bit
+}
+
+impl Color {
+    /// Parse a color name such as `red` or `bright black`, a number of the 256-color palette or `#rrggbb`
+    fn parse(spec: &str) -> Option<Color> {
+        if let Some(hex) = spec.strip_prefix('#') {
+            if hex.len() != 6 || !hex.is_ascii() {
+                return None;
+            }
+            let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16).ok();
+            return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+        }
+        if spec.starts_with(|c: char| c.is_ascii_digit()) {
+            return spec.parse().ok().map(Color::Fixed);
+        }
+        spec.parse().ok().map(Color::Ansi)
+    }
+
+    /// The SGR parameters selecting the color for the text, or its background
+    fn sgr(self, background: bool) -> String {
+        let base = if background { 48 } else { 38 };
+        match self {
+            Color::Ansi(color) if background => color.to_bg_str().into_owned(),
+            Color::Ansi(color) => color.to_fg_str().into_owned(),
+            Color::Fixed(index) => format!("{};5;{}", base, index),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base, r, g, b),
+        }
+    }
+}
+
+/// Colors and text attributes, e.g. `bold yellow`, `dimmed` or `#000000 on 214`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    color: Option<Color>,
+    background: Option<Color>,
+    bold: bool,
+    dimmed: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl Style {
+    /// Parse attributes (`bold`, `dimmed`, `italic`, `underline`) followed by
+    /// an optional color, and an optional background color after `on`
+    pub fn parse(spec: &str) -> Option<Style> {
+        let mut style = Style::default();
+        let mut words = spec.split_whitespace().peekable();
+        while let Some(&word) = words.peek() {
+            match word {
+                "bold" => style.bold = true,
+                "dim" | "dimmed" => style.dimmed = true,
+                "italic" => style.italic = true,
+                "underline" => style.underline = true,
+                _ => break,
+            }
+            words.next();
+        }
+
+        let words: Vec<&str> = words.collect();
+        let (color, background) = match words.iter().position(|&word| word == "on") {
+            Some(on) => (&words[..on], Some(&words[on + 1..])),
+            None => (&words[..], None),
+        };
+        if !color.is_empty() {
+            style.color = Some(Color::parse(&color.join(" "))?);
+        }
+        if let Some(background) = background {
+            style.background = Some(Color::parse(&background.join(" "))?);
+        }
+        if style == Style::default() {
+            return None;
+        }
+        Some(style)
+    }
+
+    /// The SGR parameters of the style, e.g. `1;38;5;208`
+    fn sgr(&self) -> String {
+        let attributes = [(self.bold, "1"), (self.dimmed, "2"), (self.italic, "3"), (self.underline, "4")];
+        let mut parameters: Vec<String> =
+            attributes.iter().filter(|(set, _)| *set).map(|(_, code)| code.to_string()).collect();
+        parameters.extend(self.color.map(|color| color.sgr(false)));
+        parameters.extend(self.background.map(|color| color.sgr(true)));
+        parameters.join(";")
+    }
+
+    pub(crate) fn paint(&self, text: &str) -> Painted {
+        Painted::Styled(text.to_string(), *self)
+    }
+}
+
+/// A name colored by a [`Theme`]
+/// Either of the built-in colors, of a style, possibly beyond the 16 colors of
+/// `colored`, or of SGR parameters from `LS_COLORS`. All are printed without
+/// colors when they are off.
+pub(crate) enum Painted {
+    Builtin(ColoredString),
+    Styled(String, Style),
+    Codes(String, String),
+}
+
+impl fmt::Display for Painted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (text, sgr) = match self {
+            Painted::Builtin(text) => return write!(f, "{}", text),
+            Painted::Styled(text, style) => (text, style.sgr()),
+            Painted::Codes(text, codes) => (text, codes.clone()),
+        };
+        if colored::control::SHOULD_COLORIZE.should_colorize() {
+            write!(f, "\x1b[{}m{}\x1b[0m", sgr, text)
+        } else {
+            write!(f, "{}", text)
+        }
+    }
+}
+
+/// Whose files a [`ColorRule`] applies to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Owner {
+    Root,  // owned by uid 0
+    Other, // not owned by the user running listare
+}
+
+impl Owner {
+    pub fn parse(name: &str) -> Option<Owner> {
+        match name {
+            "root" => Some(Owner::Root),
+            "other" => Some(Owner::Other),
+            _ => None,
+        }
+    }
+
+    fn matches(self, owner: u32, uid: u32) -> bool {
+        match self {
+            Owner::Root => owner == 0,
+            Owner::Other => owner != uid,
+        }
+    }
+}
+
+/// A style for names matching a glob
+#[derive(Clone, Debug)]
+pub struct ColorRule {
+    pattern: glob::Pattern,
+    dirs_only: bool, // the glob ended with a slash
+    owner: Option<Owner>,
+    style: Style,
+}
+
+impl ColorRule {
+    pub fn new(glob: &str, style: Style) -> Result<ColorRule, String> {
+        let (glob, dirs_only) = match glob.strip_suffix('/') {
+            Some(glob) => (glob, true),
+            None => (glob, false),
+        };
+        let pattern = glob::Pattern::new(glob).map_err(|e| format!("invalid glob {}: {}", glob, e.msg))?;
+        Ok(ColorRule { pattern, dirs_only, owner: None, style })
+    }
+
+    /// Restrict the rule to files of an owner
+    pub fn owned_by(self, owner: Owner) -> ColorRule {
+        ColorRule { owner: Some(owner), ..self }
+    }
+
+    /// Whether the rule applies to a name, given the uid owning it and the uid
+    /// of the user running listare
+    fn matches(&self, name: &str, is_dir: bool, owner: u32, uid: u32) -> bool {
+        (is_dir || !self.dirs_only)
+            && self.owner.is_none_or(|o| o.matches(owner, uid))
+            && self.pattern.matches(name)
+    }
+}
+
+/// What decides the color of a name, unless a rule gives it one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Category {
+    Directory,
+    Symlink,
+    BrokenSymlink,
+    Executable,
+    Setuid,
+    Setgid,
+    Sticky,              // a directory
+    OtherWritable,       // a directory
+    StickyOtherWritable, // a directory
+    Kind(colors::Kind),  // a regular file, by its extension
+}
+
+impl Category {
+    /// Every category with its name in theme files
+    pub(crate) const NAMES: [(&'static str, Category); 15] = [
+        ("directory", Category::Directory),
+        ("symlink", Category::Symlink),
+        ("broken-symlink", Category::BrokenSymlink),
+        ("executable", Category::Executable),
+        ("setuid", Category::Setuid),
+        ("setgid", Category::Setgid),
+        ("sticky", Category::Sticky),
+        ("other-writable", Category::OtherWritable),
+        ("sticky-other-writable", Category::StickyOtherWritable),
+        ("archive", Category::Kind(colors::Kind::Archive)),
+        ("image", Category::Kind(colors::Kind::Image)),
+        ("video", Category::Kind(colors::Kind::Video)),
+        ("audio", Category::Kind(colors::Kind::Audio)),
+        ("source", Category::Kind(colors::Kind::Source)),
+        ("temporary", Category::Kind(colors::Kind::Temporary)),
+    ];
+
+    /// The categories whose built-in colors are chosen for the background
+    pub(crate) const ON_BACKGROUND: [Category; 3] =
+        [Category::Symlink, Category::Kind(colors::Kind::Audio), Category::Kind(colors::Kind::Source)];
+
+    /// The category dircolors gives for the file type, special bits and permissions of `mode`, if any
+    fn from_mode(mode: u32) -> Option<Category> {
+        let other_writable = mode & libc::S_IWOTH != 0;
+        match mode & libc::S_IFMT {
+            libc::S_IFDIR => Some(match (mode & libc::S_ISVTX != 0, other_writable) {
+                (true, true) => Category::StickyOtherWritable,
+                (false, true) => Category::OtherWritable,
+                (true, false) => Category::Sticky,
+                (false, false) => Category::Directory,
+            }),
+            libc::S_IFREG if mode & libc::S_ISUID != 0 => Some(Category::Setuid),
+            libc::S_IFREG if mode & libc::S_ISGID != 0 => Some(Category::Setgid),
+            libc::S_IFREG if mode & 0o111 != 0 => Some(Category::Executable),
+            _ => None,
+        }
+    }
+
+    /// Color `text` with the built-in color of the category, readably on `background`
+    fn paint(self, text: &str, background: Background) -> ColoredString {
+        match (self, background) {
+            (Category::Directory, _) => text.bold().blue(),
+            (Category::Symlink, Background::Dark) => text.bold().cyan(),
+            // cyan is hard to read on a light background
+            (Category::Symlink, Background::Light) => text.bold().magenta(),
+            (Category::BrokenSymlink, _) => text.bold().red(),
+            (Category::Executable, _) => text.bold().green(),
+            (Category::Setuid, _) => text.white().on_red(),
+            (Category::Setgid, _) => text.black().on_yellow(),
+            (Category::Sticky, _) => text.white().on_blue(),
+            (Category::OtherWritable, _) => text.blue().on_green(),
+            (Category::StickyOtherWritable, _) => text.black().on_green(),
+            (Category::Kind(kind), _) => kind.paint(text, background),
+        }
+    }
+}
+
+/// Everything that decides the colors of names
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub background: Background,
+    pub rules: Vec<ColorRule>,
+    /// The user running listare, for rules on the owner of files
+    pub uid: u32,
+    /// Styles replacing the built-in colors, from a theme file
+    pub palette: Palette,
+    /// Colors replacing the built-in ones, but not the theme's, from `LS_COLORS`
+    pub ls_colors: Option<LsColors>,
+}
+
+impl Theme {
+    /// Color `text`, the name of `entry` or a path to it
+    pub(crate) fn paint(&self, entry: &EntryData, text: &str) -> Painted {
+        let is_dir = entry.metadata.is_dir();
+        let owner = entry.metadata.uid();
+        if let Some(rule) = self.rules.iter().rev().find(|rule| rule.matches(&entry.name, is_dir, owner, self.uid)) {
+            return rule.style.paint(text);
+        }
+
+        let is_file = entry.metadata.is_file();
+        let category = if entry.metadata.is_symlink() {
+            if entry.target_metadata().is_some() {
+                Some(Category::Symlink)
+            } else {
+                Some(Category::BrokenSymlink)
+            }
+        } else if let Some(category) = Category::from_mode(entry.metadata.mode()) {
+            Some(category)
+        } else if let Some(style) = self.palette.extension(&entry.name).filter(|_| is_file) {
+            // a theme's extensions come before the built-in ones
+            return style.paint(text);
+        } else {
+            // the built-in extensions are left to LS_COLORS when it is set
+            colors::kind(&entry.name).filter(|_| self.ls_colors.is_none() && is_file).map(Category::Kind)
+        };
+
+        if let Some(style) = category.and_then(|category| self.palette.style(category)) {
+            return style.paint(text);
+        }
+        if let Some(ls_colors) = &self.ls_colors {
+            return match ls_colors.sgr(entry) {
+                Some(sgr) => Painted::Codes(text.to_string(), sgr.to_string()),
+                None => Painted::Builtin(text.normal()),
+            };
+        }
+        match category {
+            Some(category) => Painted::Builtin(category.paint(text, self.background)),
+            None => Painted::Builtin(text.normal()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Category, Color, ColorRule, Owner, Style};
+    use crate::Background;
+    use colored::Colorize;
+
+    #[test]
+    fn parses_styles() {
+        let bold = Style { color: Some(Color::Ansi(colored::Color::BrightBlack)), bold: true, ..Style::default() };
+        assert_eq!(Style::parse("bold bright black"), Some(bold));
+        assert_eq!(Style::parse("dimmed"), Some(Style { dimmed: true, ..Style::default() }));
+        let yellow = Style { color: Some(Color::Ansi(colored::Color::Yellow)), ..Style::default() };
+        assert_eq!(Style::parse("yellow"), Some(yellow));
+        assert_eq!(Style::parse(""), None);
+        assert_eq!(Style::parse("bold mauve"), None);
+        // attributes come before the color
+        assert_eq!(Style::parse("red bold"), None);
+    }
+
+    #[test]
+    fn parses_palette_and_rgb_colors() {
+        let style = |spec: &str| Style::parse(spec).map(|style| style.sgr());
+        assert_eq!(style("bold 208").as_deref(), Some("1;38;5;208"));
+        assert_eq!(style("#5f87FF").as_deref(), Some("38;2;95;135;255"));
+        assert_eq!(style("black on #ffd700").as_deref(), Some("30;48;2;255;215;0"));
+        assert_eq!(style("underline on 236").as_deref(), Some("4;48;5;236"));
+        assert_eq!(style("bold bright black").as_deref(), Some("1;90"));
+        assert_eq!(style("256"), None);
+        assert_eq!(style("#12345"), None);
+        assert_eq!(style("#gg0000"), None);
+        assert_eq!(style("red on"), None);
+    }
+
+    #[test]
+    fn categorizes_by_mode() {
+        assert_eq!(Category::from_mode(libc::S_IFREG | 0o755), Some(Category::Executable));
+        assert_eq!(Category::from_mode(libc::S_IFREG | 0o4755), Some(Category::Setuid));
+        assert_eq!(Category::from_mode(libc::S_IFREG | 0o2755), Some(Category::Setgid));
+        // setuid is shown even on a file no one can execute
+        assert_eq!(Category::from_mode(libc::S_IFREG | 0o4644), Some(Category::Setuid));
+        assert_eq!(Category::from_mode(libc::S_IFREG | 0o644), None);
+        assert_eq!(Category::from_mode(libc::S_IFDIR | 0o1777), Some(Category::StickyOtherWritable));
+        assert_eq!(Category::from_mode(libc::S_IFDIR | 0o777), Some(Category::OtherWritable));
+        assert_eq!(Category::from_mode(libc::S_IFDIR | 0o1755), Some(Category::Sticky));
+        assert_eq!(Category::from_mode(libc::S_IFDIR | 0o755), Some(Category::Directory));
+        // the mode of a symlink says nothing of its target
+        assert_eq!(Category::from_mode(libc::S_IFLNK | 0o777), None);
+    }
+
+    #[test]
+    fn paints_categories_as_dircolors() {
+        let paint = |category: Category| category.paint("x", Background::Dark);
+        assert_eq!(paint(Category::Executable), "x".bold().green());
+        assert_eq!(paint(Category::Setuid), "x".white().on_red());
+        assert_eq!(paint(Category::Setgid), "x".black().on_yellow());
+        assert_eq!(paint(Category::StickyOtherWritable), "x".black().on_green());
+        assert_eq!(paint(Category::OtherWritable), "x".blue().on_green());
+        assert_eq!(paint(Category::Sticky), "x".white().on_blue());
+        assert_eq!(paint(Category::Directory), "x".bold().blue());
+    }
+
+    #[test]
+    fn matches_names_and_directories() {
+        let matches = |glob: &str, name: &str, is_dir: bool| {
+            ColorRule::new(glob, Style::default()).is_ok_and(|rule| rule.matches(name, is_dir, 1000, 1000))
+        };
+        assert!(matches("*.lock", "Cargo.lock", false));
+        assert!(!matches("*.lock", "Cargo.toml", false));
+        assert!(matches("target/", "target", true));
+        assert!(!matches("target/", "target", false));
+        assert!(ColorRule::new("[", Style::default()).is_err());
+    }
+
+    #[test]
+    fn matches_owners() {
+        let matches = |owner: Owner, file_owner: u32, uid: u32| {
+            ColorRule::new("*", Style::default())
+                .is_ok_and(|rule| rule.owned_by(owner).matches("notes", false, file_owner, uid))
+        };
+        assert!(matches(Owner::Other, 0, 1000));
+        assert!(!matches(Owner::Other, 1000, 1000));
+        assert!(matches(Owner::Root, 0, 1000));
+        assert!(matches(Owner::Root, 0, 0));
+        assert!(!matches(Owner::Root, 1000, 1000));
+        assert_eq!(Owner::parse("me"), None);
+    }
+}
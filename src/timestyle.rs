@@ -0,0 +1,351 @@
+//! Parsing of `--time-style` values
+//!
+//! The styles are those of GNU ls: `full-iso`, `long-iso`, `iso`, `locale` and
+//! `+FORMAT`, and `relative` for ages such as `3 days ago` and `precise` for
+//! local times to the nanosecond. A `posix-` prefix applies the style only
+//! outside the POSIX locale. As in GNU date, `%N` in a format is the
+//! nanoseconds of the time.
+//! Month names are those of the locale rather than chrono's English ones.
+
+use std::{borrow::Cow, fmt, sync::OnceLock, time::SystemTime};
+
+use chrono::format::{Item, StrftimeItems};
+
+use crate::{posix, width};
+
+/// How timestamps are rendered in long format
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TimeStyle {
+    /// Month, day and time for recent files, month, day and year for older ones
+    Locale,
+    /// strftime formats for files older than six months and for recent files
+    Format { old: String, recent: String },
+    /// How long ago, e.g. `2 min ago`
+    Relative,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TimeStyleError {
+    Unknown(String),         // the style is not one of the known names
+    InvalidFormat(String),   // a +FORMAT contains an invalid conversion
+    UnknownTimeZone(String), // a time zone that is not in the time zone database
+}
+
+impl fmt::Display for TimeStyleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeStyleError::Unknown(style) => write!(f, "invalid time style: {}", style),
+            TimeStyleError::InvalidFormat(format) => {
+                write!(f, "invalid time style format: {}", format)
+            }
+            TimeStyleError::UnknownTimeZone(zone) => write!(f, "unknown time zone: {}", zone),
+        }
+    }
+}
+
+impl std::error::Error for TimeStyleError {}
+
+const FULL_ISO: &str = "%Y-%m-%d %H:%M:%S%.9f %z";
+const LONG_ISO: &str = "%Y-%m-%d %H:%M";
+const PRECISE: &str = "%Y-%m-%d %H:%M:%S.%f";
+
+impl TimeStyle {
+    /// Full date and time with nanoseconds and the timezone offset, for every file
+    pub fn full_iso() -> TimeStyle {
+        TimeStyle::Format {
+            old: FULL_ISO.to_string(),
+            recent: FULL_ISO.to_string(),
+        }
+    }
+
+    /// Date and time to the minute, the same for every file
+    pub fn long_iso() -> TimeStyle {
+        TimeStyle::Format {
+            old: LONG_ISO.to_string(),
+            recent: LONG_ISO.to_string(),
+        }
+    }
+
+    /// Parse a style given to `--time-style` or in `TIME_STYLE`
+    ///
+    /// As in GNU ls, a `+FORMAT` containing a newline is split in two: the first
+    /// line applies to old files and the second line to recent files.
+    pub fn parse(style: &str) -> Result<TimeStyle, TimeStyleError> {
+        if let Some(style) = style.strip_prefix("posix-") {
+            // in the POSIX locale the traditional format is kept
+            if is_posix_locale() {
+                return Ok(TimeStyle::Locale);
+            }
+            return TimeStyle::parse(style);
+        }
+
+        let (old, recent) = match style {
+            "full-iso" => return Ok(TimeStyle::full_iso()),
+            "long-iso" => return Ok(TimeStyle::long_iso()),
+            "precise" => (PRECISE, PRECISE),
+            "iso" => ("%Y-%m-%d ", "%m-%d %H:%M"),
+            "locale" => return Ok(TimeStyle::Locale),
+            "relative" => return Ok(TimeStyle::Relative),
+            _ => {
+                let format = style
+                    .strip_prefix('+')
+                    .ok_or_else(|| TimeStyleError::Unknown(style.to_string()))?;
+                match format.split_once('\n') {
+                    Some((old, recent)) => (old, recent),
+                    None => (format, format),
+                }
+            }
+        };
+        let (old, recent) = (nanoseconds(old), nanoseconds(recent));
+        for format in [&old, &recent] {
+            validate(format)?;
+        }
+
+        Ok(TimeStyle::Format {
+            old: old.into_owned(),
+            recent: recent.into_owned(),
+        })
+    }
+
+    /// The style given by the `TIME_STYLE` environment variable, if it is set
+    pub fn from_env() -> Option<Result<TimeStyle, TimeStyleError>> {
+        let style = std::env::var("TIME_STYLE").ok()?;
+        Some(TimeStyle::parse(&style))
+    }
+
+    /// The strftime format used for a timestamp, None for the relative style
+    pub fn format(&self, recent: bool) -> Option<&str> {
+        match self {
+            TimeStyle::Locale if recent => Some("%b %e %H:%M"),
+            TimeStyle::Locale => Some("%b %e  %Y"),
+            TimeStyle::Format { recent: format, .. } if recent => Some(format),
+            TimeStyle::Format { old: format, .. } => Some(format),
+            TimeStyle::Relative => None,
+        }
+    }
+}
+
+/// How long before `now` a timestamp is, in its largest whole unit, e.g. `3 days ago`
+/// Timestamps in the future are given as e.g. `in 2 hours`.
+pub(crate) fn relative(timestamp: SystemTime, now: SystemTime) -> String {
+    let (secs, future) = match now.duration_since(timestamp) {
+        Ok(age) => (age.as_secs(), false),
+        Err(e) => (e.duration().as_secs(), true),
+    };
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+    const MONTH: u64 = 30 * DAY;
+    const YEAR: u64 = 365 * DAY;
+    let (count, unit, units) = if secs < MINUTE {
+        (secs, "sec", "sec")
+    } else if secs < HOUR {
+        (secs / MINUTE, "min", "min")
+    } else if secs < DAY {
+        (secs / HOUR, "hour", "hours")
+    } else if secs < MONTH {
+        (secs / DAY, "day", "days")
+    } else if secs < YEAR {
+        (secs / MONTH, "month", "months")
+    } else {
+        (secs / YEAR, "year", "years")
+    };
+    let unit = if count == 1 { unit } else { units };
+    if future {
+        format!("in {} {}", count, unit)
+    } else {
+        format!("{} {} ago", count, unit)
+    }
+}
+
+/// The locale's abbreviated month names, padded to the same width so that dates line up
+fn abbreviated_months() -> Option<&'static [String; 12]> {
+    static MONTHS: OnceLock<Option<[String; 12]>> = OnceLock::new();
+    MONTHS
+        .get_or_init(|| {
+            let mut names = posix::month_names(true)?;
+            let widest = names.iter().map(|n| width::display_width(n, false)).max().unwrap_or(0);
+            for name in names.iter_mut() {
+                let padding = widest - width::display_width(name, false);
+                name.push_str(&" ".repeat(padding));
+            }
+            Some(names)
+        })
+        .as_ref()
+}
+
+/// The locale's full month names
+fn full_months() -> Option<&'static [String; 12]> {
+    static MONTHS: OnceLock<Option<[String; 12]>> = OnceLock::new();
+    MONTHS.get_or_init(|| posix::month_names(false)).as_ref()
+}
+
+/// Replace the month name conversions of `format` with the locale's name for `month` (0 to 11)
+pub(crate) fn localize(format: &str, month: usize) -> Cow<'_, str> {
+    localize_with(format, abbreviated_months().map(|n| &n[month]), full_months().map(|n| &n[month]))
+}
+
+fn localize_with<'a>(format: &'a str, abbreviated: Option<&String>, full: Option<&String>) -> Cow<'a, str> {
+    if !format.contains('%') || (abbreviated.is_none() && full.is_none()) {
+        return Cow::Borrowed(format);
+    }
+
+    let mut localized = String::with_capacity(format.len());
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            localized.push(c);
+            continue;
+        }
+        // keep the conversion as it is unless it is a month name the locale has
+        let name = match chars.next() {
+            Some(spec @ ('b' | 'h')) => abbreviated.ok_or(spec),
+            Some('B') => full.ok_or('B'),
+            Some(spec) => Err(spec),
+            None => {
+                localized.push('%');
+                break;
+            }
+        };
+        match name {
+            // a literal % in a name must not start a conversion
+            Ok(name) => localized.push_str(&name.replace('%', "%%")),
+            Err(spec) => {
+                localized.push('%');
+                localized.push(spec);
+            }
+        }
+    }
+    Cow::Owned(localized)
+}
+
+/// Where the time zone database is looked for, as by chrono
+const ZONE_INFO_DIRECTORIES: [&str; 4] = ["/usr/share/zoneinfo", "/share/zoneinfo", "/etc/zoneinfo", "/usr/share/lib/zoneinfo"];
+
+/// Check a `--time-zone` name, `UTC` or an IANA name such as `Europe/Paris`
+/// Times are shown in the zone by setting `TZ`, which chrono would otherwise
+/// silently ignore when it names no zone.
+pub fn parse_time_zone(name: &str) -> Result<String, TimeStyleError> {
+    let known = match name {
+        "UTC" | "GMT" => true,
+        _ => {
+            let path = std::path::Path::new(name);
+            // names are relative to the database, and must stay inside it
+            path.is_relative()
+                && path.components().all(|c| matches!(c, std::path::Component::Normal(_)))
+                && ZONE_INFO_DIRECTORIES.iter().any(|dir| is_zone_file(&std::path::Path::new(dir).join(path)))
+        }
+    };
+    if known {
+        Ok(name.to_string())
+    } else {
+        Err(TimeStyleError::UnknownTimeZone(name.to_string()))
+    }
+}
+
+/// Whether a file in the database describes a zone, rather than being one of
+/// its tables such as `zone.tab` or `tzdata.zi`, by the magic of the TZif format
+fn is_zone_file(path: &std::path::Path) -> bool {
+    use std::io::Read;
+
+    let mut magic = [0u8; 4];
+    std::fs::File::open(path).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && &magic == b"TZif"
+}
+
+/// Replace GNU's `%N` with chrono's `%f`, the nanoseconds padded to nine digits
+fn nanoseconds(format: &str) -> Cow<'_, str> {
+    if !format.contains("%N") {
+        return Cow::Borrowed(format);
+    }
+    let mut converted = String::with_capacity(format.len());
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        converted.push(c);
+        if c == '%' {
+            match chars.next() {
+                Some('N') => converted.push('f'),
+                Some(spec) => converted.push(spec),
+                None => break,
+            }
+        }
+    }
+    Cow::Owned(converted)
+}
+
+/// Whether times are formatted in the POSIX ("C") locale
+fn is_posix_locale() -> bool {
+    let locale = ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+    matches!(locale.as_deref(), None | Some("C") | Some("POSIX"))
+}
+
+/// Reject formats chrono cannot render, which would otherwise fail while printing
+fn validate(format: &str) -> Result<(), TimeStyleError> {
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        Err(TimeStyleError::InvalidFormat(format.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{localize_with, nanoseconds, relative, TimeStyle};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn substitutes_the_locale_month_names() {
+        let abbreviated = "févr.".to_string();
+        let full = "février".to_string();
+        let localize = |format| localize_with(format, Some(&abbreviated), Some(&full)).into_owned();
+        assert_eq!(localize("%b %e %H:%M"), "févr. %e %H:%M");
+        assert_eq!(localize("%h/%B/%Y"), "févr./février/%Y");
+        // escaped percent signs are not conversions
+        assert_eq!(localize("%%b %"), "%%b %");
+    }
+
+    #[test]
+    fn escapes_percent_signs_in_names() {
+        let odd = "50%".to_string();
+        assert_eq!(localize_with("%b", Some(&odd), None), "50%%");
+        // without a full name chrono's is kept
+        assert_eq!(localize_with("%B", Some(&odd), None), "%B");
+    }
+
+    #[test]
+    fn keeps_formats_without_locale_names() {
+        assert_eq!(localize_with("%b %Y", None, None), "%b %Y");
+    }
+
+    #[test]
+    fn describes_ages_in_their_largest_unit() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let ago = |secs| relative(now - Duration::from_secs(secs), now);
+        assert_eq!(ago(0), "0 sec ago");
+        assert_eq!(ago(59), "59 sec ago");
+        assert_eq!(ago(120), "2 min ago");
+        assert_eq!(ago(3600), "1 hour ago");
+        assert_eq!(ago(3 * 86400 + 5), "3 days ago");
+        assert_eq!(ago(65 * 86400), "2 months ago");
+        assert_eq!(ago(800 * 86400), "2 years ago");
+        assert_eq!(relative(now + Duration::from_secs(7200), now), "in 2 hours");
+    }
+
+    #[test]
+    fn converts_gnu_nanoseconds() {
+        assert_eq!(nanoseconds("%H:%M:%S.%N"), "%H:%M:%S.%f");
+        // an escaped percent sign is not a conversion
+        assert_eq!(nanoseconds("%%N %N"), "%%N %f");
+        assert_eq!(nanoseconds("%Y-%m-%d"), "%Y-%m-%d");
+        let precise = TimeStyle::parse("precise");
+        assert!(precise.is_ok_and(|style| style.format(true) == Some("%Y-%m-%d %H:%M:%S.%f")));
+    }
+
+    #[test]
+    fn parses_the_relative_style() {
+        assert_eq!(TimeStyle::parse("relative"), Ok(TimeStyle::Relative));
+        assert_eq!(TimeStyle::Relative.format(true), None);
+    }
+}
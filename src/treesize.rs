@@ -0,0 +1,61 @@
+//! Sizes of whole directory trees, for `--total-size`
+//!
+//! As with `du --apparent-size`, the size of a directory is its own size and
+//! that of everything beneath it. Symlinks count as the links they are and are
+//! never followed, so each tree is walked once however it links to itself or
+//! elsewhere. With `--count-links-once`, a file or directory met again under
+//! another name, which is told by its (device, inode) pair, is counted only the
+//! first time in a listing, so that the numbers match those of `du
+//! --apparent-size`. The implied `..` of `-a` is not walked, and keeps its own
+//! size.
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::vfs;
+
+/// Walks the trees of the entries of one listing
+pub(crate) struct TreeSizes {
+    seen: Option<HashSet<(u64, u64)>>, // with --count-links-once, the inodes counted so far
+}
+
+impl TreeSizes {
+    pub(crate) fn new(count_links_once: bool) -> Self {
+        TreeSizes { seen: count_links_once.then(HashSet::new) }
+    }
+
+    /// Whether the inode of `metadata` is to be counted, marking it as counted
+    fn first_sight(&mut self, metadata: &Metadata) -> bool {
+        match &mut self.seen {
+            // only these can be met under more than one name
+            Some(seen) if metadata.nlink() > 1 || metadata.is_dir() => {
+                seen.insert((metadata.dev(), metadata.ino()))
+            }
+            _ => true,
+        }
+    }
+
+    /// The size of `path` and of everything beneath it, 0 if it was already counted
+    /// Entries that cannot be read are left out, as du leaves them out of its totals.
+    pub(crate) fn size(&mut self, path: &Path, metadata: &Metadata) -> u64 {
+        let mut total = 0;
+        // walked without recursion, as trees can be deeper than the stack allows
+        let mut pending: Vec<(PathBuf, Metadata)> = vec![(path.to_path_buf(), metadata.clone())];
+        while let Some((path, metadata)) = pending.pop() {
+            if !self.first_sight(&metadata) {
+                continue;
+            }
+            total += metadata.len();
+            if metadata.is_dir() {
+                for entry in vfs::read_dir(&path).into_iter().flatten().flatten() {
+                    let path = entry.path();
+                    if let Ok(metadata) = vfs::symlink_metadata(&path) {
+                        pending.push((path, metadata));
+                    }
+                }
+            }
+        }
+        total
+    }
+}
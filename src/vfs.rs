@@ -0,0 +1,93 @@
+//! Filesystem access
+//!
+//! Every filesystem call made while listing goes through here, so that when
+//! built with the `fault-injection` feature tests can make chosen calls fail.
+//! Faults are read from `LISTARE_FAULTS`, a `;` separated list of
+//! `OP:ERRNO:PATTERN` where OP is `lstat`, `stat`, `readdir` or `readlink`,
+//! ERRNO is `EACCES`, `ENOENT` or `EIO`, and the fault applies to every path
+//! containing PATTERN. For example `readdir:EACCES:secret;lstat:EIO:bad`.
+use std::{
+    fs::{self, Metadata, ReadDir},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Metadata of a path, not following symlinks
+pub fn symlink_metadata(path: &Path) -> io::Result<Metadata> {
+    inject(Op::Lstat, path)?;
+    fs::symlink_metadata(path)
+}
+
+/// Metadata of a path, following symlinks
+pub fn metadata(path: &Path) -> io::Result<Metadata> {
+    inject(Op::Stat, path)?;
+    fs::metadata(path)
+}
+
+pub fn read_dir(path: &Path) -> io::Result<ReadDir> {
+    inject(Op::ReadDir, path)?;
+    fs::read_dir(path)
+}
+
+pub fn read_link(path: &Path) -> io::Result<PathBuf> {
+    inject(Op::ReadLink, path)?;
+    fs::read_link(path)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Lstat,
+    Stat,
+    ReadDir,
+    ReadLink,
+}
+
+#[cfg(not(feature = "fault-injection"))]
+#[inline(always)]
+fn inject(_op: Op, _path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(feature = "fault-injection")]
+fn inject(op: Op, path: &Path) -> io::Result<()> {
+    use std::sync::OnceLock;
+
+    struct Fault {
+        op: Op,
+        errno: i32,
+        pattern: String,
+    }
+
+    // malformed faults are ignored, this is only meant for tests
+    fn parse(spec: &str) -> Option<Fault> {
+        let mut parts = spec.splitn(3, ':');
+        let op = match parts.next()? {
+            "lstat" => Op::Lstat,
+            "stat" => Op::Stat,
+            "readdir" => Op::ReadDir,
+            "readlink" => Op::ReadLink,
+            _ => return None,
+        };
+        let errno = match parts.next()? {
+            "EACCES" => libc::EACCES,
+            "ENOENT" => libc::ENOENT,
+            "EIO" => libc::EIO,
+            _ => return None,
+        };
+        let pattern = parts.next()?.to_string();
+        Some(Fault { op, errno, pattern })
+    }
+
+    static FAULTS: OnceLock<Vec<Fault>> = OnceLock::new();
+    let faults = FAULTS.get_or_init(|| {
+        std::env::var("LISTARE_FAULTS")
+            .map(|spec| spec.split(';').filter_map(parse).collect())
+            .unwrap_or_default()
+    });
+
+    let path = path.to_string_lossy();
+    match faults.iter().find(|f| f.op == op && path.contains(&f.pattern)) {
+        Some(fault) => Err(io::Error::from_raw_os_error(fault.errno)),
+        None => Ok(()),
+    }
+}
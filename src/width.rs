@@ -0,0 +1,196 @@
+//! The number of terminal columns taken by text
+//!
+//! Width is measured per grapheme cluster rather than per character, so that
+//! an emoji with a variation selector, a flag made of two regional indicators
+//! or a ZWJ sequence counts as the two columns terminals draw it in, and
+//! combining marks count as nothing. Characters of ambiguous East Asian width
+//! (e.g. `§`, `°`, `±`) take one column unless
+//! `ambiguous_wide` is set, as they do in CJK terminals.
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+const ZERO_WIDTH_JOINER: char = '\u{200d}';
+const TEXT_PRESENTATION: char = '\u{fe0e}';
+const EMOJI_PRESENTATION: char = '\u{fe0f}';
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1f1e6}'..='\u{1f1ff}').contains(&c)
+}
+
+fn char_width(c: char, ambiguous_wide: bool) -> usize {
+    let width = if ambiguous_wide { c.width_cjk() } else { c.width() };
+    // control characters are not printed as themselves
+    width.unwrap_or(0)
+}
+
+fn grapheme_width(grapheme: &str, ambiguous_wide: bool) -> usize {
+    let mut chars = grapheme.chars();
+    let first = match chars.next() {
+        Some(c) => c,
+        None => return 0,
+    };
+    let base = char_width(first, ambiguous_wide);
+
+    if grapheme.contains(TEXT_PRESENTATION) {
+        return base.min(1);
+    }
+    // whatever follows a wide base, like a skin tone, is drawn within its two columns
+    let is_emoji_sequence = base == 2
+        || grapheme.contains(EMOJI_PRESENTATION)
+        || (grapheme.contains(ZERO_WIDTH_JOINER) && base > 0)
+        || (is_regional_indicator(first) && chars.next().is_some_and(is_regional_indicator));
+    if is_emoji_sequence {
+        return 2;
+    }
+
+    grapheme.chars().map(|c| char_width(c, ambiguous_wide)).sum()
+}
+
+/// The number of columns `text` takes in a terminal
+pub(crate) fn display_width(text: &str, ambiguous_wide: bool) -> usize {
+    text.graphemes(true).map(|g| grapheme_width(g, ambiguous_wide)).sum()
+}
+
+/// `text` cut to at most `max_width` columns, ending with `…` when it was cut
+/// Whole grapheme clusters are kept or dropped, so accents and emoji sequences
+/// are never split.
+pub(crate) fn truncate(text: &str, max_width: usize, ambiguous_wide: bool) -> String {
+    if display_width(text, ambiguous_wide) <= max_width {
+        return text.to_string();
+    }
+    let mut cut = String::new();
+    let mut width = 0;
+    for grapheme in text.graphemes(true) {
+        width += grapheme_width(grapheme, ambiguous_wide);
+        // leave a column for the ellipsis
+        if width + 1 > max_width {
+            break;
+        }
+        cut.push_str(grapheme);
+    }
+    if max_width > 0 {
+        cut.push(ELLIPSIS);
+    }
+    cut
+}
+
+const ELLIPSIS: char = '…';
+
+/// The line length used when neither `$COLUMNS` nor the terminal gives one
+const DEFAULT_LINE_LENGTH: usize = 80;
+
+/// Widths above this are mistakes rather than real terminals, whose sizes are 16 bit
+const MAX_LINE_LENGTH: usize = u16::MAX as usize;
+
+/// The length of output lines, from `$COLUMNS` and then the width of the terminal
+///
+/// Values of `$COLUMNS` that are not numbers or too large for a terminal are
+/// ignored, as is a terminal reporting no columns. A width of 0 means lines
+/// are never wrapped, and is returned as `usize::MAX`.
+pub fn line_length(columns: Option<&str>, terminal: Option<usize>) -> usize {
+    let columns = columns
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|&width| width <= MAX_LINE_LENGTH);
+    match columns {
+        Some(0) => usize::MAX,
+        Some(width) => width,
+        None => terminal.filter(|&width| width > 0).unwrap_or(DEFAULT_LINE_LENGTH),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{display_width, line_length, truncate};
+
+    fn narrow(text: &str) -> usize {
+        display_width(text, false)
+    }
+
+    #[test]
+    fn measures_plain_text() {
+        assert_eq!(narrow(""), 0);
+        assert_eq!(narrow("Cargo.toml"), 10);
+        assert_eq!(narrow("日本語.txt"), 10);
+        assert_eq!(narrow("ｆｕｌｌ"), 8);
+    }
+
+    #[test]
+    fn ignores_combining_marks() {
+        // e followed by a combining acute accent
+        assert_eq!(narrow("cafe\u{301}"), 4);
+        assert_eq!(narrow("café"), 4);
+        assert_eq!(narrow("a\u{20dd}"), 1);
+    }
+
+    #[test]
+    fn measures_emoji_as_two_columns() {
+        assert_eq!(narrow("🎉 party"), 8);
+        // heart, which is narrow, with the emoji presentation selector
+        assert_eq!(narrow("\u{2764}\u{fe0f}.txt"), 6);
+        // the same heart forced to text presentation
+        assert_eq!(narrow("\u{2764}\u{fe0e}.txt"), 5);
+        // thumbs up with a skin tone modifier
+        assert_eq!(narrow("👍🏽"), 2);
+        // family: man, woman, girl joined by ZWJ
+        assert_eq!(narrow("👨\u{200d}👩\u{200d}👧 photos"), 9);
+        // keycap one: digit, emoji presentation, combining enclosing keycap
+        assert_eq!(narrow("1\u{fe0f}\u{20e3}"), 2);
+    }
+
+    #[test]
+    fn measures_flags_as_two_columns() {
+        assert_eq!(narrow("🇯🇵"), 2);
+        assert_eq!(narrow("🇺🇸🇫🇷.png"), 8);
+        // a lone regional indicator is not a flag
+        assert_eq!(narrow("🇯"), 1);
+    }
+
+    #[test]
+    fn ignores_control_characters() {
+        assert_eq!(narrow("a\u{7}b"), 2);
+        assert_eq!(narrow("tab\there"), 7);
+    }
+
+    #[test]
+    fn widens_ambiguous_characters_on_request() {
+        assert_eq!(display_width("§1 ±0.5°", false), 8);
+        assert_eq!(display_width("§1 ±0.5°", true), 11);
+        // unambiguous characters are unaffected
+        assert_eq!(display_width("abc日本", true), 7);
+    }
+
+    #[test]
+    fn truncates_whole_graphemes() {
+        assert_eq!(truncate("alice", 5, false), "alice");
+        assert_eq!(truncate("alexander.hamilton", 8, false), "alexand…");
+        assert_eq!(truncate("cafe\u{301}-owners", 5, false), "cafe\u{301}…");
+        // a wide character that does not fit is dropped whole
+        assert_eq!(truncate("日本語", 4, false), "日…");
+        assert_eq!(truncate("👨\u{200d}👩\u{200d}👧 team", 3, false), "👨\u{200d}👩\u{200d}👧…");
+        assert_eq!(truncate("bob", 1, false), "…");
+        assert_eq!(truncate("bob", 0, false), "");
+    }
+
+    #[test]
+    fn prefers_columns_over_the_terminal() {
+        assert_eq!(line_length(Some("100"), Some(120)), 100);
+        assert_eq!(line_length(None, Some(120)), 120);
+        assert_eq!(line_length(None, None), 80);
+    }
+
+    #[test]
+    fn ignores_unusable_columns() {
+        assert_eq!(line_length(Some("wide"), Some(120)), 120);
+        assert_eq!(line_length(Some(""), Some(120)), 120);
+        assert_eq!(line_length(Some("-5"), None), 80);
+        assert_eq!(line_length(Some("99999999999999999999999"), Some(120)), 120);
+        assert_eq!(line_length(Some("70000"), None), 80);
+        // a terminal without a size
+        assert_eq!(line_length(None, Some(0)), 80);
+    }
+
+    #[test]
+    fn zero_columns_is_unlimited() {
+        assert_eq!(line_length(Some("0"), Some(120)), usize::MAX);
+    }
+}
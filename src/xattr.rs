@@ -0,0 +1,119 @@
+//! Extended attributes, read without following symlinks
+//!
+//! Only Linux is supported; elsewhere files appear to have no attributes.
+use std::{ffi::CString, io, os::unix::ffi::OsStrExt, path::Path};
+
+fn c_path(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))
+}
+
+/// The names of the extended attributes of a file
+#[cfg(target_os = "linux")]
+pub fn list(path: &Path) -> io::Result<Vec<String>> {
+    let path = c_path(path)?;
+    loop {
+        let size = unsafe { libc::llistxattr(path.as_ptr(), std::ptr::null_mut(), 0) };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut buf = vec![0u8; size as usize];
+        let size = unsafe { libc::llistxattr(path.as_ptr(), buf.as_mut_ptr().cast(), buf.len()) };
+        if size < 0 {
+            let err = io::Error::last_os_error();
+            // the list grew between the two calls
+            if err.raw_os_error() == Some(libc::ERANGE) {
+                continue;
+            }
+            return Err(err);
+        }
+        buf.truncate(size as usize);
+
+        // the names are a sequence of null terminated strings
+        return Ok(buf
+            .split(|&b| b == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect());
+    }
+}
+
+/// The value of an extended attribute
+#[cfg(target_os = "linux")]
+pub fn get(path: &Path, name: &str) -> io::Result<Vec<u8>> {
+    let path = c_path(path)?;
+    let name = CString::new(name).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    loop {
+        let size = unsafe { libc::lgetxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut buf = vec![0u8; size as usize];
+        let size = unsafe { libc::lgetxattr(path.as_ptr(), name.as_ptr(), buf.as_mut_ptr().cast(), buf.len()) };
+        if size < 0 {
+            let err = io::Error::last_os_error();
+            // the value grew between the two calls
+            if err.raw_os_error() == Some(libc::ERANGE) {
+                continue;
+            }
+            return Err(err);
+        }
+        buf.truncate(size as usize);
+        return Ok(buf);
+    }
+}
+
+/// The size in bytes of the value of an extended attribute
+#[cfg(target_os = "linux")]
+pub fn size(path: &Path, name: &str) -> io::Result<usize> {
+    let path = c_path(path)?;
+    let name = CString::new(name).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let size = unsafe { libc::lgetxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(size as usize)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn size(path: &Path, _name: &str) -> io::Result<usize> {
+    c_path(path)?;
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get(path: &Path, _name: &str) -> io::Result<Vec<u8>> {
+    c_path(path)?;
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+/// The security label of a file, from SELinux or else another security module
+pub fn security_context(path: &Path) -> Option<String> {
+    CONTEXT_NAMES.iter().find_map(|name| {
+        let value = get(path, name).ok()?;
+        // labels are usually stored with a terminating null
+        let value = value.strip_suffix(&[0]).unwrap_or(&value);
+        Some(String::from_utf8_lossy(value).into_owned())
+    })
+}
+
+/// Attributes holding POSIX ACLs
+pub const ACL_NAMES: [&str; 2] = ["system.posix_acl_access", "system.posix_acl_default"];
+
+/// Attribute holding the SELinux security context
+pub const SELINUX_NAME: &str = "security.selinux";
+
+/// Attribute holding the capabilities of an executable
+pub const CAPABILITY_NAME: &str = "security.capability";
+
+/// Attributes holding a security label, in order of preference
+const CONTEXT_NAMES: [&str; 3] = [SELINUX_NAME, "security.SMACK64", "security.apparmor"];
+
+#[cfg(not(target_os = "linux"))]
+pub fn list(path: &Path) -> io::Result<Vec<String>> {
+    c_path(path).map(|_| Vec::new())
+}
@@ -1,6 +1,2560 @@
 use assert_cmd::Command;
+use predicates::prelude::PredicateBooleanExt;
 
 #[test]
 fn runs() {
     Command::cargo_bin("listare").unwrap().assert().success();
 }
+
+#[test]
+fn expands_config_aliases() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "[aliases]\nall = [\"-a\"]\nla = [\"@all\", \"-x\"]\n").unwrap();
+    std::fs::write(dir.path().join(".hidden"), "").unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LISTARE_CONFIG", &config)
+        .arg("@la")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(".hidden"));
+}
+
+#[test]
+fn rejects_alias_cycles() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "[aliases]\na = [\"@b\"]\nb = [\"@a\"]\n").unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LISTARE_CONFIG", &config)
+        .arg("@a")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("alias cycle detected: a -> b -> a"));
+}
+
+#[test]
+fn sorts_by_width() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["ccc", "a", "bb", "dd"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("COLUMNS", "80")
+        .args(["-C", "--sort=width"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("a  bb  dd  ccc\n");
+
+    // by the width shown, markers and ambiguous characters included
+    std::fs::remove_file(dir.path().join("bb")).unwrap();
+    std::fs::remove_file(dir.path().join("ccc")).unwrap();
+    std::fs::create_dir(dir.path().join("bb")).unwrap();
+    std::fs::write(dir.path().join("§§§§"), "").unwrap();
+    std::fs::write(dir.path().join("eeeee"), "").unwrap();
+    let list = |flags: &[&str]| {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .env("COLUMNS", "80")
+            .args(["-x", "--sort=width", "--color=never"])
+            .args(flags)
+            .arg(dir.path())
+            .assert()
+            .success()
+    };
+    list(&["-F"]).stdout("a  dd  bb/  §§§§  eeeee\n");
+    list(&["--ambiguous-wide"]).stdout("a  bb  dd  eeeee  §§§§\n");
+}
+
+#[test]
+fn annotates_entries_with_command_output() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("file"), "first\nsecond\n").unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("-l")
+        .arg("--annotate-cmd")
+        .arg("cat {}")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(" first file\n"));
+
+    // any way of asking for the long format will do
+    for flags in [&["-o"][..], &["-g"], &["-n"], &["--full-time"], &["--format=long"], &["-1", "-l"]] {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .args(flags)
+            .args(["--annotate-cmd", "cat {}"])
+            .arg(dir.path())
+            .assert()
+            .success()
+            .stdout(predicates::str::contains(" first file\n"));
+    }
+
+    // a quoted placeholder still stands for the path, however it is named
+    let odd = dir.path().join("it's a \"file\"");
+    std::fs::write(&odd, "odd\n").unwrap();
+    for template in ["cat {}", "cat '{}'", "cat \"{}\""] {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .args(["-l", "--annotate-cmd", template])
+            .arg(&odd)
+            .assert()
+            .success()
+            .stdout(predicates::str::contains(" odd "));
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "-1", "--annotate-cmd", "cat {}"])
+        .arg(dir.path())
+        .assert()
+        .code(2)
+        .stderr(predicates::str::contains("--annotate-cmd can only be used with the long format"));
+}
+
+#[test]
+fn stops_annotation_commands_with_what_they_started() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("file");
+    std::fs::write(&file, "").unwrap();
+
+    // the command prints its line and exits, leaving a process behind on its output
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--annotate-cmd", "sleep 30 & echo $! > {}.pid; echo done"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(" done "));
+    let pid = std::fs::read_to_string(dir.path().join("file.pid")).unwrap();
+    // gone, or a zombie left for init to reap
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim())).unwrap_or_default();
+    assert!(stat.is_empty() || stat.contains(") Z "), "{}", stat);
+}
+
+#[test]
+fn stops_when_max_entries_exceeded() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["a", "b", "c"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("--max-entries=2")
+        .arg(dir.path())
+        .assert()
+        .code(2)
+        .stdout("")
+        .stderr(predicates::str::contains("more than 2 entries"));
+}
+
+#[test]
+fn breaks_ties_between_equivalent_names_by_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    // the same name composed and decomposed, created in both orders
+    for name in ["\u{e9}", "e\u{301}"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LC_ALL", "C")
+        .env("COLUMNS", "80")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with("e\u{301}"));
+}
+
+#[test]
+fn random_sort_is_reproducible_with_seed() {
+    let dir = tempfile::tempdir().unwrap();
+    for i in 0..20 {
+        std::fs::write(dir.path().join(format!("file{}", i)), "").unwrap();
+    }
+
+    let list = || {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .args(["--sort=random", "--seed=42"])
+            .arg(dir.path())
+            .output()
+            .unwrap()
+            .stdout
+    };
+    assert_eq!(list(), list());
+}
+
+#[test]
+fn prints_epoch_timestamps() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    let file = std::fs::File::create(&path).unwrap();
+    let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_123);
+    file.set_modified(mtime).unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--epoch=ms"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(" 1700000000123 "));
+}
+
+#[test]
+fn prints_nanoseconds() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    let file = std::fs::File::create(&path).unwrap();
+    file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::new(1_700_000_000, 5_000))
+        .unwrap();
+
+    let long = |style: &str| {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .arg("-l")
+            .arg(style)
+            .arg(&path)
+            .assert()
+            .success()
+    };
+    long("--time-style=+%s.%N").stdout(predicates::str::contains(" 1700000000.000005000 "));
+    long("--time-style=precise").stdout(predicates::str::is_match(r" \d{4}-\d\d-\d\d \d\d:\d\d:\d\d\.000005000 ").unwrap());
+}
+
+#[test]
+fn prints_times_in_a_time_zone() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    let file = std::fs::File::create(&path).unwrap();
+    file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000))
+        .unwrap();
+
+    let long = |zone: &str| {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .env("TZ", "America/New_York")
+            .args(["-l", "--time-style=long-iso", "--time-zone", zone])
+            .arg(&path)
+            .assert()
+    };
+    long("UTC").success().stdout(predicates::str::contains(" 2023-11-14 22:13 "));
+    if std::path::Path::new("/usr/share/zoneinfo/Asia/Tokyo").exists() {
+        long("Asia/Tokyo").success().stdout(predicates::str::contains(" 2023-11-15 07:13 "));
+    }
+    long("Mars/Olympus_Mons").failure().stderr(predicates::str::contains("unknown time zone: Mars/Olympus_Mons"));
+    // the database's tables are not zones
+    if std::path::Path::new("/usr/share/zoneinfo/zone.tab").exists() {
+        long("zone.tab").failure().stderr(predicates::str::contains("unknown time zone: zone.tab"));
+    }
+}
+
+#[test]
+fn prints_all_times() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    let file = std::fs::File::create(&path).unwrap();
+    let second = |secs| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+    file.set_times(std::fs::FileTimes::new().set_modified(second(1_700_000_000)).set_accessed(second(1_600_000_000)))
+        .unwrap();
+
+    // the change and birth times are those of the test run
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--all-times", "--epoch=s"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r" 0 1700000000 1600000000 \d+ (\d+|\?) /").unwrap());
+}
+
+#[test]
+fn prints_human_readable_sizes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    std::fs::write(&path, vec![0u8; 1536]).unwrap();
+
+    let long = |flag: &str| {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .args(["-l", flag])
+            .arg(&path)
+            .assert()
+            .success()
+    };
+    long("-h").stdout(predicates::str::contains(" 1.5K "));
+    long("--si").stdout(predicates::str::contains(" 1.6k "));
+}
+
+#[test]
+fn uses_two_line_time_style_for_old_and_recent_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let old = dir.path().join("old");
+    std::fs::File::create(&old)
+        .unwrap()
+        .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000))
+        .unwrap();
+    std::fs::write(dir.path().join("recent"), "").unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--time-style=+old\nrecent"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r" old +old\n").unwrap())
+        .stdout(predicates::str::contains(" recent recent\n"));
+}
+
+#[test]
+fn prints_relative_times() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    let three_days = std::time::Duration::from_secs(3 * 24 * 60 * 60 + 60);
+    std::fs::File::create(&path)
+        .unwrap()
+        .set_modified(std::time::SystemTime::now() - three_days)
+        .unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--time-style=relative"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(" 3 days ago "));
+}
+
+#[test]
+fn prints_inode_numbers() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    std::fs::write(&path, "").unwrap();
+    let ino = std::fs::metadata(&path).unwrap().ino();
+
+    for flags in [&["-i"][..], &["-i", "-l"][..]] {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .args(flags)
+            .arg(dir.path())
+            .assert()
+            .success()
+            .stdout(predicates::str::is_match(format!("(?m)^{} ", ino)).unwrap());
+    }
+}
+
+#[test]
+fn aligns_long_format_fields_from_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "[long.align]\nsize = \"left\"\n").unwrap();
+    std::fs::write(dir.path().join("a"), "1").unwrap();
+    std::fs::write(dir.path().join("b"), [0u8; 1000]).unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LISTARE_CONFIG", &config)
+        .arg("-l")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(" 1    "));
+}
+
+#[test]
+fn auto_shrink_drops_a_column_to_fit() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    std::fs::write(&path, "").unwrap();
+
+    let list = |columns: usize| {
+        let output = Command::cargo_bin("listare")
+            .unwrap()
+            .env("COLUMNS", columns.to_string())
+            .args(["-l", "--auto-shrink"])
+            .arg(&path)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let full = list(1000);
+    // one character too narrow for the full line, so only the group is dropped
+    let shrunk = list(full.trim_end().len() - 1);
+    assert_eq!(
+        shrunk.split_whitespace().count(),
+        full.split_whitespace().count() - 1
+    );
+}
+
+#[test]
+fn prints_numeric_ids() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    std::fs::write(&path, "").unwrap();
+    let meta = std::fs::metadata(&path).unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "-n"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!(" 1 {} {} 0 ", meta.uid(), meta.gid())));
+}
+
+#[test]
+fn prints_allocated_blocks() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    std::fs::write(&path, [1u8; 5000]).unwrap();
+    let blocks = std::fs::metadata(&path).unwrap().blocks() * 512;
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("-s")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(format!("{} file\n", blocks.div_ceil(1024)));
+}
+
+#[test]
+fn scales_sizes_by_block_size() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    std::fs::write(&path, [1u8; 3000]).unwrap();
+
+    let long = |args: &[&str], env: Option<(&str, &str)>| {
+        let mut cmd = Command::cargo_bin("listare").unwrap();
+        if let Some((key, value)) = env {
+            cmd.env(key, value);
+        }
+        cmd.arg("-l").args(args).arg(&path).assert().success()
+    };
+    long(&["--block-size=K"], None).stdout(predicates::str::contains(" 3K "));
+    long(&["--block-size=1K"], None).stdout(predicates::str::contains(" 3 "));
+    long(&["--block-size=kB"], None).stdout(predicates::str::contains(" 3kB "));
+    long(&[], Some(("BLOCK_SIZE", "K"))).stdout(predicates::str::contains(" 3K "));
+    long(&[], Some(("BLOCK_SIZE", "garbage"))).stdout(predicates::str::contains(" 3000 "));
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("--block-size=0")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid block size"));
+}
+
+#[test]
+fn marks_groups_the_user_belongs_to() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    std::fs::write(&path, "").unwrap();
+    let gid = std::fs::metadata(&path).unwrap().gid();
+
+    // a new file gets the effective group of its creator
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "-n", "--mark-groups"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!(" {}* 0 ", gid)));
+}
+
+#[test]
+fn prints_total_blocks_for_directories() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut blocks = 0;
+    for name in ["a", "b"] {
+        let path = dir.path().join(name);
+        std::fs::write(&path, [1u8; 5000]).unwrap();
+        blocks += std::fs::metadata(&path).unwrap().blocks();
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--block-size=512"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with(format!("total {}\n", blocks)));
+
+    // 10000 bytes are 20 blocks of 512 bytes, rounded up
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--block-size=512", "--total-apparent"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with(format!("total {} (apparent 20)\n", blocks)));
+
+    // no total for files named on the command line
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("-l")
+        .arg(dir.path().join("a"))
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("total").not());
+}
+
+#[test]
+fn info_reports_on_a_single_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    std::fs::write(&path, "hello").unwrap();
+    std::fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o640)).unwrap();
+    std::os::unix::fs::symlink("file", dir.path().join("link")).unwrap();
+    std::os::unix::fs::symlink("link", dir.path().join("link2")).unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("--info")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Type: regular file\n"))
+        .stdout(predicates::str::contains("Size: 5 bytes"))
+        .stdout(predicates::str::contains("Mode: -rw-r----- (0640)\n"))
+        .stdout(predicates::str::contains("Modify: "));
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("--info")
+        .arg(dir.path().join("link2"))
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Links to: link -> file\n"));
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("--info")
+        .arg(dir.path().join("missing"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("cannot access"));
+}
+
+#[test]
+fn lists_files_named_like_reports() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["info", "shell"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+
+        Command::cargo_bin("listare")
+            .unwrap()
+            .current_dir(dir.path())
+            .arg(name)
+            .assert()
+            .success()
+            .stdout(format!("{}\n", name));
+    }
+}
+
+#[test]
+fn long_format_without_group() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    std::fs::write(&path, "").unwrap();
+    let meta = std::fs::metadata(&path).unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-o", "-n"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!(" 1 {} 0 ", meta.uid())));
+
+    for flags in [&["-l", "-G", "-n"][..], &["-o", "-G", "-n"][..]] {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .args(flags)
+            .arg(&path)
+            .assert()
+            .success()
+            .stdout(predicates::str::contains(format!(" 1 {} 0 ", meta.uid())));
+    }
+
+    // -G alone does not imply a long listing
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("-G")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(format!("{}\n", path.display()));
+}
+
+#[test]
+fn normalizes_symlink_targets() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+    std::fs::create_dir(dir.path().join("x")).unwrap();
+    std::fs::write(dir.path().join("x/y"), "").unwrap();
+    std::os::unix::fs::symlink("../../x/./y", dir.path().join("a/b/link")).unwrap();
+
+    let long = |args: &[&std::ffi::OsStr]| {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .arg("-l")
+            .args(args)
+            .arg(dir.path().join("a/b"))
+            .assert()
+            .success()
+    };
+    long(&[]).stdout(predicates::str::ends_with("link -> ../../x/./y\n"));
+    long(&["--normalize-targets".as_ref()]).stdout(predicates::str::ends_with("link -> ../../x/y\n"));
+    long(&["--relative-to".as_ref(), dir.path().as_os_str()])
+        .stdout(predicates::str::ends_with("link -> x/y\n"));
+}
+
+#[test]
+fn long_format_without_owner() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    std::fs::write(&path, "").unwrap();
+    let meta = std::fs::metadata(&path).unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-g", "-n"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!(" 1 {} 0 ", meta.gid())));
+
+    // -g and -o together leave neither
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-g", "-o"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(" 1 0 "));
+}
+
+#[test]
+fn counts_skipped_hidden_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["visible", ".hidden", ".also-hidden"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("--count-skipped")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("visible\n(+2 hidden)\n");
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["--count-skipped", "-a"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("hidden)").not());
+}
+
+#[test]
+fn reports_missing_paths_and_lists_the_rest() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("file"), "").unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["missing", "file"])
+        .assert()
+        .code(2)
+        .stdout("file\n")
+        .stderr("listare: cannot access 'missing': No such file or directory\n");
+}
+
+#[test]
+fn shows_dangling_symlink_targets() {
+    let dir = tempfile::tempdir().unwrap();
+    std::os::unix::fs::symlink("nowhere", dir.path().join("link")).unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("-l")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::ends_with("link -> nowhere\n"));
+}
+
+#[test]
+fn renders_timestamps_before_the_epoch_and_in_the_future() {
+    use std::time::{Duration, SystemTime};
+
+    let dir = tempfile::tempdir().unwrap();
+    let year = 365 * 24 * 60 * 60;
+    let times = [
+        ("old", SystemTime::UNIX_EPOCH - Duration::from_secs(10 * year)),
+        ("future", SystemTime::now() + Duration::from_secs(100 * year)),
+    ];
+    for (name, time) in times {
+        let file = std::fs::File::create(dir.path().join(name)).unwrap();
+        file.set_modified(time).unwrap();
+    }
+
+    // future timestamps are not recent, so show a year rather than a time
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--time-style=+%Y"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r" 21\d\d future\n").unwrap())
+        .stdout(predicates::str::is_match(r" 19[56]\d old\n").unwrap());
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--epoch"])
+        .arg(dir.path().join("old"))
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!(" -{} ", 10 * year)));
+}
+
+#[test]
+fn shows_and_sorts_by_the_chosen_time() {
+    use std::fs::FileTimes;
+    use std::time::{Duration, SystemTime};
+
+    let dir = tempfile::tempdir().unwrap();
+    let day = Duration::from_secs(24 * 60 * 60);
+    let base = SystemTime::UNIX_EPOCH + 10_000 * day;
+    // modification order is aaa, bbb, ccc while access order is the reverse
+    for (i, name) in ["aaa", "bbb", "ccc"].iter().enumerate() {
+        let file = std::fs::File::create(dir.path().join(name)).unwrap();
+        let times = FileTimes::new()
+            .set_modified(base + day * i as u32)
+            .set_accessed(base + day * (10 - i as u32));
+        file.set_times(times).unwrap();
+    }
+
+    let list = |args: &[&str]| {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .args(["-x"])
+            .args(args)
+            .arg(dir.path())
+            .assert()
+            .success()
+    };
+    list(&["-t"]).stdout("ccc  bbb  aaa\n");
+    list(&["--sort=time"]).stdout("ccc  bbb  aaa\n");
+    list(&["-t", "--time=atime"]).stdout("aaa  bbb  ccc\n");
+    // the last of -t and --sort wins
+    list(&["-t", "--sort=name"]).stdout("aaa  bbb  ccc\n");
+
+    let accessed = (base + day * 10).duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--epoch", "--time=access"])
+        .arg(dir.path().join("aaa"))
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!(" {} ", accessed)));
+}
+
+#[test]
+fn separates_groups_when_sorting_by_size() {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, size) in [("tiny", 5), ("small", 50), ("also-small", 60), ("large", 5000)] {
+        std::fs::write(dir.path().join(name), vec![0u8; size]).unwrap();
+    }
+
+    let list = |args: &[&str]| {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .arg("-S")
+            .args(args)
+            .arg(dir.path())
+            .assert()
+            .success()
+    };
+    list(&["-x", "--separators"])
+        .stdout("-- 1.0k to 10k --\nlarge\n\n-- 10 to 100 --\nalso-small  small\n\n-- under 10 bytes --\ntiny\n");
+    list(&["-x", "--separators=blank"]).stdout("large\n\nalso-small  small\n\ntiny\n");
+    list(&["--zero", "--separators"])
+        .stdout("-- 1.0k to 10k --\0large\0\0-- 10 to 100 --\0also-small\0small\0\0-- under 10 bytes --\0tiny\0");
+    list(&["-l", "--separators=blank"])
+        .stdout(predicates::str::is_match(r"^total \d+\n.* large\n\n.* also-small\n.* small\n\n.* tiny\n$").unwrap());
+}
+
+#[test]
+fn supports_named_time_styles() {
+    use std::time::{Duration, SystemTime};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    let file = std::fs::File::create(&path).unwrap();
+    // 2001-09-09 01:46:40 UTC, an old file
+    file.set_modified(SystemTime::UNIX_EPOCH + Duration::new(1_000_000_000, 5)).unwrap();
+
+    let long = |args: &[&str], env: &[(&str, &str)]| {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .env("TZ", "UTC")
+            .envs(env.iter().copied())
+            .arg("-l")
+            .args(args)
+            .arg(&path)
+            .assert()
+    };
+    long(&["--time-style=full-iso"], &[])
+        .success()
+        .stdout(predicates::str::contains(" 2001-09-09 01:46:40.000000005 +0000 "));
+    long(&["--time-style=long-iso"], &[]).success().stdout(predicates::str::contains(" 2001-09-09 01:46 "));
+    long(&["--time-style=iso"], &[]).success().stdout(predicates::str::contains(" 2001-09-09  "));
+    long(&[], &[("TIME_STYLE", "long-iso")]).success().stdout(predicates::str::contains(" 2001-09-09 01:46 "));
+    // the command line wins over the environment
+    long(&["--time-style=+%Y"], &[("TIME_STYLE", "long-iso")])
+        .success()
+        .stdout(predicates::str::contains(" 2001 "));
+    // posix- styles are ignored in the POSIX locale
+    long(&["--time-style=posix-long-iso"], &[("LC_ALL", "C")])
+        .success()
+        .stdout(predicates::str::contains(" Sep  9  2001 "));
+    long(&[], &[("TIME_STYLE", "bogus")]).code(2).stderr(predicates::str::contains("invalid time style"));
+}
+
+// the expected order is the C library's, the UCA puts emoji before letters
+#[cfg(not(feature = "no-c-locale"))]
+#[test]
+fn aligns_columns_of_emoji_and_flag_names() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["🇯🇵 flag", "abcdefgh", "❤\u{fe0f} love", "plain", "👨\u{200d}👩\u{200d}👧 fams", "z"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("COLUMNS", "30")
+        .arg("-C")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("abcdefgh  z        🇯🇵 flag\nplain     ❤\u{fe0f} love  👨\u{200d}👩\u{200d}👧 fams\n");
+}
+
+#[test]
+fn full_time_implies_long_format_with_full_iso_times() {
+    use std::time::{Duration, SystemTime};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    let file = std::fs::File::create(&path).unwrap();
+    file.set_modified(SystemTime::UNIX_EPOCH + Duration::new(1_000_000_000, 123_456_789)).unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("TZ", "UTC")
+        .arg("--full-time")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"^-rw.* 0 2001-09-09 01:46:40\.123456789 \+0000 ").unwrap());
+
+    // whichever of --full-time and --time-style comes last wins
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("TZ", "UTC")
+        .args(["--full-time", "--time-style=+%Y"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(" 0 2001 "));
+}
+
+#[cfg(feature = "no-c-locale")]
+#[test]
+fn collates_with_uca_regardless_of_locale() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["Bob", "alice", "Émile", "eve", "zed"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LC_ALL", "C")
+        .arg("-x")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("alice  Bob  Émile  eve  zed\n");
+}
+
+#[test]
+fn picks_link_color_for_the_background() {
+    let dir = tempfile::tempdir().unwrap();
+    std::os::unix::fs::symlink("/", dir.path().join("link")).unwrap();
+
+    let list = |args: &[&str], colorfgbg: &str| {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .env_remove("LS_COLORS")
+            .env("CLICOLOR_FORCE", "1")
+            .env("COLORFGBG", colorfgbg)
+            .args(args)
+            .arg(dir.path())
+            .assert()
+            .success()
+    };
+    let cyan = "\x1b[1;36mlink\x1b[0m\n";
+    let magenta = "\x1b[1;35mlink\x1b[0m\n";
+    list(&["--background=dark"], "0;15").stdout(cyan);
+    list(&["--background=light"], "15;0").stdout(magenta);
+    list(&[], "15;0").stdout(cyan);
+    list(&[], "0;15").stdout(magenta);
+}
+
+#[test]
+fn marks_files_with_extended_attributes() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let plain = dir.path().join("plain");
+    let tagged = dir.path().join("tagged");
+    std::fs::write(&plain, "").unwrap();
+    std::fs::write(&tagged, "").unwrap();
+
+    let path = std::ffi::CString::new(tagged.as_os_str().as_bytes()).unwrap();
+    let set = unsafe { libc::setxattr(path.as_ptr(), c"user.test".as_ptr(), b"1".as_ptr().cast(), 1, 0) };
+    if set != 0 {
+        // the filesystem holding temporary files has no user attributes
+        return;
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("-l")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"\n-[-rwx]{9} +1 .* plain\n").unwrap())
+        .stdout(predicates::str::is_match(r"\n-[-rwx]{9}@ 1 .* tagged\n").unwrap());
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("-l@")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r" plain\n-[-rwx]{9}@ 1 .* tagged\n\tuser\.test\t   1\n$").unwrap());
+}
+
+#[test]
+fn hides_control_characters_in_attribute_names() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("file");
+    std::fs::write(&file, "").unwrap();
+
+    let path = std::ffi::CString::new(file.as_os_str().as_bytes()).unwrap();
+    let set = unsafe { libc::setxattr(path.as_ptr(), c"user.\x1b[2J".as_ptr(), b"1".as_ptr().cast(), 1, 0) };
+    if set != 0 {
+        // the filesystem holding temporary files has no user attributes
+        return;
+    }
+
+    let list = |flag: &str| {
+        Command::cargo_bin("listare").unwrap().args(["-l@", flag]).arg(&file).assert().success()
+    };
+    list("-q").stdout(predicates::str::ends_with("\n\tuser.?[2J\t   1\n"));
+    list("--show-control-chars").stdout(predicates::str::ends_with("\n\tuser.\x1b[2J\t   1\n"));
+}
+
+#[test]
+fn prints_file_capabilities() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let plain = dir.path().join("plain");
+    let server = dir.path().join("server");
+    std::fs::write(&plain, "").unwrap();
+    std::fs::write(&server, "").unwrap();
+
+    // revision 2 with the effective flag, permitting cap_net_bind_service
+    let value: Vec<u8> = [0x0200_0001u32, 1 << 10, 0, 0, 0].iter().flat_map(|w| w.to_le_bytes()).collect();
+    let path = std::ffi::CString::new(server.as_os_str().as_bytes()).unwrap();
+    let set = unsafe {
+        libc::setxattr(path.as_ptr(), c"security.capability".as_ptr(), value.as_ptr().cast(), value.len(), 0)
+    };
+    if set != 0 {
+        // setting capabilities needs CAP_SETFCAP
+        return;
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--capabilities"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r" - +0 .* plain\n").unwrap())
+        .stdout(predicates::str::is_match(r" cap_net_bind_service\+ep +0 .* server\n").unwrap());
+}
+
+#[test]
+fn prints_security_context() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("file");
+    std::fs::write(&file, "").unwrap();
+
+    // the label depends on the security module, "?" when there is none
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("-lZ")
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"^-[-rwx]{9}\S? +1 \S+ +\S+ +\S+ +0 .*file\n$").unwrap());
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("--context")
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"^\S+ \S*file\n$").unwrap());
+}
+
+#[test]
+fn dirs_first_page_lists_only_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("alpha")).unwrap();
+    std::fs::write(dir.path().join("beta"), "").unwrap();
+    std::os::unix::fs::symlink("alpha", dir.path().join("gamma")).unwrap();
+    std::os::unix::fs::symlink("missing", dir.path().join("delta")).unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("COLUMNS", "80")
+        .args(["--dirs-first-page", "-l"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("alpha  gamma\n");
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn prints_device_numbers() {
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "/dev/null"])
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"^c[-rwx]{9}\S? +1 \S+ +\S+ +1, 3 .* /dev/null\n$").unwrap());
+}
+
+#[test]
+fn prints_shell_integration() {
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["--print-shell-init", "bash"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("alias ll='listare -l'\n"))
+        .stdout(predicates::str::contains("lcd() {"))
+        .stdout(predicates::str::contains("complete -F _listare").or(predicates::str::contains("-F _listare listare")));
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["--print-shell-init", "tcsh"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn prints_directory_sizes() {
+    let dir = tempfile::tempdir().unwrap();
+    let size = std::fs::metadata(dir.path()).unwrap().len();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("-ld")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(format!(r"^d[-rwx]{{9}}\S? +\d+ \S+ +\S+ +{} ", size)).unwrap());
+}
+
+#[test]
+fn prints_octal_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("script");
+    std::fs::write(&file, "").unwrap();
+    std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o4750)).unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--octal-permissions"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"^4750 -rwsr-x---\S? +1 ").unwrap());
+}
+
+#[test]
+fn prints_what_the_user_can_do() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("notes");
+    std::fs::write(&file, "").unwrap();
+    std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--can"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"^-rw-r-----\S? rw- +1 ").unwrap());
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--can=exec,write"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"^-rw-r-----\S? -w +1 ").unwrap());
+}
+
+#[test]
+fn cuts_long_owner_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("notes");
+    std::fs::write(&file, "").unwrap();
+    let owner = users::get_current_username().unwrap().to_string_lossy().into_owned();
+    if owner.chars().count() < 3 {
+        return;
+    }
+    let cut = format!("{}…", owner.chars().next().unwrap());
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--no-group", "--max-owner-width=2"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(format!(r"^-\S+ +1 {} +0 ", cut)).unwrap());
+}
+
+#[test]
+fn lays_out_long_records_side_by_side() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("first"), "").unwrap();
+    std::fs::write(dir.path().join("second"), "").unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("COLUMNS", "200")
+        .args(["-l", "--grid"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"^total 0\n-\S+ +1 .* first  -\S+ +1 .* second\n$").unwrap());
+
+    // a record per line when two do not fit
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("COLUMNS", "40")
+        .args(["-l", "--grid"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"^total 0\n-\S+ +1 .* first\n-\S+ +1 .* second\n$").unwrap());
+}
+
+#[test]
+fn prints_unknown_owners_by_number() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("orphan");
+    std::fs::write(&file, "").unwrap();
+    // only root can give files away
+    if std::os::unix::fs::chown(&file, Some(54321), Some(54322)).is_err() {
+        return;
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("-l")
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"^-[-rwx]{9}\S? +1 54321 54322 0 ").unwrap());
+}
+
+#[test]
+fn zero_columns_never_wraps() {
+    let dir = tempfile::tempdir().unwrap();
+    let names: Vec<String> = (0..40).map(|i| format!("file{:02}", i)).collect();
+    for name in &names {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("COLUMNS", "0")
+        .arg("-C")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(format!("{}\n", names.join("  ")));
+
+    // an unusable value falls back to 80 columns
+    let output = Command::cargo_bin("listare")
+        .unwrap()
+        .env("COLUMNS", "wide")
+        .arg("-C")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().count() > 1);
+    assert!(stdout.lines().all(|line| line.len() <= 80 && !line.ends_with(' ')));
+}
+
+#[test]
+fn colors_names_by_config_rules() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let config = config_dir.path().join("config.toml");
+    std::fs::write(
+        &config,
+        "[[colors]]\nglob = \"*.lock\"\nstyle = \"dimmed\"\n\n\
+         [[colors]]\nglob = \"target/\"\nstyle = \"bright black\"\n\n\
+         [[colors]]\nglob = \"*.txt\"\nstyle = \"red\"\n\n\
+         [[colors]]\nglob = \"target.*\"\nstyle = \"bold yellow\"\n",
+    )
+    .unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("Cargo.lock"), "").unwrap();
+    std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+    std::fs::write(dir.path().join("target.txt"), "").unwrap();
+    std::fs::create_dir(dir.path().join("target")).unwrap();
+
+    // the last matching rule wins
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LISTARE_CONFIG", &config)
+        .env("CLICOLOR_FORCE", "1")
+        .env("COLUMNS", "1")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("\x1b[2mCargo.lock\x1b[0m\n\x1b[31mnotes.txt\x1b[0m\n\x1b[90mtarget\x1b[0m\n\x1b[1;33mtarget.txt\x1b[0m\n");
+}
+
+#[test]
+fn colors_names_by_theme_file() {
+    let themes = tempfile::tempdir().unwrap();
+    let theme = themes.path().join("theme.toml");
+    std::fs::write(&theme, "[types]\ndirectory = \"bold 33\"\n\n[extensions]\nmd = \"#ff8700 on 236\"\n").unwrap();
+    let config = themes.path().join("config.toml");
+    std::fs::write(&config, "theme = \"theme.toml\"\n").unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("notes.md"), "").unwrap();
+    std::fs::write(dir.path().join("main.rs"), "").unwrap();
+    std::fs::create_dir(dir.path().join("src")).unwrap();
+
+    // kinds the theme leaves out keep their built-in colors
+    let expected = "\x1b[33mmain.rs\x1b[0m\n\x1b[38;2;255;135;0;48;5;236mnotes.md\x1b[0m\n\x1b[1;38;5;33msrc\x1b[0m\n";
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env_remove("LS_COLORS")
+        .env("CLICOLOR_FORCE", "1")
+        .args(["-1", "--theme"])
+        .arg(&theme)
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(expected);
+
+    // a theme named in the config file is relative to it
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env_remove("LS_COLORS")
+        .env("LISTARE_CONFIG", &config)
+        .env("CLICOLOR_FORCE", "1")
+        .arg("-1")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(expected);
+
+    std::fs::write(&theme, "[types]\nfolder = \"red\"\n").unwrap();
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("--theme")
+        .arg(&theme)
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("types: unknown type folder"));
+}
+
+#[test]
+fn colors_names_by_owner() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let config = config_dir.path().join("config.toml");
+    std::fs::write(&config, "[[colors]]\nowner = \"other\"\nstyle = \"bold red\"\n").unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("mine"), "").unwrap();
+    std::fs::write(dir.path().join("theirs"), "").unwrap();
+    // only root can give files away
+    if std::os::unix::fs::chown(dir.path().join("theirs"), Some(54321), None).is_err() {
+        return;
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LISTARE_CONFIG", &config)
+        .env("CLICOLOR_FORCE", "1")
+        .env("COLUMNS", "1")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("mine\n\x1b[1;31mtheirs\x1b[0m\n");
+}
+
+#[test]
+fn translates_messages_for_the_locale() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing");
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env_remove("LC_ALL")
+        .env_remove("LC_MESSAGES")
+        .env("LANG", "fr_FR.UTF-8")
+        .arg(&missing)
+        .assert()
+        .code(2)
+        .stderr(predicates::str::starts_with(format!("listare: impossible d'accéder à '{}': ", missing.display())));
+
+    // LC_ALL overrides LANG
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LC_ALL", "C")
+        .env("LANG", "fr_FR.UTF-8")
+        .arg(&missing)
+        .assert()
+        .code(2)
+        .stderr(predicates::str::starts_with(format!("listare: cannot access '{}': ", missing.display())));
+
+    // headers, separators and the errors that end a listing too
+    std::fs::write(dir.path().join("file"), "12345678901").unwrap();
+    let german = |args: &[&str]| {
+        Command::cargo_bin("listare").unwrap().env("LC_ALL", "de_DE.UTF-8").args(args).arg(dir.path()).assert()
+    };
+    german(&["-o", "--header", "--time-style=+%Y", "-d"])
+        .success()
+        .stdout(predicates::str::starts_with("Rechte     Links Besitzer Größe Datum Name\n"));
+    german(&["-1", "-S", "--separators=labeled"]).success().stdout("-- 10 bis 100 --\nfile\n");
+    german(&["--max-entries=0"]).code(2).stderr("Auflistung abgebrochen: mehr als 0 Einträge\n");
+}
+
+#[test]
+fn ignores_the_environment_when_plain() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["a", "B", "é"] {
+        std::fs::write(dir.path().join(name), "x").unwrap();
+    }
+    let missing = dir.path().join("missing");
+
+    let plain = |plain_env: Option<&str>| {
+        let mut command = Command::cargo_bin("listare").unwrap();
+        command
+            .env("LANG", "fr_FR.UTF-8")
+            .env("LC_ALL", "fr_FR.UTF-8")
+            .env("TIME_STYLE", "full-iso")
+            .env("BLOCK_SIZE", "1M")
+            .env("COLUMNS", "2")
+            .env("CLICOLOR_FORCE", "1");
+        match plain_env {
+            Some(value) => command.env("LISTARE_PLAIN", value),
+            None => command.arg("--plain"),
+        };
+        command.arg("-C").arg(dir.path()).arg(&missing).assert()
+    };
+
+    for plain_env in [None, Some("1")] {
+        plain(plain_env)
+            .code(2)
+            .stdout(format!("{}:\nB  a  é\n", dir.path().display()))
+            .stderr(predicates::str::starts_with(format!("listare: cannot access '{}': ", missing.display())));
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LISTARE_PLAIN", "1")
+        .env("TIME_STYLE", "full-iso")
+        .env("BLOCK_SIZE", "1M")
+        .arg("-l")
+        .arg(dir.path().join("a"))
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r" 1 \d{4}-\d\d-\d\d \d\d:\d\d .*/a\n$").unwrap());
+}
+
+/// Run listare on `dir` with `setup` applied to the child's descriptors before it starts
+fn run_with_descriptors(dir: &std::path::Path, setup: fn() -> std::io::Result<()>) -> std::process::Output {
+    use std::os::unix::process::CommandExt;
+
+    let mut command = std::process::Command::new(assert_cmd::cargo::cargo_bin("listare"));
+    command.arg("-l").arg(dir).stdout(std::process::Stdio::inherit());
+    unsafe { command.pre_exec(setup) };
+    command.output().unwrap()
+}
+
+#[test]
+fn survives_a_closed_stdout() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("file"), "").unwrap();
+
+    let output = run_with_descriptors(dir.path(), || {
+        unsafe { libc::close(1) };
+        Ok(())
+    });
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn writes_to_stdout_shared_with_stderr() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("file"), "").unwrap();
+
+    let output = run_with_descriptors(dir.path(), || {
+        unsafe { libc::dup2(2, 1) };
+        Ok(())
+    });
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).ends_with(" file\n"));
+}
+
+#[test]
+fn stops_quietly_when_the_reader_goes_away() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("file"), "").unwrap();
+
+    // a pipe whose read end is closed before anything is written
+    let output = run_with_descriptors(dir.path(), || {
+        let mut fds = [0; 2];
+        unsafe {
+            libc::pipe(fds.as_mut_ptr());
+            libc::close(fds[0]);
+            libc::dup2(fds[1], 1);
+        }
+        Ok(())
+    });
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn reports_write_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("file"), "").unwrap();
+
+    use assert_cmd::assert::OutputAssertExt;
+
+    std::process::Command::new(assert_cmd::cargo::cargo_bin("listare"))
+        .arg(dir.path())
+        .stdout(std::fs::OpenOptions::new().write(true).open("/dev/full").unwrap())
+        .assert()
+        .code(2)
+        .stderr("listare: write error: No space left on device\n");
+}
+
+#[test]
+fn prints_a_header_aligned_with_the_columns() {
+    use predicates::Predicate;
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("notes"), "hello").unwrap();
+
+    let output = Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--header"])
+        .arg(dir.path().join("notes"))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(predicates::str::is_match(r"^Permissions Links Owner +Group +Size Date +Name$")
+        .unwrap()
+        .eval(lines[0]));
+    // titles end where right aligned columns end and start where left aligned ones start
+    assert_eq!(lines[0].find("Size").unwrap() + "Size".len(), lines[1].find(" 5 ").unwrap() + 2);
+    assert_eq!(lines[0].find("Name"), lines[1].find('/'));
+}
+
+#[test]
+fn warns_about_insecure_files() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    for (name, mode) in [("open", 0o666), ("plain", 0o644), ("suid", 0o4755)] {
+        let path = dir.path().join(name);
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LC_ALL", "C")
+        .env("COLUMNS", "1")
+        .arg("--warn-insecure")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("open!\nplain\nsuid!\n")
+        .stderr("listare: 2 world-writable or setuid files\n");
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--warn-insecure"])
+        .arg(dir.path().join("suid"))
+        .assert()
+        .success()
+        .stdout(predicates::str::ends_with("/suid!\n"));
+}
+
+#[test]
+fn sorts_by_inode() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["delta", "alpha", "charlie", "bravo"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    let output = Command::cargo_bin("listare")
+        .unwrap()
+        .env("COLUMNS", "1")
+        .args(["-i", "--sort=inode"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let inodes: Vec<u64> = stdout
+        .lines()
+        .map(|line| line.split_whitespace().next().unwrap().parse().unwrap())
+        .collect();
+    assert_eq!(inodes.len(), 4);
+    assert!(inodes.windows(2).all(|pair| pair[0] < pair[1]), "{}", stdout);
+}
+
+#[test]
+fn prints_entries_from_a_format_string() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("notes");
+    std::fs::write(&file, "hello").unwrap();
+    std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o644)).unwrap();
+    std::os::unix::fs::symlink("notes", dir.path().join("link")).unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["--format-string", r"%N\t%s\t%a %A"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("'link' -> 'notes'\t5\t777 lrwxrwxrwx\n'notes'\t5\t644 -rw-r--r--\n");
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["--format-string", "%n %q"])
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid format string directive: %q"));
+}
+
+#[test]
+fn implies_the_long_format() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("file"), "").unwrap();
+    let uid = users::get_current_uid().to_string();
+
+    let list = |flags: &[&str]| {
+        let output = Command::cargo_bin("listare").unwrap().args(flags).arg(dir.path()).output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let long = |flags: &[&str]| list(flags).starts_with("total ");
+
+    for flags in [&["-l"][..], &["-n"], &["-o"], &["-g"], &["--full-time"], &["-x", "-l"], &["-xn"], &["-lxo"]] {
+        assert!(long(flags), "{:?}", flags);
+    }
+    // -x after every long format flag asks for a grid
+    for flags in [&[][..], &["-l", "-x"], &["-lx"], &["-n", "-x"], &["-gox"]] {
+        assert_eq!(list(flags), "file\n", "{:?}", flags);
+    }
+
+    // -n alone lists numeric IDs in the long format
+    assert!(list(&["-n"]).contains(&format!(" {} ", uid)));
+}
+
+#[test]
+fn lists_in_batches_within_max_memory() {
+    let dir = tempfile::tempdir().unwrap();
+    let names: Vec<String> = (0..50).map(|i| format!("file{:02}", i)).collect();
+    for name in &names {
+        std::fs::write(dir.path().join(name), [b'x'; 5000]).unwrap();
+    }
+
+    let list = |flags: &[&str]| {
+        let output = Command::cargo_bin("listare")
+            .unwrap()
+            .env("LC_ALL", "C")
+            .args(flags)
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    };
+
+    let (stdout, stderr) = list(&["--format-string", "%n", "--max-memory", "1M"]);
+    assert_eq!(stdout, names.join("\n") + "\n");
+    assert_eq!(stderr, "");
+
+    // every entry is still listed once, a few at a time in the order the directory gives them
+    let batched = format!("listare: listing '{}' in unsorted batches to stay within --max-memory\n", dir.path().display());
+    let (stdout, stderr) = list(&["--format-string", "%n", "--max-memory", "2K"]);
+    assert_eq!(stdout, list(&["--format-string", "%n", "--sort=none"]).0);
+    assert_eq!(stderr, batched);
+
+    // the long format still has the total of the whole directory, after the entries
+    let (stdout, stderr) = list(&["-l", "--max-memory", "2K"]);
+    let (unsorted, _) = list(&["-l", "--sort=none"]);
+    let (total, entries) = unsorted.split_once('\n').unwrap();
+    assert!(total.starts_with("total ") && total != "total 0", "{}", unsorted);
+    assert_eq!(stdout, format!("{}{}\n", entries, total));
+    assert_eq!(stderr, batched);
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["--max-memory", "human-readable"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("not a number of bytes"));
+}
+
+#[test]
+fn reports_timing() {
+    let expected = if cfg!(feature = "memprofile") {
+        r"^listare: listed in \d+\.\d{3} s, peak memory \d+ bytes\n$"
+    } else {
+        r"^listare: listed in \d+\.\d{3} s\n$"
+    };
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("--timing")
+        .arg("src")
+        .assert()
+        .success()
+        .stderr(predicates::str::is_match(expected).unwrap());
+}
+
+#[test]
+fn strips_a_prefix_from_named_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+    std::fs::write(dir.path().join("a/b/deep.txt"), "").unwrap();
+    std::fs::write(dir.path().join("top.txt"), "").unwrap();
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["--format-string", "%n", "--strip-prefix"])
+        .arg(dir.path())
+        .arg(dir.path().join("a/b/deep.txt"))
+        .arg(dir.path().join("top.txt"))
+        .assert()
+        .success()
+        .stdout("a/b/deep.txt\ntop.txt\n");
+
+    // paths outside the prefix are printed as they are
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["--strip-prefix", "/nonexistent", "Cargo.toml"])
+        .assert()
+        .success()
+        .stdout("Cargo.toml\n");
+
+    // headings of directories are stripped too
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("--strip-prefix")
+        .arg(dir.path().join("a"))
+        .arg(dir.path().join("a"))
+        .arg(dir.path().join("a/b"))
+        .assert()
+        .success()
+        .stdout(".:\nb\n\nb:\ndeep.txt\n");
+}
+
+#[test]
+fn counts_hard_links_once_in_the_total() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("file"), vec![b'x'; 5000]).unwrap();
+    std::fs::hard_link(dir.path().join("file"), dir.path().join("link")).unwrap();
+    std::os::unix::fs::symlink("file", dir.path().join("symlink")).unwrap();
+
+    let total = |flags: &[&str]| {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .args(["-l", "--total-apparent", "--block-size=1"])
+            .args(flags)
+            .arg(dir.path())
+            .assert()
+            .success()
+    };
+    // the symlink is 4 bytes long, and is not followed
+    total(&[]).stdout(predicates::str::is_match(r"^total \d+ \(apparent 10004\)\n").unwrap());
+    total(&["--count-links-once"]).stdout(predicates::str::is_match(r"^total \d+ \(apparent 5004\)\n").unwrap());
+}
+
+#[test]
+fn totals_the_sizes_of_directory_trees() {
+    let dir = tempfile::tempdir().unwrap();
+    let sub = dir.path().join("sub");
+    std::fs::create_dir_all(sub.join("inner")).unwrap();
+    std::fs::write(sub.join("file"), vec![b'x'; 5000]).unwrap();
+    std::fs::write(sub.join("inner/deep"), vec![b'x'; 100]).unwrap();
+    std::fs::hard_link(sub.join("file"), sub.join("link")).unwrap();
+    // a loop that would never end if symlinks were followed
+    std::os::unix::fs::symlink("..", sub.join("up")).unwrap();
+    // a second link to the file, outside of sub
+    std::fs::hard_link(sub.join("file"), dir.path().join("other")).unwrap();
+
+    let len = |path: &std::path::Path| std::fs::symlink_metadata(path).unwrap().len();
+    let dirs = len(&sub) + len(&sub.join("inner"));
+    let sizes = |flags: &[&str], paths: &[std::path::PathBuf]| {
+        let output = Command::cargo_bin("listare")
+            .unwrap()
+            .env("LC_ALL", "C")
+            .args(["-l", "--total-size", "--block-size=1"])
+            .args(flags)
+            .args(paths)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        stdout
+            .lines()
+            .filter(|line| !line.starts_with("total "))
+            .map(|line| line.split_whitespace().nth(4).unwrap().parse::<u64>().unwrap())
+            .collect::<Vec<_>>()
+    };
+    let listed = [dir.path().to_path_buf()];
+
+    // other, then sub
+    assert_eq!(sizes(&[], &listed), [5000, dirs + 5000 + 5000 + 2 + 100]);
+    // the file is counted in sub once, and not at all after its link outside of sub
+    assert_eq!(sizes(&["--count-links-once"], &listed), [5000, dirs + 2 + 100]);
+    // the first tree listed counts it
+    let named = [sub.clone(), dir.path().join("other")];
+    assert_eq!(sizes(&["-d", "--sort=none", "--count-links-once"], &named), [dirs + 5000 + 2 + 100, 5000]);
+    // . is its whole tree, while .. keeps its own size rather than walking the parent
+    let inner = [sub.join("inner")];
+    let inner_dir = len(&sub.join("inner"));
+    assert_eq!(sizes(&["-a"], &inner), [inner_dir + 100, len(&sub), 100]);
+    assert_eq!(sizes(&["-d"], &[sub.join("inner/..")]), [dirs + 5000 + 5000 + 2 + 100]);
+}
+
+#[test]
+fn ends_lines_with_nul() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("new\nline"), "").unwrap();
+    std::fs::write(dir.path().join("plain"), "").unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub/inner"), "").unwrap();
+
+    let list = |flags: &[&str]| {
+        let output = Command::cargo_bin("listare").unwrap().args(flags).arg(dir.path()).output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // one entry per line, with control characters left as they are
+    assert_eq!(list(&["--zero", "-q", "--show-control-chars"]), "new\nline\0plain\0sub\0");
+    assert_eq!(list(&["--zero"]), "new\nline\0plain\0sub\0");
+
+    let long = list(&["--zero", "-l"]);
+    assert!(!long.contains("\nplain") && !long.ends_with('\n'));
+    assert_eq!(long.matches('\0').count(), 4);
+
+    let output = Command::cargo_bin("listare")
+        .unwrap()
+        .arg("--zero")
+        .arg(dir.path().join("sub"))
+        .arg(dir.path().join("plain"))
+        .output()
+        .unwrap();
+    let expected = format!("{}\0\0{}:\0inner\0", dir.path().join("plain").display(), dir.path().join("sub").display());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[cfg(feature = "fs-attributes")]
+#[test]
+fn prints_filesystem_attributes() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("file"), "").unwrap();
+
+    // a temporary directory is rarely on btrfs, and a plain file has no attributes anyway
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["-l", "--header", "--fs-attributes"])
+        .arg(dir.path().join("file"))
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"^Permissions +Links +Owner +Group +Attributes +Size .*\n-rw\S* +1 +\S+ +\S+ +- +0 ").unwrap());
+}
+
+#[test]
+fn needs_a_terminal_to_choose() {
+    use std::os::unix::process::CommandExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("file"), "").unwrap();
+
+    // a new session has no controlling terminal
+    let mut command = std::process::Command::new(assert_cmd::cargo::cargo_bin("listare"));
+    command.arg("--choose").arg(dir.path());
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        })
+    };
+    let output = command.output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "listare: --choose needs a terminal\n");
+}
+
+/// Run listare in `dir` with a new pseudo-terminal of `rows` by `cols` as its controlling terminal,
+/// and stdout on it too if `stdout_on_terminal`, typing `keys` once it first writes to the terminal.
+/// Returns what was printed to stdout, and what was written to the terminal.
+fn run_on_terminal(
+    args: &[&str],
+    dir: &std::path::Path,
+    (rows, cols): (u16, u16),
+    stdout_on_terminal: bool,
+    keys: &[u8],
+) -> (String, String) {
+    use std::io::{Read, Write};
+    use std::os::fd::FromRawFd;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::process::CommandExt;
+
+    let master = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+    assert!(master >= 0 && unsafe { libc::grantpt(master) } == 0 && unsafe { libc::unlockpt(master) } == 0);
+    let mut name = [0 as libc::c_char; 64];
+    assert_eq!(unsafe { libc::ptsname_r(master, name.as_mut_ptr(), name.len()) }, 0);
+    let size = libc::winsize { ws_row: rows, ws_col: cols, ws_xpixel: 0, ws_ypixel: 0 };
+    assert_eq!(unsafe { libc::ioctl(master, libc::TIOCSWINSZ, &size) }, 0);
+    let mut master = unsafe { std::fs::File::from_raw_fd(master) };
+    // held open until the child exits, so that the terminal stays up while the child reopens it
+    let slave = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(std::ffi::OsStr::from_bytes(unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) }.to_bytes()))
+        .unwrap();
+
+    let mut command = std::process::Command::new(assert_cmd::cargo::cargo_bin("listare"));
+    command.args(args).current_dir(dir).env_remove("COLUMNS").stdin(std::process::Stdio::null());
+    unsafe {
+        command.pre_exec(move || {
+            // the first terminal a session leader opens becomes its controlling terminal
+            libc::setsid();
+            let slave = libc::open(name.as_ptr(), libc::O_RDWR);
+            if slave < 0 || libc::ioctl(slave, libc::TIOCSCTTY, 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if stdout_on_terminal {
+                libc::dup2(slave, 1);
+            }
+            libc::close(slave);
+            Ok(())
+        })
+    };
+    let child = command.stdout(std::process::Stdio::piped()).spawn().unwrap();
+
+    let mut screen = vec![0u8; 4096];
+    let mut count = 0;
+    if !keys.is_empty() {
+        count = master.read(&mut screen).unwrap();
+        master.write_all(keys).unwrap();
+    }
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    drop(slave);
+    // the terminal reports an error once the last of its users has closed it
+    screen.truncate(count);
+    let mut buf = [0u8; 4096];
+    while let Ok(count @ 1..) = master.read(&mut buf) {
+        screen.extend_from_slice(&buf[..count]);
+    }
+    (String::from_utf8_lossy(&output.stdout).into_owned(), String::from_utf8_lossy(&screen).into_owned())
+}
+
+#[test]
+fn takes_the_width_from_the_terminal() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["alpha", "bravo", "charlie", "delta"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    let (_, screen) = run_on_terminal(&["--color=never"], dir.path(), (24, 20), true, b"");
+    assert_eq!(screen, "alpha  charlie\r\nbravo  delta\r\n");
+}
+
+#[test]
+fn cuts_names_wider_than_the_terminal() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["short", "a-very-long-name"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    let (_, screen) = run_on_terminal(&["--color=never"], dir.path(), (24, 10), true, b"");
+    assert_eq!(screen, "a-very-lo…\r\nshort\r\n");
+    // but not when they are read by another program
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("COLUMNS", "10")
+        .arg("-C")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("a-very-long-name\nshort\n");
+}
+
+#[test]
+fn chooses_on_the_terminal() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["apple", "banana", "cherry"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    // a terminal of one row only has room for the first entry
+    let (stdout, screen) = run_on_terminal(&["--choose"], dir.path(), (1, 80), false, b"\r");
+    assert_eq!(stdout, "./apple\n");
+    assert!(screen.contains("apple") && !screen.contains("banana"), "{:?}", screen);
+}
+
+#[test]
+fn prints_json_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("say \"hi\"");
+    std::fs::write(&file, "hello").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&file)
+        .unwrap()
+        .set_modified(std::time::UNIX_EPOCH + std::time::Duration::new(1_700_000_000, 250_000_000))
+        .unwrap();
+    std::os::unix::fs::symlink("nowhere", dir.path().join("link")).unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub/inner"), "").unwrap();
+
+    for sort in ["--sort=name", "--sort=none"] {
+        let output = Command::cargo_bin("listare")
+            .unwrap()
+            .args(["--json-lines", sort])
+            .arg(dir.path())
+            .arg(dir.path().join("sub"))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let mut lines: Vec<&str> = stdout.lines().collect();
+        lines.sort();
+
+        // one object per line, without headings or blank lines
+        assert_eq!(lines.len(), 4, "{}", stdout);
+        assert!(lines.iter().all(|line| line.starts_with("{\"name\":") && line.ends_with('}')));
+        assert!(lines[0].contains(r#""name":"inner","#));
+        assert!(lines[1].contains(r#""name":"link","#));
+        assert!(lines[1].contains(r#""type":"symbolic link","#));
+        assert!(lines[1].ends_with(r#","target":"nowhere"}"#));
+        assert!(lines[2].contains(r#""name":"say \"hi\"","#));
+        assert!(lines[2].contains(r#","size":5,"#));
+        assert!(lines[2].contains(r#","modified":1700000000.250000000,"#));
+        assert!(lines[2].ends_with(r#","target":null}"#));
+        assert!(lines[3].contains(r#""name":"sub","path":"#));
+        assert!(lines[3].contains(r#""type":"directory","#));
+    }
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .args(["--json-lines", "--format-string", "%n"])
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("cannot be used with"));
+}
+
+#[test]
+fn sorts_json_lines_by_bytes_in_any_locale() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["a", "B", "\u{e9}"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    let output = Command::cargo_bin("listare")
+        .unwrap()
+        .env("LANG", "fr_FR.UTF-8")
+        .env("LC_ALL", "fr_FR.UTF-8")
+        .arg("--json-lines")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let names: Vec<&str> = stdout.lines().filter_map(|line| line.split('"').nth(3)).collect();
+    assert_eq!(names, ["B", "a", "\u{e9}"]);
+}
+
+#[test]
+fn hides_control_characters_in_names() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("evil\x1b[2Jname"), "").unwrap();
+    std::os::unix::fs::symlink("target\x07\rspoof", dir.path().join("link")).unwrap();
+
+    let list = |args: &[&str]| {
+        Command::cargo_bin("listare")
+            .unwrap()
+            .env("COLUMNS", "1")
+            .args(args)
+            .arg(dir.path())
+            .assert()
+            .success()
+    };
+    list(&["-q"]).stdout("evil?[2Jname\nlink\n");
+    list(&["-lq"])
+        .stdout(predicates::str::contains(" evil?[2Jname\n"))
+        .stdout(predicates::str::contains(" link -> target??spoof\n"));
+    list(&["-q", "--format-string", r"%n\t%N"]).stdout("evil?[2Jname\t'evil?[2Jname'\nlink\t'link' -> 'target??spoof'\n");
+    // the last of the two options wins
+    list(&["-q", "--show-control-chars"]).stdout("evil\x1b[2Jname\nlink\n");
+}
+
+#[test]
+fn prints_a_markdown_table() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a|b"), "hello").unwrap();
+    std::os::unix::fs::symlink("a|b", dir.path().join("link")).unwrap();
+
+    let output = Command::cargo_bin("listare")
+        .unwrap()
+        .env("CLICOLOR_FORCE", "1")
+        .args(["--markdown", "-o", "--time-style=+T"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let table = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", table);
+    assert!(lines[0].starts_with("| Permissions ") && lines[0].contains("| Size |"));
+    // sizes are right aligned
+    assert!(lines[1].starts_with("| ---") && lines[1].contains("---: |"));
+    assert!(lines[2].ends_with("| a\\|b         |"), "{}", lines[2]);
+    assert!(lines[2].contains("|    5 |"));
+    assert!(lines[3].ends_with("| link -> a\\|b |"), "{}", lines[3]);
+    assert!(!table.contains('\x1b') && !table.contains("total"));
+}
+
+#[test]
+fn selects_the_layout_by_format_or_flag() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["alpha", "beta", "gamma", "delta", "epsilon"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    let list = |flags: &[&str]| {
+        let output = Command::cargo_bin("listare")
+            .unwrap()
+            .env("COLUMNS", "24")
+            .env("LC_ALL", "C")
+            .args(flags)
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", flags);
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let vertical = "alpha  delta    gamma\nbeta   epsilon\n";
+    let across = "alpha    beta   delta\nepsilon  gamma\n";
+    let single = "alpha\nbeta\ndelta\nepsilon\ngamma\n";
+    let commas = "alpha, beta, delta,\nepsilon, gamma\n";
+    for (flags, expected) in [
+        // stdout is not a terminal
+        (&[][..], single),
+        (&["--format=vertical"], vertical),
+        (&["-C"], vertical),
+        (&["--format=across"], across),
+        (&["--format=horizontal"], across),
+        (&["-x"], across),
+        (&["--format=single-column"], single),
+        (&["-1"], single),
+        (&["--format=commas"], commas),
+        (&["-m"], commas),
+        // the last layout option wins
+        (&["-1", "-x"], across),
+        (&["-x", "--format=commas"], commas),
+        (&["--format=long", "-1"], single),
+        (&["-l1"], single),
+        (&["-1", "--format=vertical"], vertical),
+    ] {
+        assert_eq!(list(flags), expected, "{:?}", flags);
+    }
+    for flags in [&["--format=long"][..], &["--format=verbose"], &["-1", "-l"], &["-m", "--format=long"]] {
+        assert!(list(flags).starts_with("total "), "{:?}", flags);
+    }
+}
+
+#[test]
+fn writes_the_listing_to_a_file() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub/file"), "").unwrap();
+    let listing = dir.path().join("listing.txt");
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("CLICOLOR_FORCE", "1")
+        .arg("--output")
+        .arg(&listing)
+        .arg(dir.path().join("sub"))
+        .assert()
+        .success()
+        .stdout("");
+    // colors are left out of the file even when forced for stdout
+    assert_eq!(std::fs::read_to_string(&listing).unwrap(), "file\n");
+
+    Command::cargo_bin("listare")
+        .unwrap()
+        .arg("--output")
+        .arg(dir.path().join("missing/listing.txt"))
+        .arg(dir.path())
+        .assert()
+        .code(1)
+        .stderr(predicates::str::contains("cannot create"));
+}
+
+#[test]
+fn pads_grid_columns_with_tabs() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["a", "bbbbbbbbbbbb", "ccccccccccccccc", "d"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    let list = |flags: &[&str]| {
+        let output = Command::cargo_bin("listare")
+            .unwrap()
+            .env("COLUMNS", "30")
+            .env("LC_ALL", "C")
+            .args(flags)
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(list(&["-x", "-T", "8"]), "a\t\t bbbbbbbbbbbb\nccccccccccccccc  d\n");
+    assert_eq!(list(&["-x", "--tabsize=0"]), "a                bbbbbbbbbbbb\nccccccccccccccc  d\n");
+    assert_eq!(list(&["-x"]), list(&["-x", "-T0"]));
+}
+
+#[test]
+fn overrides_the_width() {
+    let dir = tempfile::tempdir().unwrap();
+    let names: Vec<String> = (0..12).map(|i| format!("file{:02}", i)).collect();
+    for name in &names {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    let list = |flags: &[&str]| {
+        let output = Command::cargo_bin("listare")
+            .unwrap()
+            .env("COLUMNS", "200")
+            .arg("-x")
+            .args(flags)
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(list(&[]), format!("{}\n", names.join("  ")));
+    assert_eq!(list(&["-w", "0"]), format!("{}\n", names.join("  ")));
+    assert_eq!(list(&["--width=30"]), format!("{}\n", names.chunks(4).map(|row| row.join("  ")).collect::<Vec<_>>().join("\n")));
+    // the width asked for applies even to otherwise plain output
+    assert_eq!(list(&["--plain", "-w", "0"]), format!("{}\n", names.join("  ")));
+}
+
+#[test]
+fn colors_when_asked() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+
+    let colored = |env: &[(&str, &str)], flags: &[&str]| {
+        let output = Command::cargo_bin("listare")
+            .unwrap()
+            .env_remove("CLICOLOR_FORCE")
+            .env_remove("CLICOLOR")
+            .env_remove("NO_COLOR")
+            .envs(env.iter().copied())
+            .args(flags)
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap().contains('\x1b')
+    };
+
+    // stdout is a pipe
+    assert!(!colored(&[], &[]));
+    assert!(!colored(&[], &["--color=auto"]));
+    assert!(colored(&[], &["--color=always"]));
+    assert!(colored(&[], &["--color"]));
+    assert!(!colored(&[("CLICOLOR_FORCE", "1")], &["--color=never"]));
+    // options that turn colors off by default give way to --color
+    assert!(!colored(&[("CLICOLOR_FORCE", "1")], &["--zero"]));
+    assert!(colored(&[], &["--zero", "--color=always"]));
+    assert!(colored(&[], &["--plain", "--color=always"]));
+
+    // the conventions of the environment come after --color
+    assert!(colored(&[("CLICOLOR_FORCE", "1")], &[]));
+    assert!(!colored(&[("CLICOLOR_FORCE", "0")], &[]));
+    assert!(!colored(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")], &[]));
+    assert!(colored(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "")], &[]));
+    assert!(colored(&[("NO_COLOR", "1")], &["--color=always"]));
+    assert!(colored(&[("CLICOLOR", "0")], &["--color"]));
+    assert!(!colored(&[("CLICOLOR_FORCE", "1")], &["--plain"]));
+}
+
+#[test]
+fn colors_files_by_extension_without_ls_colors() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("backup.tar.gz"), "").unwrap();
+    std::fs::create_dir(dir.path().join("photos.zip")).unwrap();
+
+    let list = |ls_colors: Option<&str>| {
+        let mut command = Command::cargo_bin("listare").unwrap();
+        match ls_colors {
+            Some(value) => command.env("LS_COLORS", value),
+            None => command.env_remove("LS_COLORS"),
+        };
+        let output = command
+            .env("LC_ALL", "C")
+            .args(["--color=always", "-1", "--background=dark"])
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let listing = list(None);
+    assert!(listing.contains("\x1b[1;31mbackup.tar.gz\x1b[0m"), "{:?}", listing);
+    // directories keep their own color whatever their name
+    assert!(listing.contains("\x1b[1;34mphotos.zip\x1b[0m"), "{:?}", listing);
+    // LS_COLORS is someone's own choice of colors
+    assert!(list(Some("di=01;34")).starts_with("backup.tar.gz\n"));
+}
+
+#[test]
+fn links_names_to_their_files() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["a", "bb", "c d"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    let list = |flags: &[&str]| {
+        let output = Command::cargo_bin("listare")
+            .unwrap()
+            .env("LC_ALL", "C")
+            .args(["-C", "-w", "9"])
+            .args(flags)
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let linked = list(&["--hyperlink"]);
+    let path = dir.path().display();
+    assert!(linked.starts_with("\x1b]8;;file://"), "{:?}", linked);
+    // the links take no columns, so the grid is laid out as without them
+    assert!(linked.contains(&format!("{}/a\x1b\\a\x1b]8;;\x1b\\   \x1b]8;;", path)), "{:?}", linked);
+    assert!(linked.contains(&format!("{}/c%20d\x1b\\c d\x1b]8;;\x1b\\\n", path)), "{:?}", linked);
+    assert_eq!(list(&[]), "a   c d\nbb\n");
+    assert_eq!(list(&["--hyperlink=auto"]), list(&[]));
+}
+
+#[test]
+fn puts_icons_before_names() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.rs"), "").unwrap();
+    std::fs::write(dir.path().join("b"), "").unwrap();
+    std::fs::create_dir(dir.path().join("src")).unwrap();
+
+    // each icon and its space take two columns, so "a.rs" takes six
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LC_ALL", "C")
+        .args(["--icons", "-C", "-w", "13"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("\u{e7a8} a.rs  \u{e5ff} src\n\u{f15b} b\n");
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LC_ALL", "C")
+        .args(["--icons", "-C", "-w", "12"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("\u{e7a8} a.rs\n\u{f15b} b\n\u{e5ff} src\n");
+}
+
+#[test]
+fn takes_icons_from_the_config() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let config = config_dir.path().join("config.toml");
+    std::fs::write(&config, "[icons.names]\njustfile = \"J\"\n\n[icons.extensions]\n\".RS\" = \"🦀\"\n").unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["justfile", "main.rs", "notes"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    // a wide glyph takes two columns, so the line takes all 31
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LISTARE_CONFIG", &config)
+        .env("LC_ALL", "C")
+        .args(["--icons", "-x", "-w", "31"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("J justfile  🦀 main.rs  \u{f15b} notes\n");
+
+    std::fs::write(&config, "[icons.extensions]\nrs = 1\n").unwrap();
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LISTARE_CONFIG", &config)
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("icons.extensions.rs must be a string"));
+}
+
+#[test]
+fn colors_sizes_and_times_on_a_scale() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("big"), vec![0; 1000]).unwrap();
+    std::fs::write(dir.path().join("small"), "").unwrap();
+    let day = std::time::Duration::from_secs(24 * 60 * 60);
+    let old = std::fs::File::options().write(true).open(dir.path().join("big")).unwrap();
+    old.set_modified(std::time::SystemTime::now() - 30 * day).unwrap();
+
+    let list = |scale: &str| {
+        let output = Command::cargo_bin("listare")
+            .unwrap()
+            .args(["-l", "--color=always", scale])
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    // the largest and the newest are the brightest, the others a third as bright
+    let sizes = list("--color-scale=size");
+    assert!(sizes.contains(" \x1b[38;2;95;255;95m1000\x1b[0m "), "{:?}", sizes);
+    assert!(sizes.contains(" \x1b[38;2;33;89;33m0\x1b[0m "), "{:?}", sizes);
+    assert!(!sizes.contains("\x1b[38;2;95;175;255m"), "{:?}", sizes);
+    let ages = list("--color-scale=age");
+    assert!(ages.contains(" \x1b[38;2;33;61;89m"), "{:?}", ages);
+    assert!(ages.contains(" \x1b[38;2;95;175;255m"), "{:?}", ages);
+    assert!(!ages.contains("\x1b[38;2;95;255;95m"), "{:?}", ages);
+    assert_eq!(list("--color-scale"), list("--color-scale=all"));
+}
+
+#[test]
+fn classifies_names_by_file_type() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("d")).unwrap();
+    std::fs::write(dir.path().join("e"), "").unwrap();
+    std::fs::set_permissions(dir.path().join("e"), std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::fs::write(dir.path().join("f"), "").unwrap();
+    std::os::unix::fs::symlink("f", dir.path().join("l")).unwrap();
+    assert!(std::process::Command::new("mkfifo").arg(dir.path().join("p")).status().unwrap().success());
+    let _socket = std::os::unix::net::UnixListener::bind(dir.path().join("s")).unwrap();
+
+    // without the indicators, four columns would fit in ten
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LC_ALL", "C")
+        .args(["-F", "-x", "-w", "10"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("d/  e*  f\nl@  p|  s=\n");
+}
+
+#[test]
+fn appends_slashes_to_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("d")).unwrap();
+    std::fs::write(dir.path().join("f"), "").unwrap();
+    std::os::unix::fs::symlink("f", dir.path().join("l")).unwrap();
+
+    let list = |flags: &[&str]| {
+        let output = Command::cargo_bin("listare")
+            .unwrap()
+            .env("LC_ALL", "C")
+            .args(["-1"])
+            .args(flags)
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(list(&["-p"]), "d/\nf\nl\n");
+    // the last of -p and -F wins
+    assert_eq!(list(&["-p", "-F"]), "d/\nf\nl@\n");
+    assert_eq!(list(&["-F", "-p"]), "d/\nf\nl\n");
+}
+
+#[test]
+fn colors_names_by_ls_colors() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("d")).unwrap();
+    std::fs::write(dir.path().join("f.tar"), "").unwrap();
+    std::fs::write(dir.path().join("notes"), "").unwrap();
+    std::os::unix::fs::symlink("d", dir.path().join("l")).unwrap();
+    std::os::unix::fs::symlink("missing", dir.path().join("m")).unwrap();
+
+    let list = |ls_colors: &str| {
+        let output = Command::cargo_bin("listare")
+            .unwrap()
+            .env("LS_COLORS", ls_colors)
+            .env("LC_ALL", "C")
+            .args(["--color=always", "-1"])
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(
+        list("di=01;33:ln=35:*.tar=01;31"),
+        "\x1b[01;33md\x1b[0m\n\x1b[01;31mf.tar\x1b[0m\n\x1b[35ml\x1b[0m\n\x1b[35mm\x1b[0m\nnotes\n"
+    );
+    // with ln=target a link takes the color of what it points to, and a dangling one that of or
+    assert_eq!(
+        list("di=01;33:ln=target:or=31"),
+        "\x1b[01;33md\x1b[0m\nf.tar\n\x1b[01;33ml\x1b[0m\n\x1b[31mm\x1b[0m\nnotes\n"
+    );
+}
+
+#[test]
+fn colors_files_with_several_links_by_ls_colors() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.txt"), "").unwrap();
+    std::fs::hard_link(dir.path().join("a.txt"), dir.path().join("b.txt")).unwrap();
+    std::fs::write(dir.path().join("c.txt"), "").unwrap();
+
+    // shared inodes stand out even from the colors of their names
+    Command::cargo_bin("listare")
+        .unwrap()
+        .env("LS_COLORS", "mh=44;37:*.txt=33")
+        .env("LC_ALL", "C")
+        .args(["--color=always", "-1"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("\x1b[44;37ma.txt\x1b[0m\n\x1b[44;37mb.txt\x1b[0m\n\x1b[33mc.txt\x1b[0m\n");
+}
+
+#[test]
+fn shows_hidden_files_without_the_implied_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join(".hidden"), "").unwrap();
+    std::fs::write(dir.path().join("shown"), "").unwrap();
+
+    let list = |flags: &[&str]| {
+        let output = Command::cargo_bin("listare")
+            .unwrap()
+            .env("LC_ALL", "C")
+            .arg("-1")
+            .args(flags)
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(list(&[]), "shown\n");
+    assert_eq!(list(&["-A"]), ".hidden\nshown\n");
+    assert_eq!(list(&["-a"]), ".\n..\n.hidden\nshown\n");
+    // the last of -a and -A wins
+    assert_eq!(list(&["-a", "-A"]), list(&["--almost-all"]));
+    assert_eq!(list(&["-A", "-a"]), list(&["--all"]));
+}
@@ -0,0 +1,109 @@
+//! Robustness tests that make filesystem calls fail through `LISTARE_FAULTS`
+//! Run with `cargo test --features fault-injection`.
+#![cfg(feature = "fault-injection")]
+
+use assert_cmd::Command;
+use predicates::prelude::PredicateBooleanExt;
+
+/// A directory holding `dir/{a,b,bad}` and `secret/c`, and a link `dir/link -> a`
+fn tree() -> tempfile::TempDir {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::create_dir(root.path().join("dir")).unwrap();
+    std::fs::create_dir(root.path().join("secret")).unwrap();
+    for name in ["dir/a", "dir/b", "dir/bad", "secret/c"] {
+        std::fs::write(root.path().join(name), "").unwrap();
+    }
+    std::os::unix::fs::symlink("a", root.path().join("dir/link")).unwrap();
+    root
+}
+
+fn listare(root: &tempfile::TempDir, faults: &str) -> Command {
+    let mut cmd = Command::cargo_bin("listare").unwrap();
+    cmd.current_dir(root.path()).env("LISTARE_FAULTS", faults);
+    cmd
+}
+
+const FLAG_SETS: [&[&str]; 4] = [&[], &["-l"], &["-l", "-i", "-s"], &["-d", "-l"]];
+
+#[test]
+fn unreadable_directory_is_reported_and_others_are_listed() {
+    let root = tree();
+    for errno in ["EACCES", "ENOENT", "EIO"] {
+        listare(&root, &format!("readdir:{}:secret", errno))
+            .args(["dir", "secret"])
+            .assert()
+            .code(2)
+            .stdout(predicates::str::contains("dir:\n"))
+            .stdout(predicates::str::contains("bad"))
+            .stderr(predicates::str::starts_with("listare: cannot open directory 'secret': "));
+    }
+}
+
+#[test]
+fn inaccessible_named_path_is_reported_and_others_are_listed() {
+    let root = tree();
+    for flags in FLAG_SETS {
+        listare(&root, "lstat:EACCES:secret")
+            .args(flags)
+            .args(["secret", "dir/a"])
+            .assert()
+            .code(2)
+            .stdout(predicates::str::contains("dir/a"))
+            .stderr("listare: cannot access 'secret': Permission denied\n");
+    }
+}
+
+#[test]
+fn inaccessible_entry_is_reported_as_a_minor_error() {
+    let root = tree();
+    for flags in &FLAG_SETS[..3] {
+        listare(&root, "lstat:EIO:bad")
+            .args(*flags)
+            .arg("dir")
+            .assert()
+            .code(1)
+            .stdout(predicates::str::contains("b").and(predicates::str::contains("bad").not()))
+            .stderr("listare: cannot access 'dir/bad': Input/output error\n");
+    }
+}
+
+#[test]
+fn unreadable_link_does_not_stop_the_listing() {
+    let root = tree();
+    listare(&root, "readlink:EIO:link")
+        .args(["-l", "dir"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("link -> ?\n"))
+        .stdout(predicates::str::contains(" bad\n"));
+}
+
+#[test]
+fn link_target_that_cannot_be_stat_is_shown() {
+    let root = tree();
+    for faults in ["stat:EIO:a", "lstat:ENOENT:dir/a"] {
+        listare(&root, faults)
+            .args(["-l", "dir/link"])
+            .assert()
+            .success()
+            .stdout(predicates::str::ends_with("dir/link -> a\n"));
+    }
+}
+
+#[test]
+fn every_fault_leaves_a_clean_exit() {
+    let root = tree();
+    for op in ["lstat", "stat", "readdir", "readlink"] {
+        for errno in ["EACCES", "ENOENT", "EIO"] {
+            for flags in FLAG_SETS {
+                let assert = listare(&root, &format!("{}:{}:", op, errno))
+                    .args(flags)
+                    .args(["dir", "secret", "dir/link"])
+                    .assert();
+                // a panic exits with 101
+                let code = assert.get_output().status.code();
+                assert!(matches!(code, Some(0..=2)), "{} {} {:?}: {:?}", op, errno, flags, code);
+            }
+        }
+    }
+}